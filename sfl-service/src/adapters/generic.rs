@@ -0,0 +1,111 @@
+use adapters::parse_verdict;
+use adapters::TestDataAdapter;
+use adapters::TestRecord;
+use serde_json;
+
+///Adapter for a JSON list of test records, e.g. `[{"id": 1, "verdict": "failed", "covered": ["a", "b"]}]`
+pub struct GenericJsonAdapter {}
+
+impl TestDataAdapter for GenericJsonAdapter {
+    fn parse(&self, input: &str) -> Result<Vec<TestRecord>, String> {
+        serde_json::from_str(input).map_err(|e| e.to_string())
+    }
+}
+
+///Adapter for CSV with the columns `test id, verdict, covered components`, where the covered components are separated by semicolons.
+/// A header line is optional.
+pub struct GenericCsvAdapter {}
+
+impl TestDataAdapter for GenericCsvAdapter {
+    fn parse(&self, input: &str) -> Result<Vec<TestRecord>, String> {
+        let mut records = vec![];
+        for (line_number, line) in input.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields = split_csv_line(line);
+            if fields.len() < 2 {
+                return Err(format!("Expected at least a test id and a verdict on line {}", line_number + 1));
+            }
+            let id = match fields[0].trim().parse::<i64>() {
+                Ok(id) => id,
+                Err(_) if line_number == 0 => continue, //The header
+                Err(e) => return Err(format!("Invalid test id {:?} on line {}: {}", fields[0], line_number + 1, e))
+            };
+            let covered = fields.get(2).map(|it| it.split(';').map(|component| component.trim().to_string()).filter(|component| !component.is_empty()).collect::<Vec<String>>()).unwrap_or(vec![]);
+            records.push(TestRecord::new(id, parse_verdict(&fields[1]), covered, None));
+        }
+        Ok(records)
+    }
+}
+
+///Split a line of CSV in its fields, where fields can be quoted with double quotes
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    quoted = false;
+                }
+            } else {
+                field.push(c);
+            }
+        } else if c == '"' {
+            quoted = true;
+        } else if c == ',' {
+            fields.push(field.clone());
+            field.clear();
+        } else {
+            field.push(c);
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::test_case::TestResult;
+    use models::error_signature::normalise_error_message;
+
+    #[test]
+    fn check_csv_adapter() {
+        //Given
+        let csv = "id,verdict,covered\n1,passed,a;b\n2,failed,\"b;c,d\"\n3,failed,\n";
+
+        //When
+        let records = GenericCsvAdapter {}.parse(csv).unwrap();
+
+        //Then
+        assert_eq!(vec![
+            TestRecord::new(1, TestResult::passed, vec!["a".to_string(), "b".to_string()], None),
+            TestRecord::new(2, TestResult::failed, vec!["b".to_string(), "c,d".to_string()], None),
+            TestRecord::new(3, TestResult::failed, vec![], None),
+        ], records);
+    }
+
+    #[test]
+    fn check_json_adapter() {
+        //Given
+        let json = r#"[{"id": 1, "verdict": "passed", "covered": ["a"]}, {"id": 2, "verdict": "failed", "covered": ["a", "b"], "error_message": "b broke"}]"#;
+
+        //When
+        let matrix = GenericJsonAdapter {}.to_coverage_matrix(json).unwrap();
+        let test_cases = GenericJsonAdapter {}.to_test_cases(json).unwrap();
+
+        //Then
+        assert_eq!(vec![1, 2], *matrix.test_ids());
+        assert_eq!(vec![false, true], matrix.error_vector);
+        assert_eq!(vec![false, true], matrix.data["b"]);
+        assert_eq!(vec![None, Some(normalise_error_message("b broke"))], matrix.error_signature_vector());
+        assert_eq!(vec!["a".to_string(), "b".to_string()], test_cases[1].get_steps(false));
+    }
+}
@@ -0,0 +1,230 @@
+use adapters::TestDataAdapter;
+use adapters::TestRecord;
+use models::test_case::TestResult;
+use std::collections::HashMap;
+
+///Adapter for JUnit XML reports. Every `testcase` element is a test, which fails when it contains a `failure` or `error` element. Skipped tests are ignored.
+/// The covered components are given as properties of the test case, either as a `covered_component` property per component or as one comma separated `covered_components` property:
+///
+/// ```xml
+/// <testcase name="login" id="1">
+///     <properties><property name="covered_components" value="a,b"/></properties>
+///     <failure message="expected b"/>
+/// </testcase>
+/// ```
+///
+/// Test cases without a numeric id attribute get an id that is derived from their `classname` and `name`, so the same test gets the same id in every import.
+pub struct JUnitAdapter {}
+
+impl TestDataAdapter for JUnitAdapter {
+    fn parse(&self, input: &str) -> Result<Vec<TestRecord>, String> {
+        let mut records: Vec<TestRecord> = vec![];
+        //How often a test name occurred in the report, so tests with the same name get different ids
+        let mut occurrences: HashMap<String, usize> = HashMap::new();
+        let mut current: Option<TestRecord> = None;
+        let mut skipped = false;
+        for tag in parse_tags(input)? {
+            match (tag.name.as_str(), tag.closing) {
+                ("testcase", false) => {
+                    let id = match tag.attributes.get("id").and_then(|it| it.parse::<i64>().ok()) {
+                        Some(id) => id,
+                        None => {
+                            let name = format!("{}.{}", tag.attributes.get("classname").map(|it| it.as_str()).unwrap_or(""), tag.attributes.get("name").map(|it| it.as_str()).unwrap_or(""));
+                            let occurrence = occurrences.entry(name.clone()).or_insert(0);
+                            *occurrence += 1;
+                            stable_id(&name, *occurrence)
+                        }
+                    };
+                    current = Some(TestRecord::new(id, TestResult::passed, vec![], None));
+                    skipped = false;
+                    if tag.self_closing {
+                        records.push(current.take().unwrap());
+                    }
+                },
+                ("testcase", true) => {
+                    if let Some(record) = current.take() {
+                        if !skipped {
+                            records.push(record);
+                        }
+                    }
+                },
+                ("failure", false) | ("error", false) => {
+                    if let Some(ref mut record) = current {
+                        record.verdict = if tag.name == "failure" { TestResult::failed } else { TestResult::error };
+                        record.error_message = tag.attributes.get("message").cloned();
+                    }
+                },
+                ("skipped", false) => skipped = true,
+                ("property", false) => {
+                    if let Some(ref mut record) = current {
+                        let value = tag.attributes.get("value").cloned().unwrap_or(String::new());
+                        match tag.attributes.get("name").map(|it| it.as_str()) {
+                            Some("covered_component") => record.covered.push(value),
+                            Some("covered_components") => record.covered.append(&mut value.split(',').map(|it| it.trim().to_string()).filter(|it| !it.is_empty()).collect::<Vec<String>>()),
+                            _ => {}
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+        Ok(records)
+    }
+}
+
+///Derive a positive id from the full name of a test and how often the name occurred before in the report (starting at 1).
+/// The FNV-1a hash is used, because it does not depend on the version of Rust or on the run, so an import of the same report gives the same ids.
+fn stable_id(name: &str, occurrence: usize) -> i64 {
+    let key = if occurrence > 1 { format!("{}#{}", name, occurrence) } else { name.to_string() };
+    let hash = key.bytes().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    (hash & i64::max_value() as u64) as i64
+}
+
+///A start or end tag of an XML document
+struct XmlTag {
+    name: String,
+    attributes: HashMap<String, String>,
+    closing: bool,
+    self_closing: bool,
+}
+
+///Find all tags in an XML document, skipping comments, CDATA sections, processing instructions and declarations.
+/// This is not a full XML parser, but it is enough for the structure of JUnit reports.
+fn parse_tags(xml: &str) -> Result<Vec<XmlTag>, String> {
+    let mut tags = vec![];
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let skip_until = if rest.starts_with("<!--") {
+            Some("-->")
+        } else if rest.starts_with("<![CDATA[") {
+            Some("]]>")
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            Some(">")
+        } else {
+            None
+        };
+        if let Some(terminator) = skip_until {
+            let end = rest.find(terminator).ok_or(format!("Unterminated {:?}", rest.chars().take(20).collect::<String>()))?;
+            rest = &rest[end + terminator.len()..];
+            continue;
+        }
+
+        let end = find_tag_end(rest).ok_or(format!("Unterminated tag {:?}", rest.chars().take(20).collect::<String>()))?;
+        let tag = parse_tag(&rest[1..end]);
+        rest = &rest[end + 1..];
+        tags.push(tag);
+    }
+    Ok(tags)
+}
+
+///Find the index of the `>` that closes the tag at the start of the input, ignoring `>` in attribute values
+fn find_tag_end(input: &str) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    for (index, c) in input.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {},
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '>' => return Some(index),
+            None => {}
+        }
+    }
+    None
+}
+
+///Parse the content of a tag (without the angle brackets)
+fn parse_tag(content: &str) -> XmlTag {
+    let closing = content.starts_with('/');
+    let self_closing = content.ends_with('/');
+    let content = content.trim_matches('/').trim();
+    let name_end = content.find(char::is_whitespace).unwrap_or(content.len());
+    XmlTag {
+        name: content[..name_end].to_string(),
+        attributes: parse_attributes(&content[name_end..]),
+        closing,
+        self_closing,
+    }
+}
+
+///Parse the attributes of a tag, e.g. `name="a" value='b'`
+fn parse_attributes(input: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = input.trim();
+    while let Some(equals) = rest.find('=') {
+        let key = rest[..equals].trim().to_string();
+        let value_part = rest[equals + 1..].trim_left();
+        let quote = match value_part.chars().next() {
+            Some(q) if q == '"' || q == '\'' => q,
+            _ => break
+        };
+        match value_part[1..].find(quote) {
+            Some(end) => {
+                attributes.insert(key, decode_entities(&value_part[1..end + 1]));
+                rest = value_part[end + 2..].trim_left();
+            },
+            None => break
+        }
+    }
+    attributes
+}
+
+///Replace the predefined XML entities by their characters
+fn decode_entities(value: &str) -> String {
+    value.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn check_junit_adapter() {
+        //Given
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+    <!-- generated by a test tool -->
+    <testsuite name="suite" tests="4">
+        <testcase name="first" id="10">
+            <properties>
+                <property name="covered_component" value="a"/>
+                <property name="covered_component" value="b &amp; c"/>
+            </properties>
+        </testcase>
+        <testcase classname="suite" name="second">
+            <properties><property name="covered_components" value="a, d"/></properties>
+            <failure message="expected &quot;a&quot;">stack trace > here</failure>
+        </testcase>
+        <testcase name="third"><skipped/></testcase>
+        <testcase name="fourth"/>
+    </testsuite>
+</testsuites>"#;
+
+        //When
+        let records = JUnitAdapter {}.parse(xml).unwrap();
+
+        //Then
+        assert_eq!(vec![
+            TestRecord::new(10, TestResult::passed, vec!["a".to_string(), "b & c".to_string()], None),
+            TestRecord::new(stable_id("suite.second", 1), TestResult::failed, vec!["a".to_string(), "d".to_string()], Some("expected \"a\"".to_string())),
+            TestRecord::new(stable_id(".fourth", 1), TestResult::passed, vec![], None),
+        ], records);
+    }
+
+    #[test]
+    fn check_stable_default_ids() {
+        //Given
+        let first_report = r#"<testsuite><testcase classname="a" name="login"/><testcase classname="a" name="logout"/><testcase classname="a" name="login"/></testsuite>"#;
+        let second_report = r#"<testsuite><testcase classname="a" name="logout"/><testcase name="other" id="1"/></testsuite>"#;
+
+        //When
+        let first_ids = JUnitAdapter {}.parse(first_report).unwrap().iter().map(|it| it.id).collect::<Vec<i64>>();
+        let second_ids = JUnitAdapter {}.parse(second_report).unwrap().iter().map(|it| it.id).collect::<Vec<i64>>();
+
+        //Then
+        assert_eq!(3, first_ids.iter().collect::<HashSet<&i64>>().len());
+        assert!(first_ids.iter().all(|it| *it > 0));
+        assert_eq!(vec![first_ids[1], 1], second_ids);
+    }
+}
\ No newline at end of file
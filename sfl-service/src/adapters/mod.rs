@@ -0,0 +1,90 @@
+//Adapters to use test results of other tools than Axini TestManager
+
+pub mod junit;
+pub mod generic;
+
+use chrono::Utc;
+use models::coverage_matrix::CoverageMatrix;
+use models::error_signature::normalise_error_message;
+use models::test_case::Label;
+use models::test_case::Step;
+use models::test_case::TestCase;
+use models::test_case::TestResult;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+///The minimal information of a test that is required for the analysis: its id, verdict and the components that it covered
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TestRecord {
+    pub id: i64,
+    pub verdict: TestResult,
+    #[serde(default)]
+    pub covered: Vec<String>,
+    #[serde(default)]
+    pub error_message: Option<String>,
+}
+
+impl TestRecord {
+    pub fn new(id: i64, verdict: TestResult, covered: Vec<String>, error_message: Option<String>) -> TestRecord {
+        TestRecord {
+            id,
+            verdict,
+            covered,
+            error_message,
+        }
+    }
+
+    ///Convert the record to a test case. Every covered component becomes a step without a direction, so the label of the step is exactly the name of the component.
+    pub fn to_test_case(&self) -> TestCase {
+        let steps = self.covered.iter().enumerate().map(|(i, component)| {
+            Step::new(Label::new(component.to_string(), String::new(), None), Utc::now(), None, i as i32, None, None, None, None)
+        }).collect::<Vec<Step>>();
+        let last_step = steps.len() as i32;
+        TestCase::new(Some(self.id), Some(self.verdict.clone()), self.error_message.clone(), steps, last_step, None, None)
+    }
+}
+
+///An adapter converts the output of a test tool to test records, which can be used as test cases or as a coverage matrix
+pub trait TestDataAdapter {
+    ///Parse the input of the test tool
+    fn parse(&self, input: &str) -> Result<Vec<TestRecord>, String>;
+
+    ///Parse the input and convert it to test cases
+    fn to_test_cases(&self, input: &str) -> Result<Vec<TestCase>, String> {
+        self.parse(input).map(|records| records.iter().map(|it| it.to_test_case()).collect())
+    }
+
+    ///Parse the input and convert it directly to a coverage matrix. The error messages of the failing tests become their error signatures.
+    fn to_coverage_matrix(&self, input: &str) -> Result<CoverageMatrix, String> {
+        let records = self.parse(input)?;
+        let mut coverage_matrix = CoverageMatrix::new(vec![], vec![], HashMap::new());
+        for record in records.iter() {
+            let covered_components = record.covered.iter().cloned().collect::<HashSet<String>>();
+            coverage_matrix.add_test(record.id, record.verdict != TestResult::passed, &covered_components);
+            if let (true, Some(error_message)) = (record.verdict != TestResult::passed, record.error_message.as_ref()) {
+                coverage_matrix.set_error_signature(record.id, normalise_error_message(error_message));
+            }
+        }
+        Ok(coverage_matrix)
+    }
+}
+
+///Find the adapter for the given format name
+pub fn get_adapter(format: &str) -> Option<Box<TestDataAdapter>> {
+    match format.to_lowercase().as_str() {
+        "junit" => Some(Box::new(junit::JUnitAdapter {})),
+        "json" => Some(Box::new(generic::GenericJsonAdapter {})),
+        "csv" => Some(Box::new(generic::GenericCsvAdapter {})),
+        _ => None
+    }
+}
+
+///Parse a verdict as it is used by the different test tools
+pub fn parse_verdict(verdict: &str) -> TestResult {
+    match verdict.trim().to_lowercase().as_str() {
+        "passed" | "pass" | "success" | "ok" => TestResult::passed,
+        "failed" | "fail" | "failure" => TestResult::failed,
+        "error" | "errored" => TestResult::error,
+        _ => TestResult::unknown
+    }
+}
@@ -1,4 +1,3 @@
-use get_settings;
 use models::analysis_result::AnalysisResult;
 use models::coverage_matrix::CoverageMatrix;
 use models::model::State;
@@ -6,45 +5,47 @@ use models::model::TestModel;
 use models::model::Transition;
 use models::test_case::TestCase;
 use models::test_case::TestResult;
-use send_progress;
-use settings::Settings;
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::mem;
-use std::sync::Mutex;
-use std::sync::MutexGuard;
-use std::thread;
-use storage_service::get_storage_service;
 use models::test_case::Step;
 use std::cmp::max;
 use std::fs::File;
+use std::io::Write;
+use models::coverage_matrix::MatrixFormat;
+use models::coverage_matrix::DuplicateRows;
+use models::similarity::ComponentCounts;
+use models::similarity::SimilarityCoefficient;
+use models::threshold_calibration::CalibrationMethod;
+use models::threshold_calibration::ThresholdCalibration;
+use models::explanation::Explanation;
+use models::reproduction_path::ReproductionPath;
+use models::deviation::Deviation;
+use models::deviation::weigh_by_proximity;
+use models::error_signature::normalise_error_message;
+use models::tag_filter::TagFilter;
+use models::flakiness::FlakyPolicy;
+use models::flakiness::detect_flaky_traces;
+use models::flakiness::add_to_flaky_traces;
+use models::flakiness::FlakyTrace;
+use storage_service::StorageService;
+use app_context::AppContext;
+use settings::Settings;
+use std::sync::Arc;
+use std::thread;
 
-//The default analysis service instance
-const SERVICE: AnalysisService = AnalysisService {};
-
-lazy_static! {
-    //The actual implementation that will be used during analysis
-    static ref TEMP_ANALYSIS_SERVICE: Mutex<Option<&'static (AnalysisServiceTrait + Sync)>> = Mutex::new(Some(&SERVICE));
-    static ref SETTINGS: Settings = Settings::unwrap(Settings::new());
-}
-
-///Return the singleton analysis service
-pub fn get_analysis_service<'a>() -> MutexGuard<'a, Option<&'static (AnalysisServiceTrait + Sync)>> {
-    return match TEMP_ANALYSIS_SERVICE.lock() {
-        Ok(service) => service,
-        Err(poisoned) => poisoned.into_inner()
-    };
-}
-
-pub fn set_analysis_service(new_service: &'static (AnalysisServiceTrait + Sync)) {
-    let mut state = TEMP_ANALYSIS_SERVICE.lock().expect("Could not lock mutex");
-    mem::replace(&mut *state, Some(new_service));
+///Return the default implementation of the analysis service
+pub fn default_analysis_service() -> Arc<AnalysisServiceTrait + Send + Sync> {
+    Arc::new(AnalysisService {})
 }
 
+///The analysis engine. The settings and the stored data are those of the given context, so the same engine can serve multiple instances.
 pub trait AnalysisServiceTrait {
-    fn train(&self);
-    fn analyse(&self, testcase: TestCase) -> Option<AnalysisResult>;
-    fn similarity_coefficient(&self, step_j: &str, coverage_matrix: &CoverageMatrix) -> f32;
+    fn train(&self, context: &AppContext);
+    fn train_with_test_case(&self, context: &AppContext, testcase: &TestCase);
+    fn train_with_coverage_information(&self, context: &AppContext, coverage_information: &TestModel);
+    fn analyse(&self, context: &AppContext, testcase: TestCase) -> Option<AnalysisResult>;
+    fn analyse_with(&self, context: &AppContext, testcase: TestCase, model: &Option<TestModel>, coverage_matrix: &Option<CoverageMatrix>, coverage_model: Option<TestModel>, threshold_calibration: &Option<ThresholdCalibration>) -> Option<AnalysisResult>;
+    fn similarity_coefficient(&self, context: &AppContext, step_j: &str, coverage_matrix: &CoverageMatrix) -> f32;
 }
 
 ///Analysis service interface
@@ -54,92 +55,275 @@ impl AnalysisServiceTrait for AnalysisService {
     //noinspection RsTypeCheck
     ///The training stage of the analysis.
     /// This stage creates the coverage matrix and possibly create pairs if this was configured in the settings.
-    fn train(&self) {
-        send_progress(0.1);
-        let matrix = {
-            send_progress(0.2);
-            if get_settings().analysis.use_steps_instead_of_transitions_for_analysis == true {
-                let storage_service = get_storage_service();
-                let test_cases: Vec<TestCase> = storage_service.failing.iter().chain(storage_service.passing.iter()).map(|it|it.to_owned()).collect::<Vec<TestCase>>();
-                CoverageMatrix::from_traces(test_cases, None, 0.25, 0.60)
-            }
-            else {
-                let coverage_information: Vec<TestModel> = (&get_storage_service().coverage_information).to_owned();
-                CoverageMatrix::from_model(coverage_information, None, 0.25, 0.60)
-            }
+    /// The coverage matrix is completed (flaky tests, weights, error signatures, deviations) before it is stored, so a request during the training never sees a half-built matrix.
+    fn train(&self, context: &AppContext) {
+        context.send_progress(0.1);
+        let settings = context.settings().clone();
+        //Only the tests that match the configured tags are used for training
+        let tag_filter = training_tag_filter(&settings);
+        let (number_of_pairs_to_include_for_order, use_steps, use_transition_data, duplicate_rows) = {
+            let duplicate_rows = match (settings.analysis.deduplicate_test_rows, settings.analysis.count_duplicate_test_rows) {
+                (false, _) => DuplicateRows::Keep,
+                (true, true) => DuplicateRows::Count,
+                (true, false) => DuplicateRows::CollapseOnce,
+            };
+            (settings.analysis.number_of_pairs_to_include_for_order, settings.analysis.use_steps_instead_of_transitions_for_analysis, settings.analysis.use_transition_data, duplicate_rows)
         };
-
-        let pair_matrix = {
-            let storage_service = get_storage_service();
+        context.send_progress(0.2);
+        let (coverage_matrix, flaky_traces) = {
+            let storage_service = context.storage();
             let mut pairs = vec![];
-            for len in 2..(get_settings().analysis.number_of_pairs_to_include_for_order+2) {
-                storage_service.passing.iter().chain(storage_service.failing.iter()).for_each(|testcase|{
+            for len in 2..(number_of_pairs_to_include_for_order+2) {
+                storage_service.passing.iter().chain(storage_service.failing.iter()).filter(|it| tag_filter.matches(it)).for_each(|testcase|{
                     pairs.append(&mut get_pairs_with_length(len as usize, &(testcase.steps)));
                 });
             }
 
-            if get_settings().analysis.use_steps_instead_of_transitions_for_analysis == true {
-                let test_cases: Vec<TestCase> = storage_service.failing.iter().chain(storage_service.passing.iter()).map(|it|it.to_owned()).collect::<Vec<TestCase>>();
-                CoverageMatrix::from_traces(test_cases, Some(pairs), 0.6, 0.9)
+            //The pairs are added to the coverage matrix next to the single steps or transitions
+            let coverage_matrix = if use_steps == true {
+                let test_cases: Vec<TestCase> = storage_service.failing.iter().chain(storage_service.passing.iter()).filter(|it| tag_filter.matches(it)).map(|it|it.to_owned()).collect::<Vec<TestCase>>();
+                CoverageMatrix::from_traces(test_cases, Some(pairs), use_transition_data, duplicate_rows, &|progress| context.send_progress(0.25 + progress * 0.65))
             }
             else {
-                let transition_pairs = pairs.iter().map(|it| it.iter().filter_map(|it2| it2.to_transition(storage_service.model.as_ref().unwrap())).collect::<Vec<Transition>>()).collect::<Vec<Vec<_>>>();
-                let coverage_information: Vec<TestModel> = (&storage_service.coverage_information).to_owned();
-                CoverageMatrix::from_model(coverage_information, Some(transition_pairs), 0.6, 0.9)
+                let transition_pairs = match storage_service.model {
+                    Some(ref model) => pairs.iter().map(|it| it.iter().filter_map(|it2| it2.to_transition(model, use_transition_data)).collect::<Vec<Transition>>()).collect::<Vec<Vec<_>>>(),
+                    None => vec![]
+                };
+                let coverage_information: Vec<TestModel> = storage_service.coverage_information.iter().filter(|it| is_included_in_training(&storage_service, it.testcase_id, &tag_filter)).map(|it| it.to_owned()).collect();
+                CoverageMatrix::from_model(coverage_information, Some(transition_pairs), use_transition_data, duplicate_rows, &|progress| context.send_progress(0.25 + progress * 0.65))
+            };
+
+            let (mut coverage_matrix, flaky_traces) = handle_flaky_tests(&settings, &storage_service, coverage_matrix, &tag_filter);
+            apply_test_weights(&storage_service, &mut coverage_matrix);
+            add_error_signatures(&storage_service, &mut coverage_matrix);
+            let coverage_matrix = separate_different_tests(&settings, &storage_service, coverage_matrix);
+            (add_deviation_components(&settings, &storage_service, coverage_matrix), flaky_traces)
+        };
+        let threshold_calibration = calibrate_similarity_threshold(&settings, &coverage_matrix);
+        {
+            let mut storage_service = context.storage();
+            storage_service.set_coverage_matrix(coverage_matrix);
+            storage_service.threshold_calibration = threshold_calibration;
+            storage_service.flaky_traces = flaky_traces;
+        }
+        context.send_progress(0.95);
+        export_coverage_matrix(context);
+        context.send_progress(0.98);
+    }
+
+    ///Incrementally update the coverage matrix and the flaky traces with a single test case, instead of rebuilding the complete matrix.
+    /// The matrix is only updated here when the steps are used for the analysis. Otherwise, it is updated when the coverage information of the test case is received.
+    /// If a single row would not give the same matrix as a full training, the test case is left out until the next training, see `incremental_training_blocker`.
+    fn train_with_test_case(&self, context: &AppContext, testcase: &TestCase) {
+        let settings = context.settings().clone();
+        let tag_filter = training_tag_filter(&settings);
+        if !tag_filter.matches(testcase) {
+            return;
+        }
+        let use_steps = settings.analysis.use_steps_instead_of_transitions_for_analysis;
+        let number_of_pairs_to_include_for_order = settings.analysis.number_of_pairs_to_include_for_order;
+        let use_transition_data = settings.analysis.use_transition_data;
+        let mut storage_service = context.storage();
+        let number_of_other_tests = storage_service.passing.iter().chain(storage_service.failing.iter()).filter(|it| tag_filter.matches(it) && *it != testcase).count();
+        if let Some(reason) = incremental_training_blocker(&settings, &storage_service, number_of_other_tests) {
+            if use_steps {
+                eprintln!("Test {:?} is not added to the coverage matrix, because {}. It is included at the next training.", testcase.id, reason);
             }
+            return;
+        }
+
+        //The flaky traces are only reported, so the other tests are not changed by the new test
+        {
+            let passing = storage_service.passing.iter().filter(|it| tag_filter.matches(it)).cloned().collect::<Vec<TestCase>>();
+            let failing = storage_service.failing.iter().filter(|it| tag_filter.matches(it)).cloned().collect::<Vec<TestCase>>();
+            add_to_flaky_traces(&mut storage_service.flaky_traces, testcase, &passing, &failing, settings.analysis.flaky_trace_similarity, use_transition_data);
+        }
+        if !use_steps {
+            return;
+        }
+
+        //Find all components that are covered by the test case
+        let mut covered_components: HashSet<String> = testcase.steps.iter().map(|it| it.get_full_label_with_params(use_transition_data)).collect();
+        let errored = testcase.verdict.as_ref().unwrap_or(&TestResult::unknown) != &TestResult::passed;
+        let test_id = testcase.id.unwrap_or(-1);
+        if errored {
+            if let Some(deviation) = Deviation::from_test_case(testcase, storage_service.model.as_ref(), use_transition_data) {
+                covered_components.insert(deviation.component());
+            }
+        }
+
+        //Only one instance of a pair is kept per label, so the test covers its pairs that are new to the matrix and none of the existing ones.
+        // The instance of the first passing test is kept, so a passing test takes over a pair that only failing tests had before.
+        let (new_pairs, existing_pairs): (Vec<String>, Vec<String>) = pair_labels(&testcase.steps, number_of_pairs_to_include_for_order, use_transition_data).into_iter()
+            .partition(|label| !storage_service.coverage_matrix.as_ref().map(|it| it.data.contains_key(label)).unwrap_or(false));
+        covered_components.extend(new_pairs);
+        let taken_over_pairs = if errored || existing_pairs.is_empty() {
+            vec![]
+        } else {
+            let passing_pairs = storage_service.passing.iter().filter(|it| tag_filter.matches(it) && *it != testcase)
+                .flat_map(|it| pair_labels(&it.steps, number_of_pairs_to_include_for_order, use_transition_data)).collect::<HashSet<String>>();
+            existing_pairs.into_iter().filter(|it| !passing_pairs.contains(it)).collect::<Vec<String>>()
         };
-        //add transition pairs to the coverage matrix
-        let combined = matrix.append(pair_matrix);
-        if combined.is_ok() {
-            get_storage_service().set_coverage_matrix(combined.unwrap());
+
+        storage_service.add_test_to_coverage_matrix(test_id, errored, &covered_components);
+        if let Some(ref mut coverage_matrix) = storage_service.coverage_matrix {
+            for label in taken_over_pairs {
+                let mut values = vec![false; coverage_matrix.error_vector.len()];
+                values[coverage_matrix.error_vector.len() - 1] = true;
+                coverage_matrix.add_component(label, values);
+            }
+            if let (true, Some(error_message)) = (errored, testcase.error_message.as_ref()) {
+                coverage_matrix.set_error_signature(test_id, normalise_error_message(error_message));
+            }
+        }
+        apply_test_weight(&mut storage_service, test_id);
+        recalibrate_similarity_threshold(&settings, &mut storage_service);
+    }
+
+    ///Incrementally update the coverage matrix with the coverage information of a single test case, instead of rebuilding the complete matrix.
+    /// Every transition of the coverage information gets a column, also when it is not covered, just like in a full training.
+    /// If a single row would not give the same matrix as a full training, the coverage information is left out until the next training, see `incremental_training_blocker`.
+    fn train_with_coverage_information(&self, context: &AppContext, coverage_information: &TestModel) {
+        let settings = context.settings().clone();
+        if settings.analysis.use_steps_instead_of_transitions_for_analysis == true {
+            return;
+        }
+        let use_transition_data = settings.analysis.use_transition_data;
+        let tag_filter = training_tag_filter(&settings);
+        let mut storage_service = context.storage();
+        if !is_included_in_training(&storage_service, coverage_information.testcase_id, &tag_filter) {
+            return;
+        }
+        let number_of_other_tests = storage_service.coverage_information.iter()
+            .filter(|it| it.testcase_id != coverage_information.testcase_id && is_included_in_training(&storage_service, it.testcase_id, &tag_filter)).count();
+        if let Some(reason) = incremental_training_blocker(&settings, &storage_service, number_of_other_tests) {
+            eprintln!("The coverage information of test {:?} is not added to the coverage matrix, because {}. It is included at the next training.", coverage_information.testcase_id, reason);
+            return;
         }
-        else {
-            eprintln!("{}. Falling back to only the basic matrix.", combined.unwrap_err());
-            get_storage_service().set_coverage_matrix(matrix);
 
+        //Find all transitions that are covered and check if every sts shows that the test has passed
+        let mut covered_components: HashSet<String> = HashSet::new();
+        let mut uncovered_components: HashSet<String> = HashSet::new();
+        let mut passed = true;
+        for sts in &coverage_information.stss {
+            if let Some(ref props) = sts.trace_properties {
+                if let Some(props_passed) = props.passed {
+                    passed = passed && props_passed
+                }
+            }
+            for transition in sts.transitions.iter() {
+                if transition.attributes.covered.unwrap_or(false) {
+                    covered_components.insert(transition.to_string_with_data(use_transition_data));
+                } else {
+                    uncovered_components.insert(transition.to_string_with_data(use_transition_data));
+                }
+            }
         }
-        send_progress(0.95);
-        thread::spawn(|| (&get_storage_service().coverage_matrix.as_ref().unwrap()).to_coverage_table().to_csv(File::create("output.csv").unwrap()));
-        send_progress(0.98);
+
+        let test_id = coverage_information.testcase_id.unwrap_or(-1);
+        let mut error_signature = None;
+        if !passed {
+            let deviation = {
+                let testcase = storage_service.failing.iter().find(|it| it.id == coverage_information.testcase_id);
+                error_signature = testcase.and_then(|it| it.error_message.as_ref()).map(|it| normalise_error_message(it));
+                testcase.and_then(|it| Deviation::from_test_case(it, storage_service.model.as_ref(), use_transition_data))
+            };
+            if let Some(deviation) = deviation {
+                covered_components.insert(deviation.component());
+            }
+        }
+        storage_service.add_test_to_coverage_matrix(test_id, !passed, &covered_components);
+        if let Some(ref mut coverage_matrix) = storage_service.coverage_matrix {
+            for component in uncovered_components.into_iter().filter(|it| !covered_components.contains(it)) {
+                if !coverage_matrix.data.contains_key(&component) {
+                    let values = vec![false; coverage_matrix.error_vector.len()];
+                    coverage_matrix.add_component(component, values);
+                }
+            }
+            if let Some(error_signature) = error_signature {
+                coverage_matrix.set_error_signature(test_id, error_signature);
+            }
+        }
+        apply_test_weight(&mut storage_service, test_id);
+        recalibrate_similarity_threshold(&settings, &mut storage_service);
+    }
+
+    ///Analyse a test case with the stored model and coverage matrix
+    fn analyse(&self, context: &AppContext, testcase: TestCase) -> Option<AnalysisResult> {
+        let storage_service = context.storage();
+        let coverage_model = testcase.to_coverage_model(&storage_service.coverage_information);
+        self.analyse_with(context, testcase, &storage_service.model, &storage_service.coverage_matrix, coverage_model, &storage_service.threshold_calibration)
     }
 
     //noinspection RsTypeCheck
     ///The analysis stage goes over a test case. For each step in the test case, the fault probability is calculated.
     /// If the calulated proabability is higher than the threshold, a step is added to the vector of problematic steps.
-    /// During the analysis proces, the highest similarity is stored for when the max_similarity setting is used
-    fn analyse(&self, testcase: TestCase) -> Option<AnalysisResult> {
+    /// During the analysis proces, the highest similarity is stored for when the max_similarity setting is used.
+    /// The model, coverage matrix, coverage model of the test case and calibrated threshold are given, so multiple test cases can be analysed without locking the storage for each of them.
+    /// Without a model (e.g. for imported tests) the steps cannot be mapped to transitions, so the problematic steps themselves are reported.
+    fn analyse_with(&self, context: &AppContext, testcase: TestCase, model: &Option<TestModel>, coverage_matrix: &Option<CoverageMatrix>, coverage_model: Option<TestModel>, threshold_calibration: &Option<ThresholdCalibration>) -> Option<AnalysisResult> {
+        let settings = context.settings().clone();
+        let use_transition_data = settings.analysis.use_transition_data;
         //Do not analyse test cases that have passed
         if testcase.verdict == Some(TestResult::passed) {
             return Some(AnalysisResult::new(String::from("Correct"), None, None, None));
         }
 
+        //Use the spectra of the failing tests with the same error signature, if there are any
+        let signature_matrix = match (settings.analysis.analyse_per_error_signature, testcase.error_message.as_ref(), coverage_matrix.as_ref()) {
+            (true, Some(error_message), Some(matrix)) => {
+                let error_signature = normalise_error_message(error_message);
+                if matrix.error_classes().contains(&error_signature) { Some(matrix.for_error_signature(&error_signature)) } else { None }
+            },
+            _ => None
+        };
+        let coverage_matrix = if signature_matrix.is_some() { &signature_matrix } else { coverage_matrix };
+
         //Extract the relevant information
         let mut problematic_steps: Vec<Transition> = vec![];
-        let coverage_model = { testcase.to_coverage_model() };
-        let storage_service = get_storage_service();
-        let model = &storage_service.model;
-        let coverage_model_transitions = coverage_model.unwrap_or((model.as_ref().unwrap()).clone()).all_transitions();
+        let coverage_model_transitions = coverage_model.or_else(|| model.clone()).map(|it| it.all_transitions()).unwrap_or(vec![]);
+        //The problematic steps if there is no model to map them to transitions
+        let mut problematic_steps_without_model: Vec<Step> = vec![];
+        let mut max_similarity_steps: Vec<Step> = vec![];
         let covered_transitions = coverage_model_transitions.iter().filter(|it|it.attributes.covered.unwrap_or(false) == true).collect::<Vec<_>>();
         let mut max_similarity: (Vec<Transition>,f32) = (vec![], 0.0);
+        let similarity_threshold = effective_similarity_threshold(&settings, threshold_calibration);
+        let include_explanation = settings.analysis.include_explanation;
+        let mut explanation = Explanation::new(configured_similarity_coefficient(&settings), similarity_threshold).recording(include_explanation);
+        //Steps close to the failing step are weighed higher
+        let failure_index = testcase.failure_index();
+        let failure_proximity_weight = settings.analysis.failure_proximity_weight;
+        let proximity = |similarity_coefficient: f32, step: Option<&Step>| {
+            let step_index = step.and_then(|step| testcase.steps.iter().position(|it| it == step)).or(failure_index).unwrap_or(0);
+            weigh_by_proximity(similarity_coefficient, step_index, failure_index, testcase.steps.len(), failure_proximity_weight)
+        };
 
-        if get_settings().analysis.use_steps_instead_of_transitions_for_analysis == true {
+        if settings.analysis.use_steps_instead_of_transitions_for_analysis == true {
             //Go over the steps
             for step in testcase.steps.iter() {
                 //Find the similarity coefficient
-                let similarity_coefficient = self.similarity_coefficient(&step.get_full_label(), &storage_service.coverage_matrix.as_ref().unwrap());
-                println!("similarity_coefficient is {} for \tstep {:?}\t in {:?} ", similarity_coefficient, step.get_full_label(), testcase.get_steps());
+                let similarity_coefficient = proximity(self.similarity_coefficient(context, &step.get_full_label_with_params(use_transition_data), coverage_matrix.as_ref().unwrap()), Some(step));
+                println!("similarity_coefficient is {} for \tstep {:?}\t in {:?} ", similarity_coefficient, step.get_full_label_with_params(use_transition_data), testcase.get_steps(use_transition_data));
+                explanation.consider(&step.get_full_label_with_params(use_transition_data), similarity_coefficient, coverage_matrix.as_ref().unwrap());
+                let transition = model.as_ref().and_then(|model| step.to_transition(model, use_transition_data));
+                explanation.map(step, transition.as_ref(), use_transition_data);
                 //Handle the found similarity coefficient according to the settings
-                if get_settings().analysis.return_highest_similarity_if_nothing_found && similarity_coefficient > max_similarity.1 {
-                    let transition = step.to_transition(model.as_ref().unwrap());
+                if settings.analysis.return_highest_similarity_if_nothing_found && similarity_coefficient > max_similarity.1 {
                     if transition.is_some() {
-                        max_similarity = (vec![transition.unwrap().to_owned()], similarity_coefficient);
+                        max_similarity = (vec![transition.clone().unwrap()], similarity_coefficient);
+                    }
+                    else if model.is_none() {
+                        max_similarity = (vec![], similarity_coefficient);
+                        max_similarity_steps = vec![step.clone()];
                     }
                     else { eprintln!("Could not find a transition for problematic step {:?}", step); }
                 }
-                if similarity_coefficient > SETTINGS.analysis.similarity_threshold {
-                    let transition = step.to_transition(model.as_ref().unwrap());
+                if similarity_coefficient > similarity_threshold {
                     if transition.is_some() {
-                        problematic_steps.push(transition.unwrap().to_owned());
+                        problematic_steps.push(transition.clone().unwrap());
+                    }
+                    else if model.is_none() {
+                        problematic_steps_without_model.push(step.clone());
                     }
                     else { eprintln!("Could not find a transition for problematic step {:?}", step); }
                 }
@@ -148,16 +332,20 @@ impl AnalysisServiceTrait for AnalysisService {
         else {
             //Go over the transitions
             for transition in covered_transitions {
-                let step = transition.to_step(&testcase.steps);
-                if step.is_some() && testcase.steps.contains(&step.unwrap()) {
+                let step = transition.to_step(&testcase.steps, use_transition_data);
+                if let Some(ref step) = step {
+                    explanation.map(step, Some(transition), use_transition_data);
+                }
+                if step.as_ref().map(|it| testcase.steps.contains(it)).unwrap_or(false) {
                     //Find the similarity coefficient
-                    let similarity_coefficient = self.similarity_coefficient(&transition.to_string(), &storage_service.coverage_matrix.as_ref().unwrap());
-                    println!("similarity_coefficient is {} for \tstep {:?}\t in {:?} ", similarity_coefficient, transition.to_string(), testcase.get_steps());
+                    let similarity_coefficient = proximity(self.similarity_coefficient(context, &transition.to_string_with_data(use_transition_data), coverage_matrix.as_ref().unwrap()), step.as_ref());
+                    println!("similarity_coefficient is {} for \tstep {:?}\t in {:?} ", similarity_coefficient, transition.to_string_with_data(use_transition_data), testcase.get_steps(use_transition_data));
+                    explanation.consider(&transition.to_string_with_data(use_transition_data), similarity_coefficient, coverage_matrix.as_ref().unwrap());
                     //Handle the found similarity coefficient according to the settings
-                    if get_settings().analysis.return_highest_similarity_if_nothing_found && similarity_coefficient > max_similarity.1 {
+                    if settings.analysis.return_highest_similarity_if_nothing_found && similarity_coefficient > max_similarity.1 {
                         max_similarity = (vec![transition.to_owned()], similarity_coefficient);
                     }
-                    if similarity_coefficient > SETTINGS.analysis.similarity_threshold {
+                    if similarity_coefficient > similarity_threshold {
                         problematic_steps.push(transition.to_owned());
                     }
                 }
@@ -166,38 +354,46 @@ impl AnalysisServiceTrait for AnalysisService {
 
         //Create the pairs
         let mut pairs = vec![];
-        for len in 2..(get_settings().analysis.number_of_pairs_to_include_for_order+2) {
+        for len in 2..(settings.analysis.number_of_pairs_to_include_for_order+2) {
             pairs.append(&mut get_pairs_with_length(len as usize, &testcase.steps));
         }
 
         //Analyse the pairs
-        if get_settings().analysis.use_steps_instead_of_transitions_for_analysis == true {
+        if settings.analysis.use_steps_instead_of_transitions_for_analysis == true {
             for step_pair in pairs {
-                let string_pair = step_pair.iter().map(|it| it.get_full_label()).collect::<Vec<_>>().join(" -> ");
-                let similarity_coefficient = self.similarity_coefficient(&string_pair, &storage_service.coverage_matrix.as_ref().unwrap());
-                println!("similarity_coefficient is {} for \tstep {:?}\t in {:?} ", similarity_coefficient, string_pair, testcase.get_steps());
-                if get_settings().analysis.return_highest_similarity_if_nothing_found && similarity_coefficient > max_similarity.1 {
-                    let mut transition_pair = step_pair.iter().filter_map(|it|it.to_transition(model.as_ref().unwrap())).collect::<Vec<Transition>>();
+                let string_pair = step_pair.iter().map(|it| it.get_full_label_with_params(use_transition_data)).collect::<Vec<_>>().join(" -> ");
+                let similarity_coefficient = proximity(self.similarity_coefficient(context, &string_pair, coverage_matrix.as_ref().unwrap()), step_pair.last());
+                println!("similarity_coefficient is {} for \tstep {:?}\t in {:?} ", similarity_coefficient, string_pair, testcase.get_steps(use_transition_data));
+                explanation.consider(&string_pair, similarity_coefficient, coverage_matrix.as_ref().unwrap());
+                let mut transition_pair = step_pair.iter().filter_map(|it| model.as_ref().and_then(|model| it.to_transition(model, use_transition_data))).collect::<Vec<Transition>>();
+                if settings.analysis.return_highest_similarity_if_nothing_found && similarity_coefficient > max_similarity.1 {
                     max_similarity = (transition_pair.clone(), similarity_coefficient);
+                    max_similarity_steps = step_pair.clone();
                 }
-                if similarity_coefficient > SETTINGS.analysis.similarity_threshold {
-                    let mut transition_pair = step_pair.iter().filter_map(|it|it.to_transition(model.as_ref().unwrap())).collect::<Vec<Transition>>();
+                if similarity_coefficient > similarity_threshold {
                     problematic_steps.append(&mut transition_pair);
+                    if model.is_none() {
+                        problematic_steps_without_model.append(&mut step_pair.clone());
+                    }
                 }
             }
         }
         else {
-            let transition_pairs = pairs.iter().map(|it| it.iter().filter_map(|it2| it2.to_transition(model.as_ref().unwrap())).collect::<Vec<Transition>>()).collect::<Vec<Vec<_>>>();
+            let transition_pairs = match model {
+                Some(ref model) => pairs.iter().map(|it| it.iter().filter_map(|it2| it2.to_transition(model, use_transition_data)).collect::<Vec<Transition>>()).collect::<Vec<Vec<_>>>(),
+                None => vec![]
+            };
             for mut transition_pair in transition_pairs {
-                let step_pair = transition_pair.iter().map(|transition| transition.to_step(&testcase.steps)).collect::<Vec<Option<Step>>>();
+                let step_pair = transition_pair.iter().map(|transition| transition.to_step(&testcase.steps, use_transition_data)).collect::<Vec<Option<Step>>>();
                 if step_pair.iter().all(|step| step.is_some() && testcase.steps.contains(step.as_ref().unwrap())) {
-                    let string_pair = transition_pair.iter().map(|it| it.to_string()).collect::<Vec<_>>().join(" ");
-                    let similarity_coefficient = self.similarity_coefficient(&string_pair, &storage_service.coverage_matrix.as_ref().unwrap());
-                    println!("similarity_coefficient is {} for \tstep {:?}\t in {:?} ", similarity_coefficient, string_pair, testcase.get_steps());
-                    if get_settings().analysis.return_highest_similarity_if_nothing_found && similarity_coefficient > max_similarity.1 {
+                    let string_pair = transition_pair.iter().map(|it| it.to_string_with_data(use_transition_data)).collect::<Vec<_>>().join(" ");
+                    let similarity_coefficient = proximity(self.similarity_coefficient(context, &string_pair, coverage_matrix.as_ref().unwrap()), step_pair.last().and_then(|it| it.as_ref()));
+                    println!("similarity_coefficient is {} for \tstep {:?}\t in {:?} ", similarity_coefficient, string_pair, testcase.get_steps(use_transition_data));
+                    explanation.consider(&string_pair, similarity_coefficient, coverage_matrix.as_ref().unwrap());
+                    if settings.analysis.return_highest_similarity_if_nothing_found && similarity_coefficient > max_similarity.1 {
                         max_similarity = (transition_pair.clone(), similarity_coefficient);
                     }
-                    if similarity_coefficient > SETTINGS.analysis.similarity_threshold {
+                    if similarity_coefficient > similarity_threshold {
                         problematic_steps.append(&mut transition_pair);
                     }
                 }
@@ -213,60 +409,84 @@ impl AnalysisServiceTrait for AnalysisService {
         step_labels.dedup();
 
         //Create the return analysis result
-        if problematic_steps.len() == 0 {
-            if get_settings().analysis.return_highest_similarity_if_nothing_found {
+        let use_steps = settings.analysis.use_steps_instead_of_transitions_for_analysis;
+        let result = if use_steps && model.is_none() {
+            explanation.state_extraction = Some(String::from("There is no model, so the problematic steps could not be mapped to transitions or states"));
+            if problematic_steps_without_model.is_empty() && settings.analysis.return_highest_similarity_if_nothing_found {
+                explanation.max_similarity_fallback_used = true;
+                analysis_result_of_steps(max_similarity_steps, use_transition_data)
+            } else {
+                analysis_result_of_steps(problematic_steps_without_model, use_transition_data)
+            }
+        } else if problematic_steps.len() == 0 {
+            if settings.analysis.return_highest_similarity_if_nothing_found {
+                explanation.max_similarity_fallback_used = true;
+                explanation.state_extraction = Some(String::from("No component crossed the threshold, so no state was extracted"));
                 let faulty_transitions = max_similarity.0;
                 if faulty_transitions.len() == 1 {
-                    return Some(AnalysisResult::new(String::from("Transition ") + &faulty_transitions[0].attributes.label, None, Some(faulty_transitions[0].to_owned()), Some(faulty_transitions.iter().filter_map(|it| it.to_step(&testcase.steps)).collect::<Vec<_>>())));
+                    AnalysisResult::new(String::from("Transition ") + &faulty_transitions[0].attributes.label, None, Some(faulty_transitions[0].to_owned()), Some(faulty_transitions.iter().filter_map(|it| it.to_step(&testcase.steps, use_transition_data)).collect::<Vec<_>>()))
                 }
                 else {
-                    return Some(AnalysisResult::new(format!("Multiple steps: {:?}", faulty_transitions), None, None, Some(faulty_transitions.iter().filter_map(|it| it.to_step(&testcase.steps)).collect::<Vec<_>>())));
+                    AnalysisResult::new(format!("Multiple steps: {:?}", faulty_transitions), None, None, Some(faulty_transitions.iter().filter_map(|it| it.to_step(&testcase.steps, use_transition_data)).collect::<Vec<_>>()))
                 }
             } else {
-                return Some(AnalysisResult::new(String::from("No problematic steps found"), None, None, None));
+                explanation.state_extraction = Some(String::from("No component crossed the threshold, so no state was extracted"));
+                AnalysisResult::new(String::from("No problematic steps found"), None, None, None)
             }
 
         } else if problematic_steps.len() == 1 {
+            explanation.state_extraction = Some(String::from("Only a single transition crossed the threshold, so no state was extracted"));
             let faulty_transition_val = problematic_steps[0].to_owned();
-            return Some(AnalysisResult::new(String::from("Transition ") + &faulty_transition_val.attributes.label, None, Some(faulty_transition_val), Some(problematic_steps.iter().filter_map(|it| it.to_step(&testcase.steps)).collect::<Vec<_>>())));
+            AnalysisResult::new(String::from("Transition ") + &faulty_transition_val.attributes.label, None, Some(faulty_transition_val), Some(problematic_steps.iter().filter_map(|it| it.to_step(&testcase.steps, use_transition_data)).collect::<Vec<_>>()))
         } else if problematic_steps.len() == 2 {
-            let faulty_state = extract_state_from_transitions(&problematic_steps[0], &problematic_steps[1], model.as_ref().unwrap());
-            return match faulty_state {
-                Ok(faulty_state_val) => Some(AnalysisResult::new(String::from("State ") + &faulty_state_val.id, Some(faulty_state_val), None, Some(problematic_steps.iter().filter_map(|it| it.to_step(&testcase.steps)).collect::<Vec<_>>()))),
+            let faulty_state = match model {
+                Some(ref model) => extract_state_from_transitions(&problematic_steps[0], &problematic_steps[1], model),
+                None => Err("There is no model to find the state".to_string())
+            };
+            match faulty_state {
+                Ok(faulty_state_val) => {
+                    explanation.state_extraction = Some(format!("State {} is both the source and the target of the problematic transitions {} and {}", faulty_state_val.id, problematic_steps[0].to_string_with_data(use_transition_data), problematic_steps[1].to_string_with_data(use_transition_data)));
+                    AnalysisResult::new(String::from("State ") + &faulty_state_val.id, Some(faulty_state_val), None, Some(problematic_steps.iter().filter_map(|it| it.to_step(&testcase.steps, use_transition_data)).collect::<Vec<_>>()))
+                },
                 Err(err_msg) => {
                     println!("{}", err_msg);
-                    return Some(AnalysisResult::new(format!("Multiple steps: {:?}", step_labels), None, None, Some(problematic_steps.iter().filter_map(|it| it.to_step(&testcase.steps)).collect::<Vec<_>>())));
+                    explanation.state_extraction = Some(format!("{} of {} and {}", err_msg, problematic_steps[0].to_string_with_data(use_transition_data), problematic_steps[1].to_string_with_data(use_transition_data)));
+                    AnalysisResult::new(format!("Multiple steps: {:?}", step_labels), None, None, Some(problematic_steps.iter().filter_map(|it| it.to_step(&testcase.steps, use_transition_data)).collect::<Vec<_>>()))
                 }
-            };
+            }
         } else {
             //It could not be limited to a single transition or state, so we will return all steps
-            return Some(AnalysisResult::new(format!("Multiple steps: {:?}", step_labels), None, None, Some(problematic_steps.iter().filter_map(|it| it.to_step(&testcase.steps)).collect::<Vec<_>>())));
-        }
-    }
-
-
-    ///Calculate the similarity coefficient using the Ochiai similarity coefficient
-    fn similarity_coefficient(&self, step_j: &str, coverage_matrix: &CoverageMatrix) -> f32 {
-        //Ochiai similarity coefficient
-        // a_pq(j) = |{ i | o_ij = p ∧ e_i = q }|, where p and q are either 0 or 1.
-        // o_ij is either 0 or 1, depending on if component j is covered by test run i.
-        match coverage_matrix.data.get(step_j) {
-            Some(col) => {
-                let mut a_11 = 0;
-                let mut a_01 = 0;
-                let mut a_10 = 0;
-                for (o_ij, e) in col.iter().zip(coverage_matrix.error_vector.iter()) {
-                    if o_ij == &true && e == &true {
-                        a_11 += 1;
-                    } else if o_ij == &false && e == &true {
-                        a_01 += 1;
-                    } else if o_ij == &true && e == &false {
-                        a_10 += 1;
-                    }
-                }
+            explanation.state_extraction = Some(format!("{} transitions crossed the threshold, so they could not be limited to a single state", problematic_steps.len()));
+            AnalysisResult::new(format!("Multiple steps: {:?}", step_labels), None, None, Some(problematic_steps.iter().filter_map(|it| it.to_step(&testcase.steps, use_transition_data)).collect::<Vec<_>>()))
+        };
 
-                return a_11 as f32 / ((((a_11 + a_01) * (a_11 + a_10)) as f32).sqrt());
-            }
+        //Compare the observed output with the expected labels
+        let deviation = Deviation::from_test_case(&testcase, model.as_ref(), use_transition_data).map(|it| it.with_coefficient(coverage_matrix.as_ref().unwrap(), &explanation.similarity_coefficient));
+        if let Some(ref deviation) = deviation {
+            explanation.consider(&deviation.component(), deviation.coefficient, coverage_matrix.as_ref().unwrap());
+        }
+        let mut result = result.with_deviation(deviation);
+
+        //Explain how the SUT got to the root cause
+        let include_reproduction_path = settings.analysis.include_reproduction_path;
+        if include_reproduction_path {
+            let reproduction_path = model.as_ref().and_then(|model| ReproductionPath::to_root_cause(model, &result, &testcase, use_transition_data));
+            result = result.with_reproduction_path(reproduction_path);
+        }
+
+        if include_explanation {
+            Some(result.with_explanation(explanation))
+        } else {
+            Some(result)
+        }
+    }
+
+
+    ///Calculate the similarity coefficient using the configured similarity coefficient (Ochiai by default)
+    fn similarity_coefficient(&self, context: &AppContext, step_j: &str, coverage_matrix: &CoverageMatrix) -> f32 {
+        let coefficient = configured_similarity_coefficient(&context.settings());
+        match ComponentCounts::from_matrix(step_j, coverage_matrix) {
+            Some(counts) => counts.similarity(&coefficient),
             None => {
                 println!("Could not find a column for {}. So returning 0 as the similarity_coefficient", step_j);
                 return 0 as f32;
@@ -275,6 +495,218 @@ impl AnalysisServiceTrait for AnalysisService {
     }
 }
 
+///Return the similarity coefficient that is configured in the settings
+pub fn configured_similarity_coefficient(settings: &Settings) -> SimilarityCoefficient {
+    let coefficient_name = settings.analysis.similarity_coefficient.clone();
+    SimilarityCoefficient::from_name(&coefficient_name).unwrap_or(SimilarityCoefficient::ochiai)
+}
+
+///The similarity threshold that is used during analysis: the calibrated threshold if there is one, otherwise the configured threshold
+pub fn effective_similarity_threshold(settings: &Settings, threshold_calibration: &Option<ThresholdCalibration>) -> f32 {
+    match threshold_calibration {
+        Some(ref calibration) => calibration.similarity_threshold,
+        None => settings.analysis.similarity_threshold
+    }
+}
+
+///The filter on the tags of the tests that are used for training, as configured in the settings
+pub fn training_tag_filter(settings: &Settings) -> TagFilter {
+    TagFilter::new(&settings.analysis.training_include_tags, &settings.analysis.training_exclude_tags)
+}
+
+///Check if the coverage information of a test case is used for training. The tags are those of the stored test case with the same id; without a stored test case, the test has no tags.
+fn is_included_in_training(storage_service: &StorageService, testcase_id: Option<i64>, tag_filter: &TagFilter) -> bool {
+    let tags = storage_service.passing.iter().chain(storage_service.failing.iter()).find(|it| it.id == testcase_id).map(|it| it.tags()).unwrap_or(&[]);
+    tag_filter.matches_tags(tags)
+}
+
+///The reason why a single test cannot be added to the trained coverage matrix without a full training, if there is one.
+/// Deduplication, flaky test policies that change the matrix and transition pairs depend on all tests. A matrix that was not trained yet can only be started by the first test.
+fn incremental_training_blocker(settings: &Settings, storage_service: &StorageService, number_of_other_tests: usize) -> Option<String> {
+    let flaky_test_policy = &settings.analysis.flaky_test_policy;
+    if settings.analysis.deduplicate_test_rows {
+        Some("duplicate test rows are collapsed".to_string())
+    } else if FlakyPolicy::from_name(flaky_test_policy).unwrap_or(FlakyPolicy::report) != FlakyPolicy::report {
+        Some(format!("the flaky test policy {} depends on all tests", flaky_test_policy))
+    } else if !settings.analysis.use_steps_instead_of_transitions_for_analysis && settings.analysis.number_of_pairs_to_include_for_order > 0 {
+        Some("the transition pairs depend on all tests".to_string())
+    } else if storage_service.coverage_matrix.is_none() && number_of_other_tests > 0 {
+        Some("the coverage matrix was not trained yet".to_string())
+    } else {
+        None
+    }
+}
+
+///The labels of the step pairs of a trace, as used for the pair columns of the coverage matrix
+fn pair_labels(steps: &Vec<Step>, number_of_pairs_to_include_for_order: i32, use_transition_data: bool) -> Vec<String> {
+    let mut labels = vec![];
+    for len in 2..(number_of_pairs_to_include_for_order+2) {
+        labels.extend(get_pairs_with_length(len as usize, steps).iter().map(|step_pair| step_pair.iter().map(|it| it.get_full_label_with_params(use_transition_data)).collect::<Vec<_>>().join(" -> ")));
+    }
+    labels
+}
+
+///Calibrate the similarity threshold on a coverage matrix with the configured calibration method.
+/// If a threshold is calibrated, it is used during analysis instead of the configured similarity threshold, which is left untouched.
+fn calibrate_similarity_threshold(settings: &Settings, coverage_matrix: &CoverageMatrix) -> Option<ThresholdCalibration> {
+    let method_name = &settings.analysis.threshold_calibration;
+    let method = match CalibrationMethod::from_name(method_name) {
+        Some(method) => method,
+        None => {
+            eprintln!("Unknown threshold calibration {}. The similarity threshold is not calibrated.", method_name);
+            CalibrationMethod::none
+        }
+    };
+    let coefficient = configured_similarity_coefficient(settings);
+    let calibration = ThresholdCalibration::calibrate(coverage_matrix, &coefficient, &method, settings.analysis.threshold_calibration_fraction, settings.analysis.similarity_threshold);
+    if let Some(ref calibration) = calibration {
+        println!("Calibrated the similarity threshold to {}: {}", calibration.similarity_threshold, calibration.justification);
+    }
+    calibration
+}
+
+///Calibrate the similarity threshold again on the stored coverage matrix, after a test was added to it
+fn recalibrate_similarity_threshold(settings: &Settings, storage_service: &mut StorageService) {
+    let calibration = storage_service.coverage_matrix.as_ref().and_then(|coverage_matrix| calibrate_similarity_threshold(settings, coverage_matrix));
+    storage_service.threshold_calibration = calibration;
+}
+
+///Find the traces that both passed and failed and apply the configured flaky test policy to their tests in the coverage matrix.
+/// The flaky traces are returned, so they can be stored and reported.
+fn handle_flaky_tests(settings: &Settings, storage_service: &StorageService, mut coverage_matrix: CoverageMatrix, tag_filter: &TagFilter) -> (CoverageMatrix, Vec<FlakyTrace>) {
+    let policy_name = &settings.analysis.flaky_test_policy;
+    let flaky_test_weight = settings.analysis.flaky_test_weight;
+    let policy = match FlakyPolicy::from_name(policy_name) {
+        Some(policy) => policy,
+        None => {
+            eprintln!("Unknown flaky test policy {}. Flaky tests are only reported.", policy_name);
+            FlakyPolicy::report
+        }
+    };
+    let flaky_traces = {
+        let passing = storage_service.passing.iter().filter(|it| tag_filter.matches(it)).cloned().collect::<Vec<TestCase>>();
+        let failing = storage_service.failing.iter().filter(|it| tag_filter.matches(it)).cloned().collect::<Vec<TestCase>>();
+        detect_flaky_traces(&passing, &failing, settings.analysis.flaky_trace_similarity, settings.analysis.use_transition_data)
+    };
+    let flaky_test_ids = flaky_traces.iter().map(|it| it.test_ids()).flatten().collect::<HashSet<i64>>();
+    if !flaky_traces.is_empty() {
+        println!("Found {} flaky traces with {} tests that both passed and failed", flaky_traces.len(), flaky_test_ids.len());
+    }
+    let number_of_tests_without_id = flaky_traces.iter().map(|it| it.number_of_tests_without_id()).sum::<usize>();
+    if number_of_tests_without_id > 0 && policy != FlakyPolicy::report {
+        eprintln!("{} flaky tests have no id, so the flaky test policy is not applied to them", number_of_tests_without_id);
+    }
+    match policy {
+        FlakyPolicy::report => {},
+        FlakyPolicy::drop => coverage_matrix = coverage_matrix.without_test_ids(&flaky_test_ids),
+        FlakyPolicy::weight => flaky_test_ids.iter().for_each(|test_id| coverage_matrix.set_test_weight(*test_id, flaky_test_weight)),
+        FlakyPolicy::separate_class => flaky_test_ids.iter().for_each(|test_id| coverage_matrix.set_separate_class(*test_id)),
+    }
+    (coverage_matrix, flaky_traces)
+}
+
+///Apply the user-supplied test weights to the coverage matrix, on top of the weights that were already set (e.g. for flaky tests)
+fn apply_test_weights(storage_service: &StorageService, coverage_matrix: &mut CoverageMatrix) {
+    for (test_id, weight) in storage_service.test_weights.iter() {
+        let current_weight = coverage_matrix.test_weight(*test_id);
+        coverage_matrix.set_test_weight(*test_id, current_weight * weight);
+    }
+}
+
+///Apply the user-supplied weight of a single test that was added to the coverage matrix
+fn apply_test_weight(storage_service: &mut StorageService, test_id: i64) {
+    let weight = storage_service.test_weights.get(&test_id).cloned();
+    if let (Some(weight), Some(coverage_matrix)) = (weight, storage_service.coverage_matrix.as_mut()) {
+        let current_weight = coverage_matrix.test_weight(test_id);
+        coverage_matrix.set_test_weight(test_id, current_weight * weight);
+    }
+}
+
+///Give the tests that were collapsed while the coverage matrix was built a row of their own again if they differ in their error signature, weight, class or deviation
+fn separate_different_tests(settings: &Settings, storage_service: &StorageService, coverage_matrix: CoverageMatrix) -> CoverageMatrix {
+    if !settings.analysis.deduplicate_test_rows {
+        return coverage_matrix;
+    }
+    let deviations = storage_service.failing.iter()
+        .filter_map(|testcase| match (testcase.id, Deviation::from_test_case(testcase, storage_service.model.as_ref(), settings.analysis.use_transition_data)) {
+            (Some(test_id), Some(deviation)) => Some((test_id, deviation.component())),
+            _ => None
+        })
+        .collect::<HashMap<i64, String>>();
+    let separated = coverage_matrix.separate_different_tests(|test_id| deviations.get(&test_id).cloned());
+    println!("Collapsed the coverage matrix to {} distinct rows", separated.test_ids().len());
+    separated
+}
+
+///Add a deviation component to the coverage matrix for every failing test case with expected labels.
+/// A deviation component is covered by all failing tests that deviate from the model in the same way.
+fn add_deviation_components(settings: &Settings, storage_service: &StorageService, coverage_matrix: CoverageMatrix) -> CoverageMatrix {
+    let mut deviation_columns: HashMap<String, Vec<bool>> = HashMap::new();
+    for testcase in storage_service.failing.iter() {
+        let row = testcase.id.and_then(|test_id| coverage_matrix.row_of_test(test_id));
+        if let (Some(row), Some(deviation)) = (row, Deviation::from_test_case(testcase, storage_service.model.as_ref(), settings.analysis.use_transition_data)) {
+            deviation_columns.entry(deviation.component()).or_insert(vec![false; coverage_matrix.error_vector.len()])[row] = true;
+        }
+    }
+    let deviation_matrix = CoverageMatrix::new(coverage_matrix.error_vector.clone(), coverage_matrix.test_ids().clone(), deviation_columns);
+    let with_deviations = coverage_matrix.append(deviation_matrix);
+    match with_deviations {
+        Ok(with_deviations) => with_deviations,
+        Err(error) => {
+            eprintln!("Could not add the deviation components: {}", error);
+            coverage_matrix
+        }
+    }
+}
+
+///Store the normalised error messages of the failing tests in the coverage matrix, so the spectra can be computed per error signature
+fn add_error_signatures(storage_service: &StorageService, coverage_matrix: &mut CoverageMatrix) {
+    for testcase in storage_service.failing.iter() {
+        if let Some(ref error_message) = testcase.error_message {
+            coverage_matrix.set_error_signature(testcase.id.unwrap_or(-1), normalise_error_message(error_message));
+        }
+    }
+}
+
+///Export the coverage matrix to the configured export path in a separate thread. If no export path is configured, nothing is exported.
+fn export_coverage_matrix(context: &AppContext) {
+    let (export_path, export_format) = {
+        let settings = context.settings();
+        (settings.analysis.export_path.clone(), settings.analysis.export_format.clone())
+    };
+    if export_path.is_empty() {
+        return;
+    }
+    let context = context.clone();
+    thread::spawn(move || {
+        let format = MatrixFormat::from_name(&export_format).unwrap_or(MatrixFormat::Csv);
+        if let Some(ref coverage_matrix) = context.storage().coverage_matrix {
+            match File::create(&export_path).and_then(|mut file| file.write_all(&coverage_matrix.export(&format))) {
+                Ok(_) => println!("Exported the coverage matrix to {}", export_path),
+                Err(error) => eprintln!("Could not export the coverage matrix to {}: {:?}", export_path, error)
+            }
+        }
+    });
+}
+
+///Create the analysis result of the problematic steps themselves, which is used when there is no model to map them to transitions or states
+fn analysis_result_of_steps(steps: Vec<Step>, use_transition_data: bool) -> AnalysisResult {
+    let mut labels: Vec<String> = vec![];
+    let mut unique_steps: Vec<Step> = vec![];
+    for step in steps {
+        let label = step.get_full_label_with_params(use_transition_data);
+        if !labels.contains(&label) {
+            labels.push(label);
+            unique_steps.push(step);
+        }
+    }
+    match unique_steps.len() {
+        0 => AnalysisResult::new(String::from("No problematic steps found"), None, None, None),
+        1 => AnalysisResult::new(String::from("Step ") + &labels[0], None, None, Some(unique_steps)),
+        _ => AnalysisResult::new(format!("Multiple steps: {:?}", labels), None, None, Some(unique_steps))
+    }
+}
+
 ///Create all combinations of clonable objects of a certain length for the given vector of these objects
 fn get_pairs_with_length<T: Clone>(pair_len: usize, haystack: &Vec<T>) -> Vec<Vec<T>> {
     let mut all_pairs = vec![];
@@ -333,20 +765,33 @@ pub mod tests {
     use std::path::PathBuf;
     use serde_json::from_reader;
     use std::fs;
+    use adapters::TestRecord;
+    use batch_analysis_service::analyse_batch;
 
     pub struct MockAnalysisService {
         pub next_result: Option<AnalysisResult>
     }
 
     impl AnalysisServiceTrait for MockAnalysisService {
-        fn train(&self) {
+        fn train(&self, _context: &AppContext) {
+            //Do nothing for now
+        }
+
+        fn train_with_test_case(&self, _context: &AppContext, _testcase: &TestCase) {
+            //Do nothing for now
+        }
+
+        fn train_with_coverage_information(&self, _context: &AppContext, _coverage_information: &TestModel) {
             //Do nothing for now
         }
 
-        fn analyse(&self, _testcase: TestCase) -> Option<AnalysisResult> {
+        fn analyse(&self, _context: &AppContext, _testcase: TestCase) -> Option<AnalysisResult> {
+            self.next_result.clone()
+        }
+        fn analyse_with(&self, _context: &AppContext, _testcase: TestCase, _model: &Option<TestModel>, _coverage_matrix: &Option<CoverageMatrix>, _coverage_model: Option<TestModel>, _threshold_calibration: &Option<ThresholdCalibration>) -> Option<AnalysisResult> {
             self.next_result.clone()
         }
-        fn similarity_coefficient(&self, _step_j: &str, _coverage_matrix: &CoverageMatrix) -> f32 {
+        fn similarity_coefficient(&self, _context: &AppContext, _step_j: &str, _coverage_matrix: &CoverageMatrix) -> f32 {
             //Do nothing for now
             return 0 as f32;
         }
@@ -368,6 +813,7 @@ pub mod tests {
         //  State 3 is wrongly implemented and should be returned. There is no faulty transition
 
         //Given
+        let context = AppContext::from_settings_file();
         let state_0 = State::new(String::from("0"), StateAttribute::new(String::from("0"), String::from("integer"), None));
         let state_1 = State::new(String::from("1"), StateAttribute::new(String::from("1"), String::from("integer"), None));
         let state_2 = State::new(String::from("2"), StateAttribute::new(String::from("2"), String::from("integer"), None));
@@ -436,16 +882,16 @@ pub mod tests {
             transition_d_out.clone_and_update(|it| it.attributes.covered = Some(true)),
         ], vec![], Some(state_0.clone().id), Some(String::from("")), Some(TraceProperties::new(Some(false))))], Some(4));
 
-        get_storage_service().set_model(Some(model));
-        get_storage_service().set_passing_tests(vec![passing_test_one, passing_test_two]);
-        get_storage_service().set_failing_tests(vec![failing_test_one, failing_test_two]);
-        get_storage_service().set_coverage_informations(vec![model_passing_test_one, model_passing_test_two, model_failing_test_one, model_failing_test_two]);
+        context.storage().set_model(Some(model));
+        context.storage().set_passing_tests(vec![passing_test_one, passing_test_two]);
+        context.storage().set_failing_tests(vec![failing_test_one, failing_test_two]);
+        context.storage().set_coverage_informations(vec![model_passing_test_one, model_passing_test_two, model_failing_test_one, model_failing_test_two]);
 
         //When
-        get_settings().analysis.number_of_pairs_to_include_for_order = 0;
+        context.settings().analysis.number_of_pairs_to_include_for_order = 0;
         let test_testcase = TestCase::new(Some(3), Some(TestResult::failed), Some(String::from("There is a failure")), vec![step_init.clone(), step_b_in.clone(), step_b_out.clone(), step_c_out.clone()], 6, None, None);
-        get_analysis_service().unwrap().train();
-        let analysis_result = get_analysis_service().unwrap().analyse(test_testcase);
+        context.analysis_service().train(&context);
+        let analysis_result = context.analysis_service().analyse(&context, test_testcase);
 
         //Then
         assert_eq!(analysis_result.unwrap(), AnalysisResult::new(String::from("State 3"), Some(State::new(String::from("3"), StateAttribute::new(String::from("3"), String::from("integer"), None))), None, Some(vec![step_b_in.clone(), step_b_out.clone()])));
@@ -467,6 +913,7 @@ pub mod tests {
         //  Transition b? is wrongly implemented (as it goes to state 2, instead of state 3) and should be returned. There is no faulty state
 
         //Given
+        let context = AppContext::from_settings_file();
         let state_0 = State::new(String::from("0"), StateAttribute::new(String::from("0"), String::from("integer"), None));
         let state_1 = State::new(String::from("1"), StateAttribute::new(String::from("1"), String::from("integer"), None));
         let state_2 = State::new(String::from("2"), StateAttribute::new(String::from("2"), String::from("integer"), None));
@@ -534,23 +981,23 @@ pub mod tests {
             transition_d_out.clone_and_update(|it| it.attributes.covered = Some(true)),
         ], vec![], Some(state_0.clone().id), Some(String::from("")), Some(TraceProperties::new(Some(false))))], Some(4));
 
-        get_storage_service().set_model(Some(model));
-        get_storage_service().set_passing_tests(vec![passing_test_one, passing_test_two]);
-        get_storage_service().set_failing_tests(vec![failing_test_one, failing_test_two]);
-        get_storage_service().set_coverage_informations(vec![model_passing_test_one, model_passing_test_two, model_failing_test_one, model_failing_test_two]);
+        context.storage().set_model(Some(model));
+        context.storage().set_passing_tests(vec![passing_test_one, passing_test_two]);
+        context.storage().set_failing_tests(vec![failing_test_one, failing_test_two]);
+        context.storage().set_coverage_informations(vec![model_passing_test_one, model_passing_test_two, model_failing_test_one, model_failing_test_two]);
 
         //When
-        get_settings().analysis.number_of_pairs_to_include_for_order = 0;
+        context.settings().analysis.number_of_pairs_to_include_for_order = 0;
         let test_testcase = TestCase::new(Some(3), Some(TestResult::failed), Some(String::from("There is a failure")), vec![step_init.clone(), step_b_in.clone(), step_a_out.clone(), step_c_out.clone()], 6, None, None);
-        get_analysis_service().unwrap().train();
-        let analysis_result = get_analysis_service().unwrap().analyse(test_testcase.clone());
-        get_storage_service().coverage_matrix.as_ref().unwrap().to_coverage_table().printstd();
+        context.analysis_service().train(&context);
+        let analysis_result = context.analysis_service().analyse(&context, test_testcase.clone());
+        context.storage().coverage_matrix.as_ref().unwrap().to_coverage_table().printstd();
         //When
-        reset_progress();
-        get_settings().analysis.use_steps_instead_of_transitions_for_analysis = true;
-        get_analysis_service().unwrap().train();
-        let analysis_result_steps = get_analysis_service().unwrap().analyse(test_testcase);
-        get_storage_service().coverage_matrix.as_ref().unwrap().to_coverage_table().printstd();
+        context.reset_progress();
+        context.settings().analysis.use_steps_instead_of_transitions_for_analysis = true;
+        context.analysis_service().train(&context);
+        let analysis_result_steps = context.analysis_service().analyse(&context, test_testcase);
+        context.storage().coverage_matrix.as_ref().unwrap().to_coverage_table().printstd();
 
         //Then
         assert_eq!(analysis_result.unwrap(), AnalysisResult::new(String::from("Transition ?b"), None, Some(transition_b_in.clone()), Some(vec![step_b_in.clone()])));
@@ -573,6 +1020,7 @@ pub mod tests {
         //
 
         //Given
+        let context = AppContext::from_settings_file();
         let step_init = Step::new(Label::new(String::from("init"), String::from("out"), Some(String::from("default"))), DateTime::from(SystemTime::now()), None, 0, None, None, None, None);
         let step_a_in = Step::new(Label::new(String::from("a"), String::from("in"), Some(String::from("default"))), DateTime::from(SystemTime::now()), None, 1, None, None, None, None);
         let step_b_in = Step::new(Label::new(String::from("b"), String::from("in"), Some(String::from("default"))), DateTime::from(SystemTime::now()), None, 2, None, None, None, None);
@@ -603,21 +1051,50 @@ pub mod tests {
         let transition_d_out = Transition::new(String::from("4"), String::from("6"), TransitionAttribute::new(String::from("!d"), None, None));
 
         let model = TestModel::new(Uuid::new_v4(), vec![Sts::new(String::from("test model"), vec![state_0.clone(), state_1, state_2, state_3, state_4, state_5, state_6], vec![], vec![state_0.clone().to_start_state()], vec![transition_init, transition_a_in, transition_b_in, transition_a_out, transition_b_out, transition_c_out, transition_d_out], vec![], Some(state_0.id), Some(String::from("")), None)], None);
-        get_storage_service().set_model(Some(model));
-        get_storage_service().set_passing_tests(vec![passing_test_one, passing_test_two, failing_test_one, failing_test_two]);
-        get_storage_service().set_failing_tests(vec![]);
-        get_storage_service().set_coverage_informations(vec![]);
+        context.storage().set_model(Some(model));
+        context.storage().set_passing_tests(vec![passing_test_one, passing_test_two, failing_test_one, failing_test_two]);
+        context.storage().set_failing_tests(vec![]);
+        context.storage().set_coverage_informations(vec![]);
 
 
         //When
         let test_testcase = TestCase::new(Some(3), Some(TestResult::failed), Some(String::from("There is a failure")), vec![step_init.clone(), step_b_in.clone(), step_b_out.clone(), step_c_out.clone()], 6, None, None);
-        get_analysis_service().unwrap().train();
-        let analysis_result = get_analysis_service().unwrap().analyse(test_testcase);
+        context.analysis_service().train(&context);
+        let analysis_result = context.analysis_service().analyse(&context, test_testcase);
 
         //Then
         assert_eq!(analysis_result.unwrap(), AnalysisResult::new(String::from("No problematic steps found"), None, None, None));
     }
 
+    #[test]
+    fn test_analyse_imported_failure_without_model() {
+        //Given
+        let context = AppContext::from_settings_file();
+        {
+            let mut settings = context.settings();
+            settings.analysis.use_steps_instead_of_transitions_for_analysis = true;
+            settings.analysis.number_of_pairs_to_include_for_order = 0;
+            settings.analysis.export_path = String::new();
+        }
+        let failing = TestRecord::new(3, TestResult::failed, vec!["a".to_string(), "b".to_string()], Some("b broke".to_string())).to_test_case();
+        context.storage().set_model(None);
+        context.storage().set_passing_tests(vec![
+            TestRecord::new(1, TestResult::passed, vec!["a".to_string()], None).to_test_case(),
+            TestRecord::new(2, TestResult::passed, vec!["a".to_string(), "c".to_string()], None).to_test_case(),
+        ]);
+        context.storage().set_failing_tests(vec![failing.clone()]);
+        context.storage().set_coverage_informations(vec![]);
+
+        //When
+        context.analysis_service().train(&context);
+        let analysis_result = context.analysis_service().analyse(&context, failing.clone()).unwrap();
+        let batch_result = analyse_batch(&context, vec![failing.clone()], &TagFilter::default(), 1);
+
+        //Then
+        assert_eq!(AnalysisResult::new(String::from("Step b"), None, None, Some(vec![failing.steps[1].clone()])), analysis_result);
+        assert_eq!(Some(analysis_result), batch_result.results[0].result);
+    }
+
     #[test]
     fn test_analyse_with_failure_based_on_order() {
         //Four test cases with each five steps, where there is one step that is failing if another transition is also taken.
@@ -634,6 +1111,7 @@ pub mod tests {
         //  Transition d! is wrongly implemented if the transition a! is taken and should be returned.
 
         //Given
+        let context = AppContext::from_settings_file();
         let state_0 = State::new(String::from("0"), StateAttribute::new(String::from("0"), String::from("integer"), None));
         let state_1 = State::new(String::from("1"), StateAttribute::new(String::from("1"), String::from("integer"), None));
         let state_2 = State::new(String::from("2"), StateAttribute::new(String::from("2"), String::from("integer"), None));
@@ -702,23 +1180,23 @@ pub mod tests {
             transition_d_out.clone_and_update(|it| it.attributes.covered = Some(true)),
         ], vec![], Some(state_0.clone().id), Some(String::from("")), Some(TraceProperties::new(Some(false))))], Some(4));
 
-        get_storage_service().set_model(Some(model));
-        get_storage_service().set_passing_tests(vec![passing_test_one, passing_test_two, passing_test_three]);
-        get_storage_service().set_failing_tests(vec![failing_test_one]);
-        get_storage_service().set_coverage_informations(vec![model_passing_test_one, model_passing_test_two, model_passing_test_three, model_failing_test_one]);
+        context.storage().set_model(Some(model));
+        context.storage().set_passing_tests(vec![passing_test_one, passing_test_two, passing_test_three]);
+        context.storage().set_failing_tests(vec![failing_test_one]);
+        context.storage().set_coverage_informations(vec![model_passing_test_one, model_passing_test_two, model_passing_test_three, model_failing_test_one]);
 
         //When
-        get_settings().analysis.number_of_pairs_to_include_for_order = 2;
+        context.settings().analysis.number_of_pairs_to_include_for_order = 2;
         let test_testcase = TestCase::new(Some(4), Some(TestResult::failed), Some(String::from("There is a failure")), vec![step_init.clone(), step_a_in.clone(), step_a_out.clone(), step_d_out.clone()], 6, None, None);
-        get_analysis_service().unwrap().train();
-        let analysis_result = get_analysis_service().unwrap().analyse(test_testcase.clone());
-        get_storage_service().coverage_matrix.as_ref().unwrap().to_coverage_table().printstd();
+        context.analysis_service().train(&context);
+        let analysis_result = context.analysis_service().analyse(&context, test_testcase.clone());
+        context.storage().coverage_matrix.as_ref().unwrap().to_coverage_table().printstd();
         //When
-        reset_progress();
-        get_settings().analysis.use_steps_instead_of_transitions_for_analysis = true;
-        get_analysis_service().unwrap().train();
-        let analysis_result_steps = get_analysis_service().unwrap().analyse(test_testcase);
-        get_storage_service().coverage_matrix.as_ref().unwrap().to_coverage_table().printstd();
+        context.reset_progress();
+        context.settings().analysis.use_steps_instead_of_transitions_for_analysis = true;
+        context.analysis_service().train(&context);
+        let analysis_result_steps = context.analysis_service().analyse(&context, test_testcase);
+        context.storage().coverage_matrix.as_ref().unwrap().to_coverage_table().printstd();
 
         //Then
         //Require that both a? and d! are in the results and preferably at most one more
@@ -751,6 +1229,7 @@ pub mod tests {
         //  Transition d! is wrongly implemented if the transition a! is taken and should be returned. There is a transition between state 7 and 4 that is a tau step, so no label is added to the transition.
 
         //Given
+        let context = AppContext::from_settings_file();
         let state_0 = State::new(String::from("0"), StateAttribute::new(String::from("0"), String::from("integer"), None));
         let state_1 = State::new(String::from("1"), StateAttribute::new(String::from("1"), String::from("integer"), None));
         let state_2 = State::new(String::from("2"), StateAttribute::new(String::from("2"), String::from("integer"), None));
@@ -825,16 +1304,16 @@ pub mod tests {
             transition_tau.clone_and_update(|it| it.attributes.covered = Some(true)),
         ], vec![], Some(state_0.clone().id), Some(String::from("")), Some(TraceProperties::new(Some(false))))], Some(4));
 
-        get_storage_service().set_model(Some(model));
-        get_storage_service().set_passing_tests(vec![passing_test_one, passing_test_two, passing_test_three]);
-        get_storage_service().set_failing_tests(vec![failing_test_one]);
-        get_storage_service().set_coverage_informations(vec![model_passing_test_one, model_passing_test_two, model_passing_test_three, model_failing_test_one]);
+        context.storage().set_model(Some(model));
+        context.storage().set_passing_tests(vec![passing_test_one, passing_test_two, passing_test_three]);
+        context.storage().set_failing_tests(vec![failing_test_one]);
+        context.storage().set_coverage_informations(vec![model_passing_test_one, model_passing_test_two, model_passing_test_three, model_failing_test_one]);
 
         //When
-        get_settings().analysis.number_of_pairs_to_include_for_order = 2;
+        context.settings().analysis.number_of_pairs_to_include_for_order = 2;
         let test_testcase = TestCase::new(Some(4), Some(TestResult::failed), Some(String::from("There is a failure")), vec![step_init.clone(), step_a_in.clone(), step_a_out.clone(), step_d_out.clone()], 6, None, None);
-        get_analysis_service().unwrap().train();
-        let analysis_result = get_analysis_service().unwrap().analyse(test_testcase);
+        context.analysis_service().train(&context);
+        let analysis_result = context.analysis_service().analyse(&context, test_testcase);
 
         //Then
         //Require that both a? and d! are in the results and preferably at most one more
@@ -848,7 +1327,6 @@ pub mod tests {
 
     use glob::glob;
     use std::result::Result;
-    use reset_progress;
 
     #[test]
 //    fn real_testcase_example() {
@@ -876,15 +1354,15 @@ pub mod tests {
 //
 //        println!("Everything is loaded from disk, storing them now...");
 //
-//        get_storage_service().set_model(Some(model.clone()));
-//        get_storage_service().set_passing_tests(testcases.clone().iter().filter(|it|it.verdict == Some(TestResult::passed)).map(|it| it.clone()).collect::<Vec<TestCase>>());
-//        get_storage_service().set_failing_tests(testcases.clone().iter().filter(|it|it.verdict == Some(TestResult::failed)).map(|it| it.clone()).collect::<Vec<TestCase>>());
-//        get_storage_service().set_coverage_informations(test_models);
+//        context.storage().set_model(Some(model.clone()));
+//        context.storage().set_passing_tests(testcases.clone().iter().filter(|it|it.verdict == Some(TestResult::passed)).map(|it| it.clone()).collect::<Vec<TestCase>>());
+//        context.storage().set_failing_tests(testcases.clone().iter().filter(|it|it.verdict == Some(TestResult::failed)).map(|it| it.clone()).collect::<Vec<TestCase>>());
+//        context.storage().set_coverage_informations(test_models);
 //
 //        //When
-//        get_settings().analysis.number_of_pairs_to_include_for_order = 2;
-//        get_analysis_service().unwrap().train();
-//        let analysis_result = get_analysis_service().unwrap().analyse(testcases[15].clone());
+//        context.settings().analysis.number_of_pairs_to_include_for_order = 2;
+//        context.analysis_service().train(&context);
+//        let analysis_result = context.analysis_service().analyse(&context, testcases[15].clone());
 //        eprintln!("analysis_result = {:?}", analysis_result);
 //    }
 
@@ -909,10 +1387,11 @@ pub mod tests {
         let coverage_matrix = CoverageMatrix::new(error, vec![1, 2, 3, 4, 5, 6], data);
         coverage_matrix.to_coverage_table().printstd();
 
+        let context = AppContext::from_settings_file();
         let expected = vec![0.58, 0.58, 0.63, 0.00, 0.71, 0.71, 0.71, 0.41];
         let mut actual = vec![];
         for component in translation.iter() {
-            actual.push((get_analysis_service().unwrap().similarity_coefficient(component, &coverage_matrix) * 100 as f32).round() / 100 as f32);
+            actual.push((context.analysis_service().similarity_coefficient(&context, component, &coverage_matrix) * 100 as f32).round() / 100 as f32);
         }
 
         assert_eq!(expected, actual);
@@ -946,4 +1425,179 @@ pub mod tests {
         assert_eq!(empty, get_pairs_with_length(10, &haystack));
         assert_eq!(empty, get_pairs_with_length(11, &haystack));
     }
+
+    ///The covered components and the verdict of every test, by the id of the test, so matrices can be compared regardless of the order of their rows
+    fn coverage_by_test(coverage_matrix: &CoverageMatrix) -> HashMap<i64, (bool, Vec<String>)> {
+        coverage_matrix.test_ids().iter().enumerate().map(|(row, test_id)| {
+            let mut covered = coverage_matrix.data.iter().filter(|&(_, values)| values[row]).map(|(component, _)| component.clone()).collect::<Vec<String>>();
+            covered.sort();
+            (*test_id, (coverage_matrix.error_vector[row], covered))
+        }).collect()
+    }
+
+    ///A model that goes from state 0 with !a to state 1 and from there with ?b or ?c to state 2, with the test cases and the coverage information of the tests that take !a and then ?b or ?c
+    struct TrainingFixture {
+        model: TestModel,
+        transitions: Vec<Transition>,
+    }
+
+    impl TrainingFixture {
+        fn new() -> TrainingFixture {
+            let transitions = vec![
+                Transition::new(String::from("0"), String::from("1"), TransitionAttribute::new(String::from("!a"), None, None)),
+                Transition::new(String::from("1"), String::from("2"), TransitionAttribute::new(String::from("?b"), None, None)),
+                Transition::new(String::from("1"), String::from("2"), TransitionAttribute::new(String::from("?c"), None, None)),
+            ];
+            TrainingFixture {
+                model: TestModel::new(Uuid::new_v4(), vec![TrainingFixture::sts(transitions.clone(), None)], None),
+                transitions,
+            }
+        }
+
+        fn sts(transitions: Vec<Transition>, passed: Option<bool>) -> Sts {
+            let state = |id: &str| State::new(id.to_string(), StateAttribute::new(id.to_string(), String::from("integer"), None));
+            Sts::new(String::from("test model"), vec![state("0"), state("1"), state("2")], vec![], vec![state("0").to_start_state()], transitions, vec![], Some(String::from("0")), Some(String::from("")), passed.map(|it| TraceProperties::new(Some(it))))
+        }
+
+        fn step(name: &str, direction: &str, index: i32) -> Step {
+            Step::new(Label::new(name.to_string(), direction.to_string(), Some(String::from("default"))), DateTime::from(SystemTime::now()), None, index, None, None, None, None)
+        }
+
+        ///The test case that takes !a and then the input with the given name. A failing test case has an error message.
+        fn test_case(&self, id: i64, input: &str, passed: bool, tags: &[&str]) -> TestCase {
+            let (verdict, error_message) = if passed { (TestResult::passed, None) } else { (TestResult::failed, Some(format!("Unexpected {}", input))) };
+            TestCase::new(Some(id), Some(verdict), error_message, vec![TrainingFixture::step("a", "out", 0), TrainingFixture::step(input, "in", 1)], 6, None, Some(tags.iter().map(|it| it.to_string()).collect()))
+        }
+
+        ///The coverage information of the test that takes !a and then the input with the given name
+        fn coverage_information(&self, id: i64, input: &str, passed: bool) -> TestModel {
+            let input_label = format!("?{}", input);
+            TestModel::new(self.model.test_run_id, vec![TrainingFixture::sts(self.transitions.iter().map(|transition| {
+                let covered = transition.attributes.label == "!a" || transition.attributes.label == input_label;
+                transition.clone_and_update(|it| it.attributes.covered = Some(covered))
+            }).collect(), Some(passed))], Some(id))
+        }
+    }
+
+    #[test]
+    fn test_training_tag_filter() {
+        //Given
+        let context = AppContext::from_settings_file();
+        let fixture = TrainingFixture::new();
+
+        for &use_steps in [true, false].iter() {
+            {
+                let mut settings = context.settings();
+                settings.analysis.use_steps_instead_of_transitions_for_analysis = use_steps;
+                settings.analysis.number_of_pairs_to_include_for_order = 0;
+                settings.analysis.training_include_tags = String::from("smoke");
+                settings.analysis.training_exclude_tags = String::from("flaky");
+                settings.analysis.export_path = String::new();
+            }
+            {
+                let mut storage_service = context.storage();
+                storage_service.set_model(Some(fixture.model.clone()));
+                storage_service.set_passing_tests(vec![fixture.test_case(1, "b", true, &["smoke"]), fixture.test_case(3, "b", true, &["smoke", "flaky"])]);
+                storage_service.set_failing_tests(vec![fixture.test_case(2, "b", false, &["smoke", "regression"]), fixture.test_case(4, "b", false, &[])]);
+                storage_service.set_coverage_informations(vec![fixture.coverage_information(1, "b", true), fixture.coverage_information(2, "b", false), fixture.coverage_information(3, "b", true), fixture.coverage_information(4, "b", false)]);
+            }
+
+            //When
+            context.analysis_service().train(&context);
+
+            //Then
+            let mut test_ids = context.storage().coverage_matrix.as_ref().unwrap().test_ids().clone();
+            test_ids.sort();
+            assert_eq!(vec![1, 2], test_ids);
+        }
+    }
+
+    ///Store a new test and its coverage information and train with them incrementally
+    fn receive_test(context: &AppContext, test_case: TestCase, coverage_information: TestModel) {
+        {
+            let mut storage_service = context.storage();
+            if test_case.verdict == Some(TestResult::passed) {
+                storage_service.passing.push(test_case.clone());
+            } else {
+                storage_service.failing.push(test_case.clone());
+            }
+            storage_service.coverage_information.push(coverage_information.clone());
+        }
+        context.analysis_service().train_with_test_case(context, &test_case);
+        context.analysis_service().train_with_coverage_information(context, &coverage_information);
+    }
+
+    #[test]
+    fn test_incremental_training_equals_full_training() {
+        //Given
+        let context = AppContext::from_settings_file();
+        let fixture = TrainingFixture::new();
+        //Test 1 fails on the same trace as the passing tests 2 and 4, so it is flaky. Test 2 takes over the pair of test 1.
+        let tests = vec![(1, "b", false), (2, "b", true), (3, "c", false), (4, "b", true)];
+
+        for &(use_steps, number_of_pairs) in [(true, 0), (true, 1), (false, 0)].iter() {
+            {
+                let mut settings = context.settings();
+                settings.analysis.use_steps_instead_of_transitions_for_analysis = use_steps;
+                settings.analysis.number_of_pairs_to_include_for_order = number_of_pairs;
+                settings.analysis.export_path = String::new();
+            }
+            {
+                let mut storage_service = context.storage();
+                storage_service.set_model(Some(fixture.model.clone()));
+                storage_service.set_passing_tests(vec![]);
+                storage_service.set_failing_tests(vec![]);
+                storage_service.set_coverage_informations(vec![]);
+                storage_service.coverage_matrix = None;
+                storage_service.flaky_traces = vec![];
+            }
+
+            //When
+            for &(id, input, passed) in tests.iter() {
+                receive_test(&context, fixture.test_case(id, input, passed, &[]), fixture.coverage_information(id, input, passed));
+            }
+            let (incremental, incremental_flaky_traces) = {
+                let storage_service = context.storage();
+                (storage_service.coverage_matrix.clone().unwrap(), storage_service.flaky_traces.clone())
+            };
+            context.analysis_service().train(&context);
+            let (full, full_flaky_traces) = {
+                let storage_service = context.storage();
+                (storage_service.coverage_matrix.clone().unwrap(), storage_service.flaky_traces.clone())
+            };
+
+            //Then
+            assert_eq!(full.components(), incremental.components());
+            assert_eq!(coverage_by_test(&full), coverage_by_test(&incremental));
+            assert_eq!(full_flaky_traces, incremental_flaky_traces);
+            assert_eq!(vec![1, 2, 4], incremental_flaky_traces[0].test_ids());
+        }
+    }
+
+    #[test]
+    fn test_incremental_training_leaves_out_tests_that_need_a_full_training() {
+        //Given
+        let context = AppContext::from_settings_file();
+        let fixture = TrainingFixture::new();
+        {
+            let mut settings = context.settings();
+            settings.analysis.use_steps_instead_of_transitions_for_analysis = false;
+            settings.analysis.number_of_pairs_to_include_for_order = 1;
+            settings.analysis.export_path = String::new();
+        }
+        {
+            let mut storage_service = context.storage();
+            storage_service.set_model(Some(fixture.model.clone()));
+            storage_service.set_passing_tests(vec![fixture.test_case(1, "b", true, &[])]);
+            storage_service.set_failing_tests(vec![]);
+            storage_service.set_coverage_informations(vec![fixture.coverage_information(1, "b", true)]);
+        }
+        context.analysis_service().train(&context);
+
+        //When
+        receive_test(&context, fixture.test_case(2, "c", false, &[]), fixture.coverage_information(2, "c", false));
+
+        //Then
+        assert_eq!(vec![1], context.storage().coverage_matrix.as_ref().unwrap().test_ids().clone());
+    }
 }
\ No newline at end of file
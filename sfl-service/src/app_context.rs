@@ -0,0 +1,207 @@
+use analysis_service::default_analysis_service;
+use analysis_service::AnalysisServiceTrait;
+use models::diagnosis_session::DiagnosisSession;
+use progress_sink::default_progress_sink;
+use progress_sink::no_progress_sink;
+use progress_sink::ProgressSink;
+use rocket::http::Status;
+use rocket::request;
+use rocket::request::FromRequest;
+use rocket::request::Request;
+use rocket::request::State;
+use rocket::Outcome;
+use settings::Settings;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::thread;
+use storage_service::StorageService;
+use uuid::Uuid;
+
+///The state of a single instance of the service: the settings, the stored data, the analysis engine and the progress that was sent.
+/// Every instance has its own context, so multiple instances can run in the same process, e.g. in integration tests or when the service is embedded.
+/// Cloning the context gives another handle to the same instance, e.g. for a thread that trains in the background.
+#[derive(Clone)]
+pub struct AppContext {
+    instance: Arc<Instance>,
+}
+
+struct Instance {
+    settings: Mutex<Settings>,
+    storage: Mutex<StorageService>,
+    analysis_service: Mutex<Arc<AnalysisServiceTrait + Send + Sync>>,
+    progress: Mutex<f32>,
+    progress_sink: Mutex<&'static (ProgressSink + Sync)>,
+    is_configured: Mutex<bool>,
+    diagnosis_sessions: Mutex<HashMap<Uuid, DiagnosisSession>>,
+    scratch: Mutex<Option<AppContext>>,
+}
+
+///Lock a mutex of the context. A thread that panicked while holding the lock does not make the context unusable.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner()
+    }
+}
+
+impl AppContext {
+    pub fn new(settings: Settings, analysis_service: Arc<AnalysisServiceTrait + Send + Sync>) -> AppContext {
+        AppContext {
+            instance: Arc::new(Instance {
+                settings: Mutex::new(settings),
+                storage: Mutex::new(StorageService::new()),
+                analysis_service: Mutex::new(analysis_service),
+                progress: Mutex::new(0.0),
+                progress_sink: Mutex::new(default_progress_sink()),
+                is_configured: Mutex::new(false),
+                diagnosis_sessions: Mutex::new(HashMap::new()),
+                scratch: Mutex::new(None),
+            })
+        }
+    }
+
+    ///A context with the settings of Settings.toml and the default analysis service
+    pub fn from_settings_file() -> AppContext {
+        AppContext::new(Settings::unwrap(Settings::new()), default_analysis_service())
+    }
+
+    pub fn settings(&self) -> MutexGuard<Settings> {
+        lock(&self.instance.settings)
+    }
+
+    pub fn storage(&self) -> MutexGuard<StorageService> {
+        lock(&self.instance.storage)
+    }
+
+    pub fn analysis_service(&self) -> Arc<AnalysisServiceTrait + Send + Sync> {
+        lock(&self.instance.analysis_service).clone()
+    }
+
+    pub fn set_analysis_service(&self, analysis_service: Arc<AnalysisServiceTrait + Send + Sync>) {
+        *lock(&self.instance.analysis_service) = analysis_service;
+    }
+
+    ///The highest progress that was sent to Atana
+    pub fn progress(&self) -> MutexGuard<f32> {
+        lock(&self.instance.progress)
+    }
+
+    pub fn reset_progress(&self) {
+        *self.progress() = 0.0;
+    }
+
+    ///Send the progress of the training to the progress sink, unless a higher progress was sent already
+    pub fn send_progress(&self, progress: f32) {
+        let (use_thread_for_progress, progress_endpoint) = {
+            let settings = self.settings();
+            (settings.analysis.use_thread_for_progress, settings.analysis.progress_endpoint.clone())
+        };
+        let context = self.clone();
+        let send = move || {
+            let mut sent = context.progress();
+            if progress > *sent {
+                context.progress_sink().send_progress(&progress_endpoint, progress);
+                *sent = progress;
+            }
+        };
+        if use_thread_for_progress {
+            thread::spawn(send);
+        } else {
+            send();
+        }
+    }
+
+    ///Where the progress of the training is sent to
+    pub fn progress_sink(&self) -> &'static (ProgressSink + Sync) {
+        *lock(&self.instance.progress_sink)
+    }
+
+    pub fn set_progress_sink(&self, progress_sink: &'static (ProgressSink + Sync)) {
+        *lock(&self.instance.progress_sink) = progress_sink;
+    }
+
+    pub fn is_configured(&self) -> MutexGuard<bool> {
+        lock(&self.instance.is_configured)
+    }
+
+    ///The diagnosis sessions, by their id
+    pub fn diagnosis_sessions(&self) -> MutexGuard<HashMap<Uuid, DiagnosisSession>> {
+        lock(&self.instance.diagnosis_sessions)
+    }
+
+    ///Lock the scratch context of this instance and reset it to a copy of the settings and the training data of this instance.
+    /// In the scratch context, the service can be trained with other settings without affecting this instance, e.g. to evaluate these settings. It does not send progress or export its coverage matrix.
+    /// The scratch context is created once and reused afterwards. It stays locked until the guard is dropped, so it is not shared by two evaluations at the same time.
+    pub fn scratch(&self) -> MutexGuard<Option<AppContext>> {
+        let mut scratch = lock(&self.instance.scratch);
+        let mut settings = self.settings().clone();
+        settings.analysis.export_path = String::new();
+        let analysis_service = self.analysis_service();
+        if scratch.is_none() {
+            *scratch = Some(AppContext::new(settings.clone(), analysis_service.clone()));
+        }
+        if let Some(ref context) = *scratch {
+            *context.settings() = settings;
+            context.set_analysis_service(analysis_service);
+            context.set_progress_sink(no_progress_sink());
+            context.reset_progress();
+            *context.storage() = self.storage().training_data();
+        }
+        scratch
+    }
+}
+
+///The context of the instance that handles a request, which is taken from the managed state of Rocket
+#[derive(Clone, Copy)]
+pub struct Context<'r>(&'r AppContext);
+
+impl<'r> Context<'r> {
+    pub fn new(context: &'r AppContext) -> Context<'r> {
+        Context(context)
+    }
+}
+
+impl<'r> Deref for Context<'r> {
+    type Target = AppContext;
+
+    fn deref(&self) -> &AppContext {
+        self.0
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for Context<'r> {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Context<'r>, ()> {
+        match request.guard::<State<'r, AppContext>>() {
+            Outcome::Success(context) => Outcome::Success(Context(context.inner())),
+            _ => Outcome::Failure((Status::InternalServerError, ()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::model::TestModel;
+
+    #[test]
+    fn check_contexts_are_isolated() {
+        //Given
+        let first = AppContext::from_settings_file();
+        let second = AppContext::from_settings_file();
+
+        //When
+        first.storage().model = Some(TestModel::new(Uuid::nil(), vec![], None));
+        let handle = first.clone();
+        let spawned = thread::spawn(move || handle.storage().model.is_some()).join().unwrap();
+
+        //Then
+        assert!(spawned);
+        assert!(second.storage().model.is_none());
+        assert!(first.storage().model.is_some());
+    }
+}
@@ -0,0 +1,142 @@
+use analysis_service::AnalysisServiceTrait;
+use models::analysis_result::AnalysisResult;
+use models::coverage_matrix::CoverageMatrix;
+use models::model::State;
+use models::model::TestModel;
+use models::model::Transition;
+use models::test_case::TestCase;
+use models::tag_filter::TagFilter;
+use models::threshold_calibration::ThresholdCalibration;
+use std::cmp::max;
+use std::sync::Arc;
+use std::thread;
+use app_context::AppContext;
+
+///The analysis result of a single test case in a batch
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TestAnalysisResult {
+    pub test_id: Option<i64>,
+    pub result: Option<AnalysisResult>,
+}
+
+///All test cases that ended up in the same group, together with the root cause they share (if they all have the same root cause)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GroupSummary {
+    pub group_name: String,
+    pub test_ids: Vec<Option<i64>>,
+    pub count: usize,
+    pub root_cause_state: Option<State>,
+    pub root_cause_transition: Option<Transition>,
+}
+
+///The results of a batch analysis, with a summary of the groups ordered from large to small
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BatchAnalysisResult {
+    pub number_of_tests: usize,
+    pub results: Vec<TestAnalysisResult>,
+    pub groups: Vec<GroupSummary>,
+}
+
+impl BatchAnalysisResult {
+    pub fn new(results: Vec<TestAnalysisResult>) -> BatchAnalysisResult {
+        let mut groups: Vec<GroupSummary> = vec![];
+        for test_result in results.iter() {
+            if let Some(ref result) = test_result.result {
+                let group_index = groups.iter().position(|it| &it.group_name == result.group_name());
+                match group_index {
+                    Some(index) => {
+                        let group = &mut groups[index];
+                        group.test_ids.push(test_result.test_id);
+                        group.count += 1;
+                        if group.root_cause_state.as_ref() != result.root_cause_state() {
+                            group.root_cause_state = None;
+                        }
+                        if group.root_cause_transition.as_ref() != result.root_cause_transition() {
+                            group.root_cause_transition = None;
+                        }
+                    },
+                    None => groups.push(GroupSummary {
+                        group_name: result.group_name().clone(),
+                        test_ids: vec![test_result.test_id],
+                        count: 1,
+                        root_cause_state: result.root_cause_state().cloned(),
+                        root_cause_transition: result.root_cause_transition().cloned(),
+                    })
+                }
+            }
+        }
+        groups.sort_by(|a, b| b.count.cmp(&a.count).then(a.group_name.cmp(&b.group_name)));
+        BatchAnalysisResult { number_of_tests: results.len(), results, groups }
+    }
+}
+
+///Analyse all test cases with a single copy of the model and the coverage matrix.
+/// If more than one thread is requested, the test cases are divided over the threads.
+/// Only the tests that match the tag filter are used as spectra, so e.g. exploratory runs do not influence the analysis of smoke tests.
+pub fn analyse_batch(context: &AppContext, test_cases: Vec<TestCase>, tag_filter: &TagFilter, number_of_threads: usize) -> BatchAnalysisResult {
+    let service = context.analysis_service();
+    let (model, coverage_matrix, coverage_information, threshold_calibration) = {
+        let mut storage_service = context.storage();
+        (Arc::new(storage_service.model.clone()), Arc::new(storage_service.tagged_coverage_matrix(tag_filter)), Arc::new(storage_service.coverage_information.clone()), Arc::new(storage_service.threshold_calibration.clone()))
+    };
+
+    let results = if number_of_threads <= 1 || test_cases.len() <= 1 {
+        test_cases.into_iter().map(|test_case| TestAnalysisResult { test_id: test_case.id, result: analyse(&*service, context, test_case, &model, &coverage_matrix, &coverage_information, &threshold_calibration) }).collect::<Vec<TestAnalysisResult>>()
+    } else {
+        let chunk_size = max(1, (test_cases.len() + number_of_threads - 1) / number_of_threads);
+        let handles = test_cases.chunks(chunk_size).map(|chunk| {
+            let chunk = chunk.to_vec();
+            let test_ids = chunk.iter().map(|it| it.id).collect::<Vec<Option<i64>>>();
+            let model = Arc::clone(&model);
+            let coverage_matrix = Arc::clone(&coverage_matrix);
+            let coverage_information = Arc::clone(&coverage_information);
+            let threshold_calibration = Arc::clone(&threshold_calibration);
+            let service = Arc::clone(&service);
+            let context = context.clone();
+            (test_ids, thread::spawn(move || {
+                chunk.into_iter().map(|test_case| TestAnalysisResult { test_id: test_case.id, result: analyse(&*service, &context, test_case, &model, &coverage_matrix, &coverage_information, &threshold_calibration) }).collect::<Vec<TestAnalysisResult>>()
+            }))
+        }).collect::<Vec<_>>();
+        //The tests of a chunk whose thread panicked are still in the results, but without an analysis result
+        handles.into_iter().map(|(test_ids, handle)| handle.join().unwrap_or_else(|_| {
+            eprintln!("A thread of the batch analysis panicked, so the tests {:?} are not analysed", test_ids);
+            test_ids.iter().map(|test_id| TestAnalysisResult { test_id: *test_id, result: None }).collect()
+        })).flatten().collect::<Vec<TestAnalysisResult>>()
+    };
+    BatchAnalysisResult::new(results)
+}
+
+///Analyse a single test case with the coverage model that has the same test case id
+fn analyse(service: &(AnalysisServiceTrait + Send + Sync), context: &AppContext, test_case: TestCase, model: &Option<TestModel>, coverage_matrix: &Option<CoverageMatrix>, coverage_information: &Vec<TestModel>, threshold_calibration: &Option<ThresholdCalibration>) -> Option<AnalysisResult> {
+    let coverage_model = coverage_information.iter().find(|it| it.testcase_id == test_case.id).cloned();
+    service.analyse_with(context, test_case, model, coverage_matrix, coverage_model, threshold_calibration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::model::TransitionAttribute;
+
+    #[test]
+    fn check_group_summary() {
+        //Given
+        let transition = Transition::new(String::from("0"), String::from("1"), TransitionAttribute::new(String::from("?a"), None, None));
+        let results = vec![
+            TestAnalysisResult { test_id: Some(1), result: Some(AnalysisResult::new(String::from("Transition ?a"), None, Some(transition.clone()), None)) },
+            TestAnalysisResult { test_id: Some(2), result: Some(AnalysisResult::new(String::from("No problematic steps found"), None, None, None)) },
+            TestAnalysisResult { test_id: Some(3), result: Some(AnalysisResult::new(String::from("Transition ?a"), None, Some(transition.clone()), None)) },
+            TestAnalysisResult { test_id: Some(4), result: None },
+        ];
+
+        //When
+        let batch_result = BatchAnalysisResult::new(results);
+
+        //Then
+        assert_eq!(4, batch_result.number_of_tests);
+        assert_eq!(2, batch_result.groups.len());
+        assert_eq!(vec![Some(1), Some(3)], batch_result.groups[0].test_ids);
+        assert_eq!(2, batch_result.groups[0].count);
+        assert_eq!(Some(transition), batch_result.groups[0].root_cause_transition);
+        assert_eq!(vec![Some(2)], batch_result.groups[1].test_ids);
+    }
+}
@@ -0,0 +1,319 @@
+use app_context::AppContext;
+use models::analysis_result::AnalysisResult;
+use models::coverage_matrix::CoverageMatrix;
+use models::error_signature::normalise_error_message;
+use models::test_case::TestCase;
+use std::collections::HashSet;
+
+///The clustering algorithms that can be used to bucket failing tests
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum ClusteringAlgorithm {
+    ///Agglomerative clustering with average linkage, which merges clusters until they are further apart than the maximum distance
+    hierarchical,
+    ///Density based clustering, where tests within the maximum distance of at least `min_points` tests form the core of a cluster
+    dbscan,
+    ///Tests with the same normalised error message form a cluster
+    signature,
+}
+
+///The distance between the suspiciousness vectors of two tests
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum DistanceMetric {
+    ///The euclidean distance, divided by the square root of the number of components so it stays between 0 and 1
+    euclidean,
+    ///One minus the cosine similarity
+    cosine,
+}
+
+///How much each feature of a failing test contributes to the distance between two tests
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct FeatureWeights {
+    pub suspiciousness: f32,
+    pub error_message: f32,
+    pub last_step: f32,
+}
+
+impl Default for FeatureWeights {
+    fn default() -> FeatureWeights {
+        FeatureWeights { suspiciousness: 1.0, error_message: 1.0, last_step: 1.0 }
+    }
+}
+
+///A request to cluster failing tests. If no test cases are given, the stored failing tests are clustered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClusteringRequest {
+    pub test_cases: Vec<TestCase>,
+    pub algorithm: ClusteringAlgorithm,
+    pub distance: DistanceMetric,
+    pub weights: FeatureWeights,
+    pub max_distance: f32,
+    pub min_points: usize,
+}
+
+impl Default for ClusteringRequest {
+    fn default() -> ClusteringRequest {
+        ClusteringRequest {
+            test_cases: vec![],
+            algorithm: ClusteringAlgorithm::hierarchical,
+            distance: DistanceMetric::cosine,
+            weights: FeatureWeights::default(),
+            max_distance: 0.3,
+            min_points: 2,
+        }
+    }
+}
+
+///A bucket of failing tests. The representative test is the test with the smallest total distance to the other tests in the cluster.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Cluster {
+    pub cluster_id: usize,
+    pub test_ids: Vec<Option<i64>>,
+    pub representative_test_id: Option<i64>,
+    pub representative_root_cause: Option<AnalysisResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ClusterAssignment {
+    pub test_id: Option<i64>,
+    pub cluster_id: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ClusteringResult {
+    pub clusters: Vec<Cluster>,
+    pub assignments: Vec<ClusterAssignment>,
+}
+
+///The features of a failing test that are used for the clustering
+struct FailureFeatures {
+    suspiciousness: Vec<f32>,
+    error_tokens: HashSet<String>,
+    error_signature: String,
+    last_step: Option<String>,
+}
+
+impl FailureFeatures {
+    ///Extract the features of a test case. The suspiciousness vector contains the coefficient of every component that is covered by the test and 0 for the other components.
+    /// The labels of the steps include their data if use transition data is set.
+    fn new(test_case: &TestCase, components: &Vec<(String, f32)>, coverage_matrix: Option<&CoverageMatrix>, use_transition_data: bool) -> FailureFeatures {
+        let row = match (coverage_matrix, test_case.id) {
+            (Some(matrix), Some(test_id)) => matrix.row_of_test(test_id),
+            _ => None
+        };
+        let step_labels: HashSet<String> = test_case.steps.iter().map(|it| it.get_full_label_with_params(use_transition_data)).collect();
+        let suspiciousness = components.iter().map(|&(ref component, coefficient)| {
+            let covered = match (row, coverage_matrix) {
+                (Some(row), Some(matrix)) => matrix.data[component][row],
+                _ => step_labels.contains(component)
+            };
+            if covered { coefficient } else { 0.0 }
+        }).collect();
+        let error_signature = normalise_error_message(test_case.error_message.as_ref().map(|it| it.as_str()).unwrap_or(""));
+        let last_step = test_case.steps.iter().find(|it| it.step_number == test_case.last_step).or(test_case.steps.last()).map(|it| it.get_full_label_with_params(use_transition_data));
+        FailureFeatures {
+            suspiciousness,
+            error_tokens: tokenize_error_message(&error_signature),
+            error_signature,
+            last_step,
+        }
+    }
+
+    ///The weighted distance between two failing tests, between 0 and 1
+    fn distance(&self, other: &FailureFeatures, metric: &DistanceMetric, weights: &FeatureWeights) -> f32 {
+        let total_weight = weights.suspiciousness + weights.error_message + weights.last_step;
+        if total_weight <= 0.0 {
+            return 0.0;
+        }
+        let vector_distance = match metric {
+            DistanceMetric::euclidean => {
+                if self.suspiciousness.is_empty() { 0.0 } else {
+                    (self.suspiciousness.iter().zip(other.suspiciousness.iter()).map(|(a, b)| (a - b) * (a - b)).sum::<f32>() / self.suspiciousness.len() as f32).sqrt()
+                }
+            },
+            DistanceMetric::cosine => {
+                let dot = self.suspiciousness.iter().zip(other.suspiciousness.iter()).map(|(a, b)| a * b).sum::<f32>();
+                let norm = self.suspiciousness.iter().map(|a| a * a).sum::<f32>().sqrt() * other.suspiciousness.iter().map(|b| b * b).sum::<f32>().sqrt();
+                if norm == 0.0 { if self.suspiciousness == other.suspiciousness { 0.0 } else { 1.0 } } else { 1.0 - dot / norm }
+            }
+        };
+        let union = self.error_tokens.union(&other.error_tokens).count();
+        let error_distance = if union == 0 { 0.0 } else { 1.0 - self.error_tokens.intersection(&other.error_tokens).count() as f32 / union as f32 };
+        let last_step_distance = if self.last_step == other.last_step { 0.0 } else { 1.0 };
+        (weights.suspiciousness * vector_distance + weights.error_message * error_distance + weights.last_step * last_step_distance) / total_weight
+    }
+}
+
+///Split a normalised error message in its words
+fn tokenize_error_message(error_signature: &str) -> HashSet<String> {
+    error_signature.split(|c: char| !(c.is_alphanumeric() || c == '<' || c == '>'))
+        .filter(|it| !it.is_empty())
+        .map(|it| it.to_string())
+        .collect()
+}
+
+///Cluster the failing tests of the request and analyse the representative test of every cluster
+pub fn cluster(context: &AppContext, request: &ClusteringRequest) -> ClusteringResult {
+    let service = context.analysis_service();
+    let use_transition_data = context.settings().analysis.use_transition_data;
+    let storage_service = context.storage();
+    let test_cases = if request.test_cases.is_empty() { storage_service.failing.clone() } else { request.test_cases.clone() };
+    let coverage_matrix = storage_service.coverage_matrix.as_ref();
+    let components = coverage_matrix.map(|matrix| matrix.components().into_iter().map(|component| {
+        let coefficient = service.similarity_coefficient(context, &component, matrix);
+        (component, coefficient)
+    }).collect::<Vec<(String, f32)>>()).unwrap_or(vec![]);
+
+    let features = test_cases.iter().map(|it| FailureFeatures::new(it, &components, coverage_matrix, use_transition_data)).collect::<Vec<FailureFeatures>>();
+    let distances = features.iter().map(|a| features.iter().map(|b| a.distance(b, &request.distance, &request.weights)).collect::<Vec<f32>>()).collect::<Vec<Vec<f32>>>();
+    let labels = match request.algorithm {
+        ClusteringAlgorithm::hierarchical => hierarchical(&distances, request.max_distance),
+        ClusteringAlgorithm::dbscan => dbscan(&distances, request.max_distance, request.min_points),
+        ClusteringAlgorithm::signature => by_signature(&features),
+    };
+
+    let number_of_clusters = labels.iter().max().map(|it| it + 1).unwrap_or(0);
+    let clusters = (0..number_of_clusters).map(|cluster_id| {
+        let members = (0..labels.len()).filter(|i| labels[*i] == cluster_id).collect::<Vec<usize>>();
+        let representative = *members.iter().min_by(|a, b| {
+            let distance_a = members.iter().map(|it| distances[**a][*it]).sum::<f32>();
+            let distance_b = members.iter().map(|it| distances[**b][*it]).sum::<f32>();
+            distance_a.partial_cmp(&distance_b).unwrap()
+        }).unwrap();
+        Cluster {
+            cluster_id,
+            test_ids: members.iter().map(|it| test_cases[*it].id).collect(),
+            representative_test_id: test_cases[representative].id,
+            representative_root_cause: service.analyse_with(context, test_cases[representative].clone(), &storage_service.model, &storage_service.coverage_matrix, storage_service.coverage_information.iter().find(|it| it.testcase_id == test_cases[representative].id).cloned(), &storage_service.threshold_calibration),
+        }
+    }).collect::<Vec<Cluster>>();
+    let assignments = labels.iter().enumerate().map(|(i, cluster_id)| ClusterAssignment { test_id: test_cases[i].id, cluster_id: *cluster_id }).collect();
+    ClusteringResult { clusters, assignments }
+}
+
+///Agglomerative clustering with average linkage. Returns the cluster of every item, numbered in the order of their first item.
+fn hierarchical(distances: &Vec<Vec<f32>>, max_distance: f32) -> Vec<usize> {
+    let mut clusters: Vec<Vec<usize>> = (0..distances.len()).map(|it| vec![it]).collect();
+    loop {
+        let mut closest: Option<(usize, usize, f32)> = None;
+        for a in 0..clusters.len() {
+            for b in (a + 1)..clusters.len() {
+                let total = clusters[a].iter().map(|i| clusters[b].iter().map(|j| distances[*i][*j]).sum::<f32>()).sum::<f32>();
+                let average = total / (clusters[a].len() * clusters[b].len()) as f32;
+                if average <= max_distance && closest.map(|it| average < it.2).unwrap_or(true) {
+                    closest = Some((a, b, average));
+                }
+            }
+        }
+        match closest {
+            Some((a, b, _)) => {
+                let mut merged = clusters.remove(b);
+                clusters[a].append(&mut merged);
+            },
+            None => break
+        }
+    }
+    to_labels(clusters, distances.len())
+}
+
+///DBSCAN clustering. Items that are not density reachable from a core item (noise) get a cluster of their own, so every failing test ends up in a bucket.
+fn dbscan(distances: &Vec<Vec<f32>>, eps: f32, min_points: usize) -> Vec<usize> {
+    let neighbours = |i: usize| (0..distances.len()).filter(|j| distances[i][*j] <= eps).collect::<Vec<usize>>();
+    let mut cluster_of: Vec<Option<usize>> = vec![None; distances.len()];
+    let mut clusters: Vec<Vec<usize>> = vec![];
+    for i in 0..distances.len() {
+        if cluster_of[i].is_some() || neighbours(i).len() < min_points {
+            continue;
+        }
+        let cluster_id = clusters.len();
+        let mut members = vec![];
+        let mut queue = vec![i];
+        cluster_of[i] = Some(cluster_id);
+        while let Some(item) = queue.pop() {
+            members.push(item);
+            let item_neighbours = neighbours(item);
+            if item_neighbours.len() < min_points {
+                continue;
+            }
+            for neighbour in item_neighbours {
+                if cluster_of[neighbour].is_none() {
+                    cluster_of[neighbour] = Some(cluster_id);
+                    queue.push(neighbour);
+                }
+            }
+        }
+        clusters.push(members);
+    }
+    for i in 0..distances.len() {
+        if cluster_of[i].is_none() {
+            clusters.push(vec![i]);
+        }
+    }
+    to_labels(clusters, distances.len())
+}
+
+///Put all items with the same error signature in the same cluster
+fn by_signature(features: &Vec<FailureFeatures>) -> Vec<usize> {
+    let mut clusters: Vec<(&String, Vec<usize>)> = vec![];
+    for (i, feature) in features.iter().enumerate() {
+        let cluster_index = clusters.iter().position(|it| it.0 == &feature.error_signature);
+        match cluster_index {
+            Some(index) => clusters[index].1.push(i),
+            None => clusters.push((&feature.error_signature, vec![i]))
+        }
+    }
+    to_labels(clusters.into_iter().map(|it| it.1).collect(), features.len())
+}
+
+///Convert clusters of items to the cluster id of every item, where clusters are numbered in the order of their first item. This keeps the ids stable.
+fn to_labels(mut clusters: Vec<Vec<usize>>, number_of_items: usize) -> Vec<usize> {
+    for cluster in clusters.iter_mut() {
+        cluster.sort();
+    }
+    clusters.sort_by_key(|it| it[0]);
+    let mut labels = vec![0; number_of_items];
+    for (cluster_id, cluster) in clusters.iter().enumerate() {
+        for item in cluster {
+            labels[*item] = cluster_id;
+        }
+    }
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_clustering_algorithms() {
+        //Given two groups of close items and one outlier
+        let distances = vec![
+            vec![0.0, 0.1, 0.9, 0.8, 1.0],
+            vec![0.1, 0.0, 0.8, 0.9, 1.0],
+            vec![0.9, 0.8, 0.0, 0.2, 1.0],
+            vec![0.8, 0.9, 0.2, 0.0, 1.0],
+            vec![1.0, 1.0, 1.0, 1.0, 0.0],
+        ];
+
+        //When
+        let hierarchical_labels = hierarchical(&distances, 0.3);
+        let dbscan_labels = dbscan(&distances, 0.3, 2);
+
+        //Then
+        assert_eq!(vec![0, 0, 1, 1, 2], hierarchical_labels);
+        assert_eq!(vec![0, 0, 1, 1, 2], dbscan_labels);
+    }
+
+    #[test]
+    fn check_error_message_distance() {
+        let a = FailureFeatures { suspiciousness: vec![1.0, 0.0], error_tokens: tokenize_error_message(&normalise_error_message("Timeout after 100 ms")), error_signature: normalise_error_message("Timeout after 100 ms"), last_step: Some("!a".to_string()) };
+        let b = FailureFeatures { suspiciousness: vec![1.0, 0.0], error_tokens: tokenize_error_message(&normalise_error_message("timeout after 250 ms")), error_signature: normalise_error_message("timeout after 250 ms"), last_step: Some("!a".to_string()) };
+
+        assert_eq!(0.0, a.distance(&b, &DistanceMetric::cosine, &FeatureWeights::default()));
+        assert_eq!(0.0, a.distance(&b, &DistanceMetric::euclidean, &FeatureWeights::default()));
+        assert_eq!(vec![0, 0], by_signature(&vec![a, b]));
+    }
+}
@@ -3,37 +3,44 @@ use rocket_contrib::Json;
 use serde_json::Map;
 use serde_json::value::Value;
 use settings::Analysis;
-use std::sync::Mutex;
-
-lazy_static! {
-    static ref IS_CONFIGURED: Mutex<bool> = Mutex::new(false);
-}
+use models::threshold_calibration::ThresholdCalibration;
+use app_context::Context;
 
 pub fn mount(rocket_instance: Rocket) -> Rocket {
     rocket_instance.mount("/data", routes![
         //This is a list of all controller functions that are mounted
         configure,
         configured,
+        configuration,
         ])
 }
 
 #[post("/configure", format = "application/json", data = "<config>")]
-fn configure(config: Json<Map<String, Value>>) {
+fn configure(context: Context, config: Json<Map<String, Value>>) {
     let config_map = config.into_inner();
     println!("Received config: {:?}", &config_map);
 
     //Store config from Json to the settings
-    Analysis::parse_from_map(config_map);
+    context.settings().analysis.parse_from_map(config_map);
 
-    if let Ok(mut x) = IS_CONFIGURED.lock() {
-        *x = true
-    }
+    *context.is_configured() = true;
 }
 
 #[get("/configured")]
-fn configured() -> Json<bool> {
-    match IS_CONFIGURED.lock() {
-        Ok(x) => Json(*x),
-        Err(_) => Json(false)
-    }
-}
\ No newline at end of file
+fn configured(context: Context) -> Json<bool> {
+    Json(*context.is_configured())
+}
+
+///The current analysis settings, together with the outcome of the last calibration of the similarity threshold
+#[derive(Debug, Serialize)]
+struct Configuration {
+    analysis: Analysis,
+    threshold_calibration: Option<ThresholdCalibration>,
+}
+
+#[get("/configure")]
+fn configuration(context: Context) -> Json<Configuration> {
+    let analysis = context.settings().analysis.clone();
+    let threshold_calibration = context.storage().threshold_calibration.clone();
+    Json(Configuration { analysis, threshold_calibration })
+}
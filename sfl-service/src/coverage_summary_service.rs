@@ -0,0 +1,161 @@
+use models::model::Sts;
+use models::model::TestModel;
+use models::model_diff::transition_key;
+use models::test_case::TestResult;
+use std::collections::HashSet;
+use storage_service::StorageService;
+use uuid::Uuid;
+
+///How many of the elements of some kind were covered
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CoveragePercentage {
+    pub covered: usize,
+    pub total: usize,
+    ///The percentage of covered elements, which is 100 if there are no elements
+    pub percentage: f32,
+}
+
+impl CoveragePercentage {
+    pub fn new(covered: usize, total: usize) -> CoveragePercentage {
+        CoveragePercentage {
+            covered,
+            total,
+            percentage: if total == 0 { 100.0 } else { covered as f32 * 100.0 / total as f32 },
+        }
+    }
+
+    fn add(&self, other: &CoveragePercentage) -> CoveragePercentage {
+        CoveragePercentage::new(self.covered + other.covered, self.total + other.total)
+    }
+}
+
+///A child behaviour of an STS and whether some of its behaviour was covered
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChildCoverage {
+    pub id: String,
+    pub label: String,
+    pub partially_covered: bool,
+}
+
+///The coverage of a single STS of the model
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StsCoverage {
+    pub name: String,
+    ///The child behaviour that this STS implements, if it is not the main behaviour
+    pub child_id: Option<String>,
+    pub transitions: CoveragePercentage,
+    pub states: CoveragePercentage,
+    pub start_states: CoveragePercentage,
+    pub children: Vec<ChildCoverage>,
+}
+
+///How well the tests exercise the model, based on the stored coverage information
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CoverageSummary {
+    pub test_run_id: Option<Uuid>,
+    pub number_of_coverage_models: usize,
+    pub transitions: CoveragePercentage,
+    pub states: CoveragePercentage,
+    pub start_states: CoveragePercentage,
+    pub stss: Vec<StsCoverage>,
+    ///The transitions that no passing test covered, including the transitions that were not covered at all
+    pub never_covered_by_passing: Vec<String>,
+    ///The transitions that were covered by failing tests, but never by a passing test
+    pub only_covered_by_failing: Vec<String>,
+}
+
+///Summarise the stored coverage information, optionally of only a single test run. If no model was submitted, the model of the first coverage information is used.
+pub fn coverage_summary(storage_service: &StorageService, test_run_id: Option<Uuid>) -> Option<CoverageSummary> {
+    let coverage_information = storage_service.coverage_information.iter().filter(|it| test_run_id.map(|id| it.test_run_id == id).unwrap_or(true)).collect::<Vec<&TestModel>>();
+    let model = match storage_service.model {
+        Some(ref model) => model,
+        None => match coverage_information.first() {
+            Some(coverage) => *coverage,
+            None => return None
+        }
+    };
+    //A coverage model passed if its test is a passing test, or else if its trace properties say so
+    let coverage = coverage_information.iter().map(|coverage| {
+        let verdict = coverage.testcase_id.and_then(|id| storage_service.passing.iter().chain(storage_service.failing.iter()).find(|it| it.id == Some(id))).and_then(|it| it.verdict.clone());
+        let passed = match verdict {
+            Some(verdict) => Some(verdict == TestResult::passed),
+            None => coverage.stss.iter().filter_map(|it| it.trace_properties.as_ref()).filter_map(|it| it.passed).next()
+        };
+        (*coverage, passed)
+    }).collect::<Vec<(&TestModel, Option<bool>)>>();
+    let mut summary = summarise(model, &coverage);
+    summary.test_run_id = test_run_id;
+    Some(summary)
+}
+
+///Summarise the coverage of the model by the coverage models, which are given together with whether their test passed (if known).
+/// An element is covered if it was covered in at least one of the coverage models.
+pub fn summarise(model: &TestModel, coverage: &[(&TestModel, Option<bool>)]) -> CoverageSummary {
+    let covered_transitions = |passed: Option<bool>| coverage.iter().filter(|it| passed.is_none() || it.1 == passed).map(|it| it.0.all_transitions()).flatten().filter(|it| it.attributes.covered.unwrap_or(false)).map(|it| transition_key(&it)).collect::<HashSet<String>>();
+    let all_covered_transitions = covered_transitions(None);
+    let passing_transitions = covered_transitions(Some(true));
+    let failing_transitions = covered_transitions(Some(false));
+    let coverage_stss = coverage.iter().map(|it| it.0.stss.iter()).flatten().collect::<Vec<&Sts>>();
+
+    let stss = model.stss.iter().map(|sts| {
+        let covered_stss = coverage_stss.iter().filter(|it| it.name() == sts.name()).collect::<Vec<_>>();
+        let covered_states = covered_stss.iter().map(|it| it.states.iter()).flatten().filter(|it| it.covered().unwrap_or(false)).map(|it| it.id.clone()).collect::<HashSet<String>>();
+        let covered_start_states = covered_stss.iter().map(|it| it.start_states().iter()).flatten().filter(|it| it.covered().unwrap_or(false)).map(|it| it.id().clone()).collect::<HashSet<String>>();
+        let covered_children = covered_stss.iter().map(|it| it.children().iter()).flatten().filter(|it| it.partially_covered().unwrap_or(false)).map(|it| it.id().clone()).collect::<HashSet<String>>();
+        StsCoverage {
+            name: sts.name().clone(),
+            child_id: model.child_of(sts).map(|it| it.id().clone()),
+            transitions: CoveragePercentage::new(sts.transitions.iter().filter(|it| all_covered_transitions.contains(&transition_key(it))).count(), sts.transitions.len()),
+            states: CoveragePercentage::new(sts.states.iter().filter(|it| covered_states.contains(&it.id)).count(), sts.states.len()),
+            start_states: CoveragePercentage::new(sts.start_states().iter().filter(|it| covered_start_states.contains(it.id())).count(), sts.start_states().len()),
+            children: sts.children().iter().map(|it| ChildCoverage { id: it.id().clone(), label: it.label().clone(), partially_covered: covered_children.contains(it.id()) }).collect(),
+        }
+    }).collect::<Vec<StsCoverage>>();
+
+    let transition_keys = model.all_transitions().iter().map(|it| transition_key(it)).collect::<Vec<String>>();
+    let empty = CoveragePercentage::new(0, 0);
+    CoverageSummary {
+        test_run_id: None,
+        number_of_coverage_models: coverage.len(),
+        transitions: stss.iter().fold(empty.clone(), |total, it| total.add(&it.transitions)),
+        states: stss.iter().fold(empty.clone(), |total, it| total.add(&it.states)),
+        start_states: stss.iter().fold(empty, |total, it| total.add(&it.start_states)),
+        never_covered_by_passing: transition_keys.iter().filter(|it| !passing_transitions.contains(*it)).cloned().collect(),
+        only_covered_by_failing: transition_keys.iter().filter(|it| failing_transitions.contains(*it) && !passing_transitions.contains(*it)).cloned().collect(),
+        stss,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::model::State;
+    use models::model::StateAttribute;
+    use models::model::Transition;
+    use models::model::TransitionAttribute;
+
+    fn sts(covered: &[bool]) -> Sts {
+        let state = |id: &str, covered: bool| State::new(id.to_string(), StateAttribute::new(id.to_string(), String::from("state"), Some(covered)));
+        let transition = |label: &str, covered: bool| Transition::new(String::from("s0"), String::from("s1"), TransitionAttribute::new(label.to_string(), None, Some(covered)));
+        let s0 = state("s0", covered[0]);
+        Sts::new(String::from("main"), vec![s0.clone(), state("s1", covered[1])], vec![], vec![s0.to_start_state()], vec![transition("?a", covered[2]), transition("?b", covered[3]), transition("?c", false)], vec![], None, None, None)
+    }
+
+    #[test]
+    fn check_summarise() {
+        //Given
+        let model = TestModel::new(Uuid::nil(), vec![sts(&[false, false, false, false])], None);
+        let passing = TestModel::new(Uuid::nil(), vec![sts(&[true, false, true, false])], Some(1));
+        let failing = TestModel::new(Uuid::nil(), vec![sts(&[true, true, true, true])], Some(2));
+
+        //When
+        let summary = summarise(&model, &[(&passing, Some(true)), (&failing, Some(false))]);
+
+        //Then
+        assert_eq!(CoveragePercentage::new(2, 3), summary.transitions);
+        assert_eq!(CoveragePercentage::new(2, 2), summary.states);
+        assert_eq!(100.0, summary.stss[0].states.percentage);
+        assert_eq!(vec![String::from("s0--?b-->s1"), String::from("s0--?c-->s1")], summary.never_covered_by_passing);
+        assert_eq!(vec![String::from("s0--?b-->s1")], summary.only_covered_by_failing);
+    }
+}
@@ -1,13 +1,18 @@
 use models::model::TestModel;
 use models::test_case::TestCase;
+use models::test_case::TestRun;
 use rocket_contrib::Json;
 use rocket::Rocket;
-use storage_service::get_storage_service;
-use analysis_service::get_analysis_service;
+use app_context::AppContext;
+use app_context::Context;
+use generator;
+use generator::GeneratedScenario;
+use generator::GeneratorConfig;
+use models::coverage_matrix::TestWeight;
+use rocket::response::status::BadRequest;
+use models::model_diff::ModelDiff;
+use models::model_diff::ModelRemapResult;
 use std::thread;
-use send_progress;
-use get_settings;
-use reset_progress;
 
 pub fn mount(rocket_instance: Rocket) -> Rocket {
     rocket_instance.mount("/data", routes![
@@ -15,6 +20,8 @@ pub fn mount(rocket_instance: Rocket) -> Rocket {
         index,
         model,
         show_model,
+        model_diff,
+        remap_model,
         passing_test,
         passing_tests,
         failing_test,
@@ -23,6 +30,9 @@ pub fn mount(rocket_instance: Rocket) -> Rocket {
         coverage_information_list,
         inform_done,
         clear,
+        generate,
+        test_weights,
+        test_run,
         ])
 }
 
@@ -34,66 +44,126 @@ fn index() -> &'static str {
 }
 
 #[post("/model", format = "application/json", data = "<model>")]
-fn model(model: Json<TestModel>) -> &'static str {
-    get_storage_service().set_model(Some(model.into_inner()));
+fn model(context: Context, model: Json<TestModel>) -> &'static str {
+    context.storage().set_model(Some(model.into_inner()));
     return "Model received";
 }
 
+///Compare a new version of the model with the stored model, without replacing it
+#[post("/model/diff", format = "application/json", data = "<model>")]
+fn model_diff(context: Context, model: Json<TestModel>) -> Option<Json<ModelDiff>> {
+    let min_similarity = context.settings().analysis.model_diff_similarity;
+    let storage = context.storage();
+    return storage.model.as_ref().map(|old_model| Json(ModelDiff::new(old_model, &model.into_inner(), min_similarity)));
+}
+
+///Replace the stored model by a new version and translate the stored coverage information to it, so the history of the tests is kept.
+/// The coverage of tests that covered removed states or transitions can no longer be used, these tests are reported.
+/// If the coverage matrix was trained, it is trained again on the translated coverage information.
+#[post("/model/remap", format = "application/json", data = "<model>")]
+fn remap_model(context: Context, model: Json<TestModel>) -> Json<ModelRemapResult> {
+    let min_similarity = context.settings().analysis.model_diff_similarity;
+    let (result, was_trained) = {
+        let mut storage = context.storage();
+        let was_trained = storage.coverage_matrix.is_some();
+        (storage.replace_model(model.into_inner(), min_similarity), was_trained)
+    };
+    if was_trained {
+        context.analysis_service().train(&context);
+    }
+    return Json(result);
+}
+
 #[get("/model")]
-fn show_model() -> Json<TestModel> {
-    return match get_storage_service().model {
+fn show_model(context: Context) -> Json<TestModel> {
+    return match context.storage().model {
         Some(ref model) => Json(model.clone()),
         None => panic!("None found, so no model to show")
     };
 }
 
 #[post("/passing_tests", format = "application/json", data = "<passing_tests>")]
-fn passing_tests(passing_tests: Json<Vec<TestCase>>) -> &'static str {
-    get_storage_service().set_passing_tests(passing_tests.into_inner());
+fn passing_tests(context: Context, passing_tests: Json<Vec<TestCase>>) -> &'static str {
+    context.storage().set_passing_tests(passing_tests.into_inner());
     return "passing_tests received";
 }
 
 #[post("/passing_test", format = "application/json", data = "<passing_test>")]
-fn passing_test(passing_test: Json<TestCase>) -> &'static str {
-    get_storage_service().passing.push(passing_test.into_inner());
+fn passing_test(context: Context, passing_test: Json<TestCase>) -> &'static str {
+    let passing_test = passing_test.into_inner();
+    context.storage().passing.push(passing_test.clone());
+    if context.settings().analysis.incremental_training {
+        context.analysis_service().train_with_test_case(&context, &passing_test);
+    }
     return "passing test received";
 }
 
 
 #[post("/failing_tests", format = "application/json", data = "<failing_tests>")]
-fn failing_tests(failing_tests: Json<Vec<TestCase>>) -> &'static str {
-    get_storage_service().set_failing_tests(failing_tests.into_inner());
+fn failing_tests(context: Context, failing_tests: Json<Vec<TestCase>>) -> &'static str {
+    context.storage().set_failing_tests(failing_tests.into_inner());
     return "failing_tests received";
 }
 
 #[post("/failing_test", format = "application/json", data = "<failing_test>")]
-fn failing_test(failing_test: Json<TestCase>) -> &'static str {
-    get_storage_service().failing.push(failing_test.into_inner());
+fn failing_test(context: Context, failing_test: Json<TestCase>) -> &'static str {
+    let failing_test = failing_test.into_inner();
+    context.storage().failing.push(failing_test.clone());
+    if context.settings().analysis.incremental_training {
+        context.analysis_service().train_with_test_case(&context, &failing_test);
+    }
     return "failing test received";
 }
 
 #[post("/coverages", format = "application/json", data = "<coverage_information>")]
-fn coverage_information_list(coverage_information: Json<Vec<TestModel>>) -> &'static str {
-    get_storage_service().set_coverage_informations(coverage_information.into_inner());
+fn coverage_information_list(context: Context, coverage_information: Json<Vec<TestModel>>) -> &'static str {
+    context.storage().set_coverage_informations(coverage_information.into_inner());
     return "coverage_informations received";
 }
 
 #[post("/coverage", format = "application/json", data = "<coverage_information>")]
-fn coverage_information(coverage_information: Json<TestModel>) -> &'static str {
-    get_storage_service().coverage_information.push(coverage_information.into_inner());
+fn coverage_information(context: Context, coverage_information: Json<TestModel>) -> &'static str {
+    let coverage_information = coverage_information.into_inner();
+    context.storage().coverage_information.push(coverage_information.clone());
+    if context.settings().analysis.incremental_training {
+        context.analysis_service().train_with_coverage_information(&context, &coverage_information);
+    }
     return "coverage information received";
 }
 
+///Set how much tests count during the computation of the coefficients. The weights are applied during the next training.
+#[post("/weights", format = "application/json", data = "<test_weights>")]
+fn test_weights(context: Context, test_weights: Json<Vec<TestWeight>>) -> &'static str {
+    let mut storage = context.storage();
+    for test_weight in test_weights.into_inner() {
+        storage.test_weights.insert(test_weight.test_id, test_weight.weight);
+    }
+    return "test weights received";
+}
+
+///Store the tests of a whole test run at once. The passing tests are added to the passing tests and all other tests to the failing tests, and they are remembered as part of the run, so the run can be compared to other runs.
+#[post("/test_run", format = "application/json", data = "<test_run>")]
+fn test_run(context: Context, test_run: Json<TestRun>) -> Result<&'static str, BadRequest<String>> {
+    let test_run = test_run.into_inner();
+    let test_run_id = match test_run.test_run_id {
+        Some(test_run_id) => test_run_id,
+        None => return Err(BadRequest(Some(String::from("A test run needs a test_run_id"))))
+    };
+    context.storage().add_test_run(test_run_id, test_run.test_cases);
+    return Ok("test run received");
+}
+
 #[post("/done", format = "application/json")]
-fn inform_done() -> &'static str {
-    let t = || {
-        reset_progress();
-        let analysis_service = get_analysis_service().unwrap();
-        analysis_service.train();
+fn inform_done(context: Context) -> &'static str {
+    let use_thread_for_training = context.settings().analysis.use_thread_for_training;
+    let context = AppContext::clone(&context);
+    let t = move || {
+        context.reset_progress();
+        context.analysis_service().train(&context);
         //Inform Atana that training is done
-        send_progress(1.0) //100% done
+        context.send_progress(1.0) //100% done
     };
-    if get_settings().analysis.use_thread_for_training {
+    if use_thread_for_training {
         thread::spawn(t);
     } else {
         t();
@@ -101,9 +171,24 @@ fn inform_done() -> &'static str {
     return "Training started";
 }
 
+///Generate a model with an injected fault and store it, together with the simulated tests and coverage, as if it was submitted.
+/// The generated scenario is returned, so the injected fault is known when the diagnosis is checked.
+#[post("/generate", format = "application/json", data = "<config>")]
+fn generate(context: Context, config: Json<GeneratorConfig>) -> Json<GeneratedScenario> {
+    let scenario = generator::generate(&config.into_inner());
+    {
+        let mut storage = context.storage();
+        storage.set_model(Some(scenario.model.clone()));
+        storage.set_passing_tests(scenario.passing.clone());
+        storage.set_failing_tests(scenario.failing.iter().map(|it| it.test_case.clone()).collect());
+        storage.set_coverage_informations(scenario.coverage_information.clone());
+    }
+    Json(scenario)
+}
+
 #[delete("/clear")]
-fn clear() -> &'static str {
-    get_storage_service().reset();
+fn clear(context: Context) -> &'static str {
+    context.storage().reset();
     return "everything cleared";
 }
 
@@ -120,10 +205,14 @@ mod tests {
     use std::option::Option::Some;
     use std::option::Option::None;
     use models::test_case::TestResult;
+    use models::model::State;
+    use models::model::StateAttribute;
+    use models::model::Transition;
+    use models::model::TransitionAttribute;
 
     #[test]
     fn check_index() {
-        let client = Client::new(create_rocket()).expect("valid rocket instance");
+        let client = Client::new(create_rocket(AppContext::from_settings_file())).expect("valid rocket instance");
         let mut response = client.get("/data").dispatch();
         assert_eq!(response.status(), Status::Ok);
         assert_eq!(response.body_string(), Some("Use this entrypoint to submit data. To submit the model, use /model. To submit passing traces, use /passing_tests. To submit failing traces, use /failing_tests.".into()));
@@ -131,6 +220,7 @@ mod tests {
 
     #[test]
     fn check_model() {
+        let context = AppContext::from_settings_file();
         let test_model = {
             TestModel::new(
                 Uuid::new_v4(),
@@ -147,16 +237,17 @@ mod tests {
                 )], None)
         };
 
-        get_storage_service().set_model(None);
-        let empty_model_result = catch_unwind(|| show_model());
+        context.storage().set_model(None);
+        let empty_model_result = catch_unwind(|| show_model(Context::new(&context)));
         assert!(empty_model_result.is_err());
-        let model_response = model(Json(test_model.clone()));
+        let model_response = model(Context::new(&context), Json(test_model.clone()));
         assert_eq!(model_response, "Model received");
-        assert_eq!(show_model().into_inner(), test_model)
+        assert_eq!(show_model(Context::new(&context)).into_inner(), test_model)
     }
 
     #[test]
     fn check_passing() {
+        let context = AppContext::from_settings_file();
         let passing_test = {
             TestCase::new(
                 Some(1),
@@ -168,13 +259,14 @@ mod tests {
                 None)
         };
 
-        let passing_response = passing_tests(Json(vec![passing_test.clone()]));
+        let passing_response = passing_tests(Context::new(&context), Json(vec![passing_test.clone()]));
         assert_eq!(passing_response, "passing_tests received");
-        assert!(get_storage_service().passing.contains(&passing_test));
+        assert!(context.storage().passing.contains(&passing_test));
     }
 
     #[test]
     fn check_failing() {
+        let context = AppContext::from_settings_file();
         let failing_test = {
             TestCase::new(
                 Some(1),
@@ -186,14 +278,15 @@ mod tests {
                 None)
         };
 
-        let failing_response = failing_tests(Json(vec![failing_test.clone()]));
+        let failing_response = failing_tests(Context::new(&context), Json(vec![failing_test.clone()]));
         assert_eq!(failing_response, "failing_tests received");
-        assert!(get_storage_service().failing.contains(&failing_test));
+        assert!(context.storage().failing.contains(&failing_test));
     }
 
     #[test]
     fn check_reset() {
         //Given
+        let context = AppContext::from_settings_file();
         let test_model = {
             TestModel::new(
                 Uuid::new_v4(),
@@ -230,18 +323,62 @@ mod tests {
                 None)
         };
 
-        get_storage_service().set_model(Some(test_model));
-        get_storage_service().set_passing_tests(vec![passing_test]);
-        get_storage_service().set_failing_tests(vec![failing_test]);
+        context.storage().set_model(Some(test_model));
+        context.storage().set_passing_tests(vec![passing_test]);
+        context.storage().set_failing_tests(vec![failing_test]);
 
         //When
-        let reset_response = clear();
+        let reset_response = clear(Context::new(&context));
 
         //Then
         assert_eq!(reset_response, "everything cleared");
-        assert_eq!(get_storage_service().model, None);
-        assert_eq!(get_storage_service().passing.len(), 0);
-        assert_eq!(get_storage_service().failing.len(), 0);
+        assert_eq!(context.storage().model, None);
+        assert_eq!(context.storage().passing.len(), 0);
+        assert_eq!(context.storage().failing.len(), 0);
     }
-}
 
+    #[test]
+    fn check_remap_model_retrains() {
+        //Given
+        let context = AppContext::from_settings_file();
+        let state = |id: &str, label: &str| State::new(id.to_string(), StateAttribute::new(label.to_string(), String::from("state"), None));
+        let transition = |source: &str, label: &str, target: &str| Transition::new(source.to_string(), target.to_string(), TransitionAttribute::new(label.to_string(), None, None));
+        let model = |states: Vec<State>, transitions: Vec<Transition>| TestModel::new(Uuid::nil(), vec![Sts::new(String::from("main"), states, vec![], vec![], transitions, vec![], None, None, None)], None);
+        let old = model(vec![state("_1", "start"), state("_2", "logged in"), state("_3", "logged out")],
+                        vec![transition("_1", "?login user", "_2"), transition("_2", "?logout", "_3")]);
+        let new = model(vec![state("_1", "start"), state("_10", "logged in"), state("_11", "error")],
+                        vec![transition("_1", "?login user password", "_10"), transition("_1", "?fail", "_11")]);
+        let coverage = |test_id: i64, covered: Vec<bool>| {
+            let mut coverage = old.clone();
+            coverage.testcase_id = Some(test_id);
+            for (transition, covered) in coverage.stss[0].transitions.iter_mut().zip(covered) {
+                transition.attributes.covered = Some(covered);
+            }
+            coverage
+        };
+        {
+            let mut settings = context.settings();
+            settings.analysis.use_steps_instead_of_transitions_for_analysis = false;
+            settings.analysis.number_of_pairs_to_include_for_order = 0;
+        }
+        {
+            let mut storage = context.storage();
+            storage.set_model(Some(old.clone()));
+            storage.set_coverage_informations(vec![coverage(1, vec![true, false]), coverage(2, vec![true, true])]);
+        }
+        context.analysis_service().train(&context);
+
+        //When
+        let result = remap_model(Context::new(&context), Json(new.clone())).into_inner();
+
+        //Then
+        assert_eq!(vec![Some(2)], result.stale_test_ids);
+        let storage = context.storage();
+        let coverage_matrix = storage.coverage_matrix.as_ref().unwrap();
+        let mut components = new.all_transitions().iter().map(|it| it.to_string_with_data(false)).collect::<Vec<String>>();
+        components.sort();
+        assert_eq!(components, coverage_matrix.components());
+        assert_eq!(vec![1], *coverage_matrix.test_ids());
+        assert_eq!(vec![true], coverage_matrix.data[&new.all_transitions()[0].to_string_with_data(false)]);
+    }
+}
\ No newline at end of file
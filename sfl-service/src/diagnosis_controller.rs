@@ -0,0 +1,37 @@
+use app_context::Context;
+use diagnosis_service;
+use models::diagnosis_session::DiagnosisSession;
+use models::diagnosis_session::FollowUpVerdict;
+use models::test_case::TestCase;
+use rocket::response::status::BadRequest;
+use rocket::Rocket;
+use rocket_contrib::Json;
+use uuid::Uuid;
+
+pub fn mount(rocket_instance: Rocket) -> Rocket {
+    rocket_instance.mount("/data", routes![
+        //This is a list of all controller functions that are mounted
+        start_session,
+        session,
+        verdict,
+        ])
+}
+
+///Analyse a failing test case and start a diagnosis session, which suggests follow-up tests to tell the candidates apart
+#[post("/diagnosis", format = "application/json", data = "<testcase>")]
+fn start_session(context: Context, testcase: Json<TestCase>) -> Result<Json<DiagnosisSession>, BadRequest<String>> {
+    diagnosis_service::start_session(&context, testcase.into_inner()).map(Json).map_err(|e| BadRequest(Some(e)))
+}
+
+#[get("/diagnosis/<id>")]
+fn session(context: Context, id: String) -> Option<Json<DiagnosisSession>> {
+    let id = Uuid::parse_str(&id).ok()?;
+    context.diagnosis_sessions().get(&id).cloned().map(Json)
+}
+
+///Post the verdict of a follow-up test, which updates the ranking of the candidates and the suggested follow-up tests
+#[post("/diagnosis/<id>/verdict", format = "application/json", data = "<verdict>")]
+fn verdict(context: Context, id: String, verdict: Json<FollowUpVerdict>) -> Result<Json<DiagnosisSession>, BadRequest<String>> {
+    let id = Uuid::parse_str(&id).map_err(|e| BadRequest(Some(format!("Invalid session id: {}", e))))?;
+    diagnosis_service::add_verdict(&context, &id, &verdict.into_inner()).map(Json).map_err(|e| BadRequest(Some(e)))
+}
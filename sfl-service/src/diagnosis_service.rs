@@ -0,0 +1,62 @@
+use app_context::AppContext;
+use analysis_service::configured_similarity_coefficient;
+use models::diagnosis_session::DiagnosisSession;
+use models::diagnosis_session::FollowUpVerdict;
+use models::model::Transition;
+use models::similarity::ComponentCounts;
+use models::test_case::TestCase;
+use uuid::Uuid;
+
+///Analyse a failing test case and start a diagnosis session for the transitions that could not be told apart.
+/// The candidates are the transitions of the root-cause steps, or else the root-cause transition.
+pub fn start_session(context: &AppContext, testcase: TestCase) -> Result<DiagnosisSession, String> {
+    let settings = context.settings().clone();
+    let use_transition_data = settings.analysis.use_transition_data;
+    let (model, coverage_matrix, threshold_calibration, coverage_model) = {
+        let storage_service = context.storage();
+        (storage_service.model.clone(), storage_service.coverage_matrix.clone(), storage_service.threshold_calibration.clone(), testcase.to_coverage_model(&storage_service.coverage_information))
+    };
+    let (model, coverage_matrix) = match (model, coverage_matrix) {
+        (Some(model), Some(coverage_matrix)) => (model, coverage_matrix),
+        _ => return Err(String::from("A diagnosis session needs a model and a trained coverage matrix"))
+    };
+    let test_id = testcase.id;
+    let result = match context.analysis_service().analyse_with(context, testcase, &Some(model.clone()), &Some(coverage_matrix.clone()), coverage_model, &threshold_calibration) {
+        Some(result) => result,
+        None => return Err(String::from("The test case did not fail, so there is nothing to diagnose"))
+    };
+
+    let mut transitions: Vec<Transition> = vec![];
+    for transition in result.root_cause_steps.iter().filter_map(|it| it.to_transition(&model, use_transition_data)).chain(result.root_cause_transition().cloned().into_iter()) {
+        if !transitions.contains(&transition) {
+            transitions.push(transition);
+        }
+    }
+    if transitions.is_empty() {
+        return Err(format!("The result ({}) has no candidate transitions", result.group_name()));
+    }
+    let coefficient = configured_similarity_coefficient(&settings);
+    let candidates = transitions.into_iter().map(|transition| {
+        let similarity = ComponentCounts::from_matrix(&transition.to_string_with_data(use_transition_data), &coverage_matrix).map(|it| it.similarity(&coefficient)).unwrap_or(0.0);
+        (transition, similarity)
+    }).collect::<Vec<(Transition, f32)>>();
+
+    let session = DiagnosisSession::new(test_id, candidates, &model);
+    context.diagnosis_sessions().insert(session.id, session.clone());
+    Ok(session)
+}
+
+///Post the verdict of a follow-up test to a diagnosis session and return the updated session
+pub fn add_verdict(context: &AppContext, session_id: &Uuid, verdict: &FollowUpVerdict) -> Result<DiagnosisSession, String> {
+    let model = match context.storage().model.clone() {
+        Some(model) => model,
+        None => return Err(String::from("There is no model"))
+    };
+    let mut sessions = context.diagnosis_sessions();
+    let session = match sessions.get_mut(session_id) {
+        Some(session) => session,
+        None => return Err(format!("There is no diagnosis session {}", session_id))
+    };
+    session.add_verdict(verdict, &model)?;
+    Ok(session.clone())
+}
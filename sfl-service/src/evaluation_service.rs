@@ -0,0 +1,320 @@
+use app_context::AppContext;
+use models::model::TestModel;
+use models::model::Transition;
+use models::similarity::SimilarityCoefficient;
+use models::test_case::TestCase;
+use analysis_service::configured_similarity_coefficient;
+use settings::Settings;
+
+///A failing test case, annotated with the fault that actually caused the failure and optionally the group it should end up in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnotatedTestCase {
+    pub test_case: TestCase,
+    #[serde(default)]
+    pub faulty_transition: Option<Transition>,
+    #[serde(default)]
+    pub faulty_state: Option<String>,
+    #[serde(default)]
+    pub expected_group: Option<String>,
+}
+
+impl AnnotatedTestCase {
+    ///Find the components that represent the actual fault. A faulty state is represented by all transitions from or to that state.
+    pub fn fault_components(&self, model: Option<&TestModel>, use_steps_instead_of_transitions: bool, use_transition_data: bool) -> Vec<String> {
+        let mut transitions: Vec<Transition> = vec![];
+        if let Some(ref faulty_transition) = self.faulty_transition {
+            transitions.push(faulty_transition.clone());
+        }
+        if let (Some(faulty_state), Some(model)) = (self.faulty_state.as_ref(), model) {
+            transitions.append(&mut model.all_transitions().into_iter().filter(|it| &it.source == faulty_state || &it.target == faulty_state).collect::<Vec<Transition>>());
+        }
+        if use_steps_instead_of_transitions {
+            transitions.iter().map(|transition| match transition.to_step(&self.test_case.steps, use_transition_data) {
+                Some(step) => step.get_full_label_with_params(use_transition_data),
+                None => transition.attributes.label.clone()
+            }).collect()
+        } else {
+            transitions.iter().map(|it| it.to_string_with_data(use_transition_data)).collect()
+        }
+    }
+}
+
+///The settings that influence the quality of the diagnosis
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SettingCombination {
+    pub similarity_coefficient: SimilarityCoefficient,
+    pub similarity_threshold: f32,
+    pub number_of_pairs_to_include_for_order: i32,
+    pub use_steps_instead_of_transitions_for_analysis: bool,
+}
+
+impl SettingCombination {
+    ///The combination of settings that is used in the given settings
+    pub fn current(settings: &Settings) -> SettingCombination {
+        SettingCombination {
+            similarity_coefficient: configured_similarity_coefficient(settings),
+            similarity_threshold: settings.analysis.similarity_threshold,
+            number_of_pairs_to_include_for_order: settings.analysis.number_of_pairs_to_include_for_order,
+            use_steps_instead_of_transitions_for_analysis: settings.analysis.use_steps_instead_of_transitions_for_analysis,
+        }
+    }
+
+    ///Store this combination of settings in the settings.
+    /// The threshold calibration is turned off, because a calibrated threshold would replace the similarity threshold that is evaluated.
+    pub fn apply(&self, settings: &mut Settings) {
+        settings.analysis.threshold_calibration = String::from("none");
+        settings.analysis.similarity_coefficient = format!("{:?}", self.similarity_coefficient);
+        settings.analysis.similarity_threshold = self.similarity_threshold;
+        settings.analysis.number_of_pairs_to_include_for_order = self.number_of_pairs_to_include_for_order;
+        settings.analysis.use_steps_instead_of_transitions_for_analysis = self.use_steps_instead_of_transitions_for_analysis;
+    }
+}
+
+///The annotated test cases and the settings that should be evaluated. Settings without any values keep their current value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluationRequest {
+    pub test_cases: Vec<AnnotatedTestCase>,
+    #[serde(default)]
+    pub similarity_coefficients: Vec<SimilarityCoefficient>,
+    #[serde(default)]
+    pub similarity_thresholds: Vec<f32>,
+    #[serde(default)]
+    pub numbers_of_pairs_to_include_for_order: Vec<i32>,
+    #[serde(default)]
+    pub use_steps_instead_of_transitions_for_analysis: Vec<bool>,
+}
+
+impl EvaluationRequest {
+    ///Create all combinations of the requested settings. The settings that are not requested keep their value in the given settings.
+    pub fn setting_combinations(&self, settings: &Settings) -> Vec<SettingCombination> {
+        let current = SettingCombination::current(settings);
+        let coefficients = if self.similarity_coefficients.is_empty() { vec![current.similarity_coefficient] } else { self.similarity_coefficients.clone() };
+        let thresholds = if self.similarity_thresholds.is_empty() { vec![current.similarity_threshold] } else { self.similarity_thresholds.clone() };
+        let pairs = if self.numbers_of_pairs_to_include_for_order.is_empty() { vec![current.number_of_pairs_to_include_for_order] } else { self.numbers_of_pairs_to_include_for_order.clone() };
+        let use_steps = if self.use_steps_instead_of_transitions_for_analysis.is_empty() { vec![current.use_steps_instead_of_transitions_for_analysis] } else { self.use_steps_instead_of_transitions_for_analysis.clone() };
+
+        let mut combinations = vec![];
+        for use_steps_instead_of_transitions_for_analysis in use_steps.iter() {
+            for number_of_pairs_to_include_for_order in pairs.iter() {
+                for similarity_coefficient in coefficients.iter() {
+                    for similarity_threshold in thresholds.iter() {
+                        combinations.push(SettingCombination {
+                            similarity_coefficient: *similarity_coefficient,
+                            similarity_threshold: *similarity_threshold,
+                            number_of_pairs_to_include_for_order: *number_of_pairs_to_include_for_order,
+                            use_steps_instead_of_transitions_for_analysis: *use_steps_instead_of_transitions_for_analysis,
+                        });
+                    }
+                }
+            }
+        }
+        combinations
+    }
+}
+
+///The quality of the diagnosis for a set of annotated test cases.
+/// The wasted effort is the average number of components that have to be inspected before the fault is found, where ties count for half.
+/// The EXAM score is the average fraction of the components that have to be inspected to find the fault (including the fault itself).
+/// The top-n accuracy is the fraction of test cases for which the fault is ranked in the first n components.
+/// The grouping precision and recall are calculated over all pairs of test cases with an expected group.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiagnosticQuality {
+    pub number_of_test_cases: usize,
+    pub number_of_located_faults: usize,
+    pub wasted_effort: f32,
+    pub exam_score: f32,
+    pub top_1: f32,
+    pub top_3: f32,
+    pub top_5: f32,
+    pub grouping_precision: Option<f32>,
+    pub grouping_recall: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EvaluationResult {
+    pub settings: SettingCombination,
+    pub quality: DiagnosticQuality,
+}
+
+///Evaluate the diagnostic quality for every requested combination of settings.
+/// For every combination the service is trained again in the scratch context of the instance, so the settings and the training of the instance itself are not affected, also not when the evaluation fails halfway.
+pub fn evaluate(context: &AppContext, request: &EvaluationRequest) -> Vec<EvaluationResult> {
+    let scratch = context.scratch();
+    let scratch_context = scratch.as_ref().unwrap();
+    let service = scratch_context.analysis_service();
+    let combinations = request.setting_combinations(&scratch_context.settings());
+    combinations.into_iter().map(|combination| {
+        println!("Evaluating {:?}", combination);
+        combination.apply(&mut scratch_context.settings());
+        service.train(scratch_context);
+        EvaluationResult {
+            quality: evaluate_training(scratch_context, &request.test_cases),
+            settings: combination,
+        }
+    }).collect::<Vec<EvaluationResult>>()
+}
+
+///Evaluate the diagnostic quality of the current training of the context
+fn evaluate_training(context: &AppContext, test_cases: &Vec<AnnotatedTestCase>) -> DiagnosticQuality {
+    let service = context.analysis_service();
+    let (use_steps_instead_of_transitions, use_transition_data) = {
+        let settings = context.settings();
+        (settings.analysis.use_steps_instead_of_transitions_for_analysis, settings.analysis.use_transition_data)
+    };
+    let (ranking, model) = {
+        let storage_service = context.storage();
+        let ranking = match storage_service.coverage_matrix {
+            Some(ref coverage_matrix) => coverage_matrix.data.keys().map(|component| (component.clone(), service.similarity_coefficient(context, component, coverage_matrix))).collect::<Vec<(String, f32)>>(),
+            None => vec![]
+        };
+        (ranking, storage_service.model.clone())
+    };
+
+    let mut wasted_effort = 0.0;
+    let mut exam_score = 0.0;
+    let mut number_of_located_faults = 0;
+    let mut top_n = [0, 0, 0];
+    let mut groups = vec![];
+    for annotated_test_case in test_cases.iter() {
+        let fault_components = annotated_test_case.fault_components(model.as_ref(), use_steps_instead_of_transitions, use_transition_data);
+        match fault_position(&ranking, &fault_components) {
+            Some((effort, rank)) => {
+                number_of_located_faults += 1;
+                wasted_effort += effort;
+                exam_score += (effort + 1.0) / ranking.len() as f32;
+                for (i, n) in [1, 3, 5].iter().enumerate() {
+                    if rank <= *n {
+                        top_n[i] += 1;
+                    }
+                }
+            },
+            None => {
+                //The fault can not be found, so everything has to be inspected
+                wasted_effort += ranking.len() as f32;
+                exam_score += 1.0;
+            }
+        }
+        let predicted_group = service.analyse(context, annotated_test_case.test_case.clone()).map(|it| it.group_name().clone());
+        groups.push((annotated_test_case.expected_group.clone(), predicted_group));
+    }
+
+    let number_of_test_cases = test_cases.len();
+    let average = |total: f32| if number_of_test_cases == 0 { 0.0 } else { total / number_of_test_cases as f32 };
+    let (grouping_precision, grouping_recall) = grouping_quality(&groups);
+    DiagnosticQuality {
+        number_of_test_cases,
+        number_of_located_faults,
+        wasted_effort: average(wasted_effort),
+        exam_score: average(exam_score),
+        top_1: average(top_n[0] as f32),
+        top_3: average(top_n[1] as f32),
+        top_5: average(top_n[2] as f32),
+        grouping_precision,
+        grouping_recall,
+    }
+}
+
+///Find the position of the best ranked fault component in the ranking of all components.
+/// Returns the wasted effort (the number of components ranked higher, plus half of the other components with the same coefficient) and the rank (starting at 1).
+fn fault_position(ranking: &Vec<(String, f32)>, fault_components: &Vec<String>) -> Option<(f32, usize)> {
+    let fault_coefficient = ranking.iter().filter(|it| fault_components.contains(&it.0)).map(|it| it.1).fold(None, |max: Option<f32>, it| match max {
+        Some(max) if max >= it => Some(max),
+        _ => Some(it)
+    });
+    fault_coefficient.map(|fault_coefficient| {
+        let higher = ranking.iter().filter(|it| it.1 > fault_coefficient).count();
+        let ties = ranking.iter().filter(|it| it.1 == fault_coefficient).count() - 1;
+        (higher as f32 + ties as f32 / 2.0, higher + 1)
+    })
+}
+
+///Calculate the pairwise precision and recall of the predicted groups compared to the expected groups.
+/// Only test cases with both an expected and a predicted group are taken into account.
+fn grouping_quality(groups: &Vec<(Option<String>, Option<String>)>) -> (Option<f32>, Option<f32>) {
+    let labelled = groups.iter().filter_map(|group| match group {
+        (Some(expected), Some(predicted)) => Some((expected, predicted)),
+        _ => None
+    }).collect::<Vec<(&String, &String)>>();
+
+    let mut true_positives = 0;
+    let mut predicted_positives = 0;
+    let mut actual_positives = 0;
+    for i in 0..labelled.len() {
+        for j in (i + 1)..labelled.len() {
+            let same_expected = labelled[i].0 == labelled[j].0;
+            let same_predicted = labelled[i].1 == labelled[j].1;
+            if same_expected { actual_positives += 1; }
+            if same_predicted { predicted_positives += 1; }
+            if same_expected && same_predicted { true_positives += 1; }
+        }
+    }
+    let precision = if predicted_positives > 0 { Some(true_positives as f32 / predicted_positives as f32) } else { None };
+    let recall = if actual_positives > 0 { Some(true_positives as f32 / actual_positives as f32) } else { None };
+    (precision, recall)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use adapters::TestRecord;
+    use models::test_case::TestResult;
+
+    #[test]
+    fn check_fault_position() {
+        let ranking = vec![("a".to_string(), 0.9), ("b".to_string(), 0.7), ("c".to_string(), 0.7), ("d".to_string(), 0.1)];
+
+        assert_eq!(Some((0.0, 1)), fault_position(&ranking, &vec!["a".to_string()]));
+        assert_eq!(Some((1.5, 2)), fault_position(&ranking, &vec!["c".to_string()]));
+        assert_eq!(Some((1.5, 2)), fault_position(&ranking, &vec!["d".to_string(), "b".to_string()]));
+        assert_eq!(None, fault_position(&ranking, &vec!["e".to_string()]));
+    }
+
+    #[test]
+    fn check_thresholds_are_evaluated_with_calibration() {
+        //Given
+        let context = AppContext::from_settings_file();
+        context.settings().analysis.threshold_calibration = String::from("top_k");
+        let failing = vec![
+            TestRecord::new(3, TestResult::failed, vec!["a".to_string(), "b".to_string()], None).to_test_case(),
+            TestRecord::new(4, TestResult::failed, vec!["b".to_string(), "c".to_string()], None).to_test_case(),
+        ];
+        {
+            let mut storage_service = context.storage();
+            storage_service.set_passing_tests(vec![
+                TestRecord::new(1, TestResult::passed, vec!["a".to_string()], None).to_test_case(),
+                TestRecord::new(2, TestResult::passed, vec!["a".to_string(), "c".to_string()], None).to_test_case(),
+            ]);
+            storage_service.set_failing_tests(failing.clone());
+        }
+        let request = EvaluationRequest {
+            test_cases: failing.into_iter().map(|test_case| AnnotatedTestCase { test_case, faulty_transition: None, faulty_state: None, expected_group: Some("x".to_string()) }).collect(),
+            similarity_coefficients: vec![SimilarityCoefficient::ochiai],
+            similarity_thresholds: vec![0.45, 0.9],
+            numbers_of_pairs_to_include_for_order: vec![0],
+            use_steps_instead_of_transitions_for_analysis: vec![true],
+        };
+
+        //When
+        let results = evaluate(&context, &request);
+
+        //Then
+        assert_eq!((None, Some(0.0)), (results[0].quality.grouping_precision, results[0].quality.grouping_recall));
+        assert_eq!((Some(1.0), Some(1.0)), (results[1].quality.grouping_precision, results[1].quality.grouping_recall));
+        assert_eq!("top_k", context.settings().analysis.threshold_calibration);
+    }
+
+    #[test]
+    fn check_grouping_quality() {
+        let groups = vec![
+            (Some("x".to_string()), Some("State 1".to_string())),
+            (Some("x".to_string()), Some("State 1".to_string())),
+            (Some("x".to_string()), Some("Transition ?a".to_string())),
+            (Some("y".to_string()), Some("Transition ?a".to_string())),
+            (None, Some("Transition ?a".to_string())),
+        ];
+
+        //Expected pairs: (0,1), (0,2), (1,2). Predicted pairs: (0,1), (2,3).
+        assert_eq!((Some(0.5), Some(1.0 / 3.0)), grouping_quality(&groups));
+        assert_eq!((None, None), grouping_quality(&vec![]));
+    }
+}
@@ -0,0 +1,405 @@
+use chrono::TimeZone;
+use chrono::Utc;
+use evaluation_service::AnnotatedTestCase;
+use models::model::ChildModel;
+use models::model::ChildModelAttribute;
+use models::model::State;
+use models::model::StateAttribute;
+use models::model::Sts;
+use models::model::TestModel;
+use models::model::TraceProperties;
+use models::model::Transition;
+use models::model::TransitionAttribute;
+use models::test_case::ExpectedLabel;
+use models::test_case::Label;
+use models::test_case::Step;
+use models::test_case::TestCase;
+use models::test_case::TestResult;
+use std::cmp::max;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+///The kinds of faults that can be injected in the generated system under test
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum FaultKind {
+    ///The SUT gives a different output on the faulty transition
+    wrong_output,
+    ///The SUT ends up in another state than the target of the faulty transition
+    wrong_target,
+    ///The SUT gives the outputs of the faulty transition and the transition after it in the wrong order
+    order_swap,
+    ///The SUT gives a different output on the faulty transition, but only when the data value of the step is 5 or higher
+    data_guard,
+}
+
+///The configuration of a generated scenario. The same seed always results in the same model and traces.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneratorConfig {
+    pub seed: u64,
+    pub number_of_states: usize,
+    pub branching_factor: usize,
+    pub number_of_child_behaviours: usize,
+    pub number_of_states_per_child_behaviour: usize,
+    pub number_of_tests: usize,
+    pub max_trace_length: usize,
+    pub fault_kind: FaultKind,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> GeneratorConfig {
+        GeneratorConfig {
+            seed: 42,
+            number_of_states: 10,
+            branching_factor: 2,
+            number_of_child_behaviours: 0,
+            number_of_states_per_child_behaviour: 4,
+            number_of_tests: 50,
+            max_trace_length: 20,
+            fault_kind: FaultKind::wrong_output,
+        }
+    }
+}
+
+///The fault that is injected in the generated system under test
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InjectedFault {
+    pub kind: FaultKind,
+    pub transition: Transition,
+    pub second_transition: Option<Transition>,
+    pub wrong_target: Option<String>,
+    pub description: String,
+}
+
+///A generated model with simulated test cases and coverage information, where the failing test cases are annotated with the injected fault
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeneratedScenario {
+    pub model: TestModel,
+    pub fault: InjectedFault,
+    pub passing: Vec<TestCase>,
+    pub failing: Vec<AnnotatedTestCase>,
+    pub coverage_information: Vec<TestModel>,
+}
+
+///A small xorshift random number generator, so that scenarios can be reproduced with their seed
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn new(seed: u64) -> Random {
+        Random { state: (seed ^ 0x9E37_79B9_7F4A_7C15) | 1 }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    ///A random number in the range [0, bound)
+    fn below(&mut self, bound: usize) -> usize {
+        if bound == 0 { 0 } else { (self.next() % bound as u64) as usize }
+    }
+
+    ///A random (version 4) uuid, so that the test run id is reproduced with the seed as well
+    fn uuid(&mut self) -> Uuid {
+        let (high, low) = (self.next(), self.next());
+        let mut bytes = [0u8; 16];
+        for index in 0..8 {
+            bytes[index] = (high >> (8 * index)) as u8;
+            bytes[index + 8] = (low >> (8 * index)) as u8;
+        }
+        Uuid::from_random_bytes(bytes)
+    }
+}
+
+///An sts as it is generated, before the coverage is added
+struct GeneratedSts {
+    name: String,
+    states: Vec<String>,
+    transitions: Vec<Transition>,
+}
+
+impl GeneratedSts {
+    ///Generate an sts where every state is reachable from the first state and every state has at least one outgoing transition
+    fn generate(name: String, number_of_states: usize, branching_factor: usize, random: &mut Random, label_counter: &mut usize) -> GeneratedSts {
+        let number_of_states = max(number_of_states, 2);
+        let states = (0..number_of_states).map(|i| format!("{}_{}", name, i)).collect::<Vec<String>>();
+        let mut transitions = vec![];
+        //Make every state reachable
+        for i in 1..number_of_states {
+            let source = random.below(i);
+            transitions.push(GeneratedSts::new_transition(&states[source], &states[i], random, label_counter));
+        }
+        //Add branches
+        for i in 0..number_of_states {
+            let number_of_outgoing = 1 + random.below(max(branching_factor, 1));
+            while transitions.iter().filter(|it: &&Transition| it.source == states[i]).count() < number_of_outgoing {
+                let target = random.below(number_of_states);
+                transitions.push(GeneratedSts::new_transition(&states[i], &states[target], random, label_counter));
+            }
+        }
+        GeneratedSts { name, states, transitions }
+    }
+
+    ///Create a transition with a unique label, which is randomly a stimulus or a response
+    fn new_transition(source: &String, target: &String, random: &mut Random, label_counter: &mut usize) -> Transition {
+        *label_counter += 1;
+        let label = if random.below(2) == 0 { format!("?in{}", label_counter) } else { format!("!out{}", label_counter) };
+        Transition::new(source.clone(), target.clone(), TransitionAttribute::new(label, None, None))
+    }
+
+    fn outgoing(&self, state: &String) -> Vec<usize> {
+        self.transitions.iter().enumerate().filter(|&(_, it)| &it.source == state).map(|(i, _)| i).collect()
+    }
+
+    ///Convert to an sts of the model, optionally with the coverage of a test
+    fn to_sts(&self, children: Vec<ChildModel>, coverage: Option<(&HashSet<usize>, &HashSet<String>)>, passed: Option<bool>) -> Sts {
+        let states = self.states.iter().map(|id| State::new(id.clone(), StateAttribute::new(id.clone(), String::from("integer"), coverage.map(|c| c.1.contains(id))))).collect::<Vec<State>>();
+        let start_states = vec![states[0].to_start_state()];
+        let transitions = self.transitions.iter().enumerate().map(|(i, transition)| transition.clone_and_update(|it| it.attributes.covered = coverage.map(|c| c.0.contains(&i)))).collect::<Vec<Transition>>();
+        Sts::new(self.name.clone(), states, children, start_states, transitions, vec![], None, None, passed.map(|it| TraceProperties::new(Some(it))))
+    }
+}
+
+///The injected fault, with indices of the transitions in the main sts
+struct FaultPlan {
+    kind: FaultKind,
+    transition: usize,
+    second_transition: Option<usize>,
+    wrong_target: Option<String>,
+}
+
+impl FaultPlan {
+    ///Choose a fault of the requested kind. If that kind can not be injected (e.g. there are no consecutive transitions to swap), a wrong output is injected instead.
+    fn choose(kind: FaultKind, sts: &GeneratedSts, random: &mut Random) -> FaultPlan {
+        let transitions = &sts.transitions;
+        match kind {
+            FaultKind::wrong_target => {
+                let transition = random.below(transitions.len());
+                let other_states = sts.states.iter().filter(|it| **it != transitions[transition].target).collect::<Vec<&String>>();
+                let wrong_target = other_states[random.below(other_states.len())].clone();
+                return FaultPlan { kind, transition, second_transition: None, wrong_target: Some(wrong_target) };
+            },
+            FaultKind::order_swap => {
+                let mut consecutive = vec![];
+                for (i, first) in transitions.iter().enumerate() {
+                    for (j, second) in transitions.iter().enumerate() {
+                        if i != j && first.target == second.source && first.attributes.label.starts_with('!') && second.attributes.label.starts_with('!') {
+                            consecutive.push((i, j));
+                        }
+                    }
+                }
+                if !consecutive.is_empty() {
+                    let (transition, second_transition) = consecutive[random.below(consecutive.len())];
+                    return FaultPlan { kind, transition, second_transition: Some(second_transition), wrong_target: None };
+                }
+            },
+            _ => {}
+        }
+        let responses = (0..transitions.len()).filter(|i| transitions[*i].attributes.label.starts_with('!')).collect::<Vec<usize>>();
+        let transition = if responses.is_empty() { random.below(transitions.len()) } else { responses[random.below(responses.len())] };
+        let kind = if kind == FaultKind::data_guard { kind } else { FaultKind::wrong_output };
+        FaultPlan { kind, transition, second_transition: None, wrong_target: None }
+    }
+
+    fn to_injected_fault(&self, sts: &GeneratedSts) -> InjectedFault {
+        let transition = sts.transitions[self.transition].clone();
+        let description = match self.kind {
+            FaultKind::wrong_output => format!("Wrong output on {}", transition.to_string_with_data(false)),
+            FaultKind::wrong_target => format!("Wrong target {} of {}", self.wrong_target.clone().unwrap_or(String::new()), transition.to_string_with_data(false)),
+            FaultKind::order_swap => format!("Swapped order of {} and {}", transition.to_string_with_data(false), self.second_transition.map(|it| sts.transitions[it].to_string_with_data(false)).unwrap_or(String::new())),
+            FaultKind::data_guard => format!("Wrong output on {} for data values of 5 or higher", transition.to_string_with_data(false)),
+        };
+        InjectedFault {
+            kind: self.kind,
+            transition,
+            second_transition: self.second_transition.map(|it| sts.transitions[it].clone()),
+            wrong_target: self.wrong_target.clone(),
+            description,
+        }
+    }
+}
+
+///Generate a random model, inject a fault and simulate the test cases and their coverage
+pub fn generate(config: &GeneratorConfig) -> GeneratedScenario {
+    let mut random = Random::new(config.seed);
+    let mut label_counter = 0;
+    let mut stss = vec![GeneratedSts::generate(String::from("main"), config.number_of_states, config.branching_factor, &mut random, &mut label_counter)];
+    for child in 0..config.number_of_child_behaviours {
+        stss.push(GeneratedSts::generate(format!("child{}", child + 1), config.number_of_states_per_child_behaviour, config.branching_factor, &mut random, &mut label_counter));
+    }
+    let fault_plan = FaultPlan::choose(config.fault_kind, &stss[0], &mut random);
+    let fault = fault_plan.to_injected_fault(&stss[0]);
+    let test_run_id = random.uuid();
+
+    let model = to_test_model(test_run_id, &stss, None, None);
+    let mut passing = vec![];
+    let mut failing = vec![];
+    let mut coverage_information = vec![];
+    for test_id in 1..(config.number_of_tests as i64 + 1) {
+        let (test_case, coverage) = simulate(test_id, test_run_id, &stss, &fault_plan, config.max_trace_length, &mut random);
+        if test_case.verdict == Some(TestResult::passed) {
+            passing.push(test_case);
+        } else {
+            failing.push(AnnotatedTestCase {
+                test_case,
+                faulty_transition: Some(fault.transition.clone()),
+                faulty_state: None,
+                expected_group: Some(fault.description.clone()),
+            });
+        }
+        coverage_information.push(coverage);
+    }
+
+    GeneratedScenario { model, fault, passing, failing, coverage_information }
+}
+
+///Create the model of the generated stss, where the first sts has all other stss as its child behaviours
+fn to_test_model(test_run_id: Uuid, stss: &Vec<GeneratedSts>, coverage: Option<(&Vec<HashSet<usize>>, &Vec<HashSet<String>>)>, passed: Option<bool>) -> TestModel {
+    let children = stss.iter().enumerate().skip(1).map(|(k, child)| {
+        let partially_covered = coverage.map(|c| !c.0[k].is_empty());
+        ChildModel::new(child.name.clone(), ChildModelAttribute::new(child.name.clone(), String::from("sts"), format!("{:x}", k), partially_covered))
+    }).collect::<Vec<ChildModel>>();
+    let model_stss = stss.iter().enumerate().map(|(k, sts)| {
+        let sts_children = if k == 0 { children.clone() } else { vec![] };
+        sts.to_sts(sts_children, coverage.map(|c| (&c.0[k], &c.1[k])), passed)
+    }).collect::<Vec<Sts>>();
+    TestModel::new(test_run_id, model_stss, None)
+}
+
+///Convert the label of a transition (e.g. `?in1`) to the label of a step
+fn to_label(transition_label: &str) -> Label {
+    let direction = if transition_label.starts_with('?') { "stimulus" } else { "response" };
+    Label::new(transition_label.trim_left_matches(|c: char| c == '?' || c == '!').to_string(), String::from(direction), Some(String::from("default")))
+}
+
+///Simulate a single test, which is a random walk through every sts (the main sts first), while the system under test contains the injected fault.
+/// The data value of each step is stored in its notes. The walk stops at the first failure.
+fn simulate(test_id: i64, test_run_id: Uuid, stss: &Vec<GeneratedSts>, fault: &FaultPlan, max_trace_length: usize, random: &mut Random) -> (TestCase, TestModel) {
+    let mut steps: Vec<Step> = vec![];
+    let mut covered_transitions: Vec<HashSet<usize>> = stss.iter().map(|_| HashSet::new()).collect();
+    let mut covered_states: Vec<HashSet<String>> = stss.iter().map(|_| HashSet::new()).collect();
+    //The expected label and the observed label (if any) of the failure
+    let mut failure: Option<(String, Option<String>)> = None;
+    let new_step = |label: &str, step_number: usize, value: usize| {
+        Step::new(to_label(label), Utc.timestamp(1_500_000_000 + test_id * 10_000 + step_number as i64, 0), Some(vec![format!("value={}", value)]), step_number as i32, None, None, None, None)
+    };
+
+    'walk: for (k, sts) in stss.iter().enumerate() {
+        let mut state = sts.states[0].clone();
+        let mut pending: Option<usize> = None;
+        let mut sut_state: Option<String> = None;
+        covered_states[k].insert(state.clone());
+        let length = 1 + random.below(max(max_trace_length / stss.len(), 1));
+        for _ in 0..length {
+            let index = match pending.take() {
+                Some(index) => index,
+                None => {
+                    let outgoing = sts.outgoing(&state);
+                    if outgoing.is_empty() { break; }
+                    outgoing[random.below(outgoing.len())]
+                }
+            };
+            let transition = &sts.transitions[index];
+            let value = random.below(10);
+            covered_transitions[k].insert(index);
+
+            if k == 0 {
+                //The SUT is in another state than the model because of a wrong target
+                if let Some(actual_state) = sut_state.take() {
+                    let actual_outgoing = sts.outgoing(&actual_state);
+                    let observed = if actual_outgoing.is_empty() { None } else { Some(sts.transitions[actual_outgoing[random.below(actual_outgoing.len())]].attributes.label.clone()) };
+                    if observed.as_ref() != Some(&transition.attributes.label) {
+                        failure = Some((transition.attributes.label.clone(), observed));
+                        break 'walk;
+                    }
+                }
+                if index == fault.transition {
+                    match fault.kind {
+                        FaultKind::wrong_output => {
+                            failure = Some((transition.attributes.label.clone(), Some(format!("{}_wrong", transition.attributes.label))));
+                            break 'walk;
+                        },
+                        FaultKind::data_guard if value >= 5 => {
+                            failure = Some((transition.attributes.label.clone(), Some(format!("{}_wrong", transition.attributes.label))));
+                            break 'walk;
+                        },
+                        FaultKind::wrong_target => sut_state = fault.wrong_target.clone(),
+                        FaultKind::order_swap => {
+                            let next_outgoing = sts.outgoing(&transition.target);
+                            let next = if next_outgoing.is_empty() { None } else { Some(next_outgoing[random.below(next_outgoing.len())]) };
+                            if next.is_some() && next == fault.second_transition {
+                                let second_label = sts.transitions[next.unwrap()].attributes.label.clone();
+                                failure = Some((transition.attributes.label.clone(), Some(second_label)));
+                                break 'walk;
+                            }
+                            pending = next;
+                        },
+                        _ => {}
+                    }
+                }
+            }
+
+            let step_number = steps.len();
+            steps.push(new_step(&transition.attributes.label, step_number, value));
+            state = transition.target.clone();
+            covered_states[k].insert(state.clone());
+        }
+    }
+
+    let passed = failure.is_none();
+    let (error_message, expected_labels) = match failure {
+        Some((expected, observed)) => {
+            let step_number = steps.len();
+            let message = match observed {
+                Some(observed) => {
+                    steps.push(new_step(&observed, step_number, 0));
+                    format!("Expected {} but received {}", expected, observed)
+                },
+                None => format!("Expected {} but received nothing", expected)
+            };
+            (Some(message), Some(vec![ExpectedLabel::new(to_label(&expected), None)]))
+        },
+        None => (None, None)
+    };
+    let last_step = max(steps.len() as i32 - 1, 0);
+    let verdict = if passed { TestResult::passed } else { TestResult::failed };
+    let test_case = TestCase::new(Some(test_id), Some(verdict), error_message, steps, last_step, expected_labels, None);
+    let mut coverage = to_test_model(test_run_id, stss, Some((&covered_transitions, &covered_states)), Some(passed));
+    coverage.testcase_id = Some(test_id);
+    (test_case, coverage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_generated_scenario() {
+        //Given
+        let config = GeneratorConfig { number_of_child_behaviours: 1, ..GeneratorConfig::default() };
+
+        //When
+        let scenario = generate(&config);
+        let same_scenario = generate(&config);
+
+        //Then
+        assert_eq!(scenario.model.stss.len(), 2);
+        assert_eq!(scenario.model.stss[0].states.len(), config.number_of_states);
+        assert_eq!(scenario.passing.len() + scenario.failing.len(), config.number_of_tests);
+        assert_eq!(scenario.coverage_information.len(), config.number_of_tests);
+        assert_eq!(scenario.fault, same_scenario.fault);
+        assert_eq!(scenario.model.test_run_id, same_scenario.model.test_run_id);
+        assert_eq!(scenario.passing.iter().map(|it| it.get_steps(false)).collect::<Vec<_>>(), same_scenario.passing.iter().map(|it| it.get_steps(false)).collect::<Vec<_>>());
+        //A wrong output always fails, so only the failing tests cover the faulty transition
+        for coverage in scenario.coverage_information.iter() {
+            let fault_covered = coverage.all_transitions().iter().any(|it| *it == scenario.fault.transition && it.attributes.covered == Some(true));
+            let passed = coverage.stss.iter().all(|it| it.trace_properties.as_ref().and_then(|props| props.passed) == Some(true));
+            assert_eq!(fault_covered, !passed);
+        }
+    }
+}
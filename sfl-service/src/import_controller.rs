@@ -0,0 +1,44 @@
+use adapters::get_adapter;
+use adapters::TestDataAdapter;
+use models::test_case::TestResult;
+use rocket::response::status::BadRequest;
+use rocket::Rocket;
+use app_context::Context;
+
+pub fn mount(rocket_instance: Rocket) -> Rocket {
+    rocket_instance.mount("/data", routes![
+        //This is a list of all controller functions that are mounted
+        import_tests,
+        import_matrix,
+        ])
+}
+
+///Import the tests of another test tool (junit, json or csv) as test cases. These are added to the passing and failing tests.
+/// Every covered component becomes a step, so the tests can be analysed when the steps are used for the analysis.
+#[post("/import/<format>/tests", data = "<input>")]
+fn import_tests(context: Context, format: String, input: String) -> Result<&'static str, BadRequest<String>> {
+    let adapter = find_adapter(&format)?;
+    let test_cases = adapter.to_test_cases(&input).map_err(|e| BadRequest(Some(e)))?;
+    let mut storage_service = context.storage();
+    for test_case in test_cases {
+        if test_case.verdict == Some(TestResult::passed) {
+            storage_service.passing.push(test_case);
+        } else {
+            storage_service.failing.push(test_case);
+        }
+    }
+    Ok("tests imported")
+}
+
+///Import the tests of another test tool (junit, json or csv) directly as the coverage matrix, which replaces the current coverage matrix
+#[post("/import/<format>/matrix", data = "<input>")]
+fn import_matrix(context: Context, format: String, input: String) -> Result<&'static str, BadRequest<String>> {
+    let adapter = find_adapter(&format)?;
+    let coverage_matrix = adapter.to_coverage_matrix(&input).map_err(|e| BadRequest(Some(e)))?;
+    context.storage().set_coverage_matrix(coverage_matrix);
+    Ok("coverage matrix imported")
+}
+
+fn find_adapter(format: &str) -> Result<Box<TestDataAdapter>, BadRequest<String>> {
+    get_adapter(format).ok_or(BadRequest(Some(format!("Unknown format {}. Use junit, json or csv.", format))))
+}
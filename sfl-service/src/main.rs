@@ -9,8 +9,6 @@ extern crate serde_json;
 extern crate uuid;
 extern crate chrono;
 #[macro_use]
-extern crate lazy_static;
-#[macro_use]
 extern crate prettytable;
 extern crate indexmap;
 extern crate rulinalg;
@@ -27,65 +25,63 @@ mod storage_service;
 mod analysis_service;
 mod settings;
 mod configuration_controller;
+mod matrix_controller;
+mod adapters;
+mod import_controller;
+mod evaluation_service;
+mod generator;
+mod batch_analysis_service;
+mod clustering_service;
+mod visualisation;
+mod report_controller;
+mod report_service;
+mod run_comparison_service;
+mod coverage_summary_service;
+mod minimisation_service;
+mod diagnosis_service;
+mod diagnosis_controller;
+mod app_context;
+mod progress_sink;
 
 use rocket::Rocket;
-use reqwest::{Client, Url};
-use settings::Settings;
-use reqwest::header::ContentType;
-use std::thread;
-use std::sync::Mutex;
-use std::sync::MutexGuard;
-
-lazy_static! {
-    //storage for the settings singleton and the current progress
-    static ref SETTINGS: Mutex<Settings> = Mutex::new(Settings::unwrap(Settings::new()));
-    static ref PROGRESS: Mutex<f32> = Mutex::new(0.0 as f32);
-}
-
-pub fn get_settings<'a>() -> MutexGuard<'a, Settings> {
-    return match SETTINGS.lock() {
-        Ok(s) => s,
-        Err(poisoned) => poisoned.into_inner()
-    };
-}
-
-fn reset_progress() {
-    *PROGRESS.lock().unwrap() = 0.0;
-}
-
-fn send_progress(progress: f32) {
-    let pf = move ||{
-        let mut p = PROGRESS.lock().unwrap();
-        if progress > *p {
-            print!("Send progress of {} to Atana. ", progress);
-            let client = Client::new();
-            let res = client.post(Url::parse(&get_settings().analysis.progress_endpoint).unwrap())
-                .body(progress.to_string())
-                .header(ContentType::json())
-                .send();
-            match res {
-                Ok(response) => println!("Success: {:?}", response),
-                Err(error) => println!("An error occurred: {:?}", error)
-            };
-            *p = progress;
-        }
-    };
-    if get_settings().analysis.use_thread_for_progress {
-        thread::spawn(pf);
-    }
-    else{
-        pf();
-    }
-}
+use std::env;
+use std::process;
+use app_context::AppContext;
 
-fn create_rocket() -> Rocket {
-    let mut rocket_instance = rocket::ignite();
+///Create a Rocket instance that serves the given context, so multiple instances can run in the same process
+fn create_rocket(context: AppContext) -> Rocket {
+    let mut rocket_instance = rocket::ignite().manage(context);
     rocket_instance = data_receiving_controller::mount(rocket_instance);
     rocket_instance = test_analysis_controller::analysis_controller::mount(rocket_instance);
     rocket_instance = configuration_controller::mount(rocket_instance);
+    rocket_instance = matrix_controller::mount(rocket_instance);
+    rocket_instance = import_controller::mount(rocket_instance);
+    rocket_instance = report_controller::mount(rocket_instance);
+    rocket_instance = diagnosis_controller::mount(rocket_instance);
     rocket_instance
 }
 
 fn main() {
-    create_rocket().launch();
+    let context = AppContext::from_settings_file();
+    //Render a model as DOT instead of starting the service: --model-dot <model.json> [--matrix <matrix.arff>] [--coverage <coverage.json>]
+    let arguments = env::args().skip(1).collect::<Vec<String>>();
+    if arguments.first().map(|it| it == "--model-dot").unwrap_or(false) {
+        match visualisation::dot_from_arguments(&context.settings(), &arguments[1..]) {
+            Ok(dot) => print!("{}", dot),
+            Err(error) => {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    //Train on the given files and write an HTML report instead of starting the service: --report [--model <model.json>] --passing <tests.json> --failing <tests.json> [--coverages <coverage.json>] [--output <report.html>]
+    if arguments.first().map(|it| it == "--report").unwrap_or(false) {
+        if let Err(error) = report_service::report_from_arguments(&context, &arguments[1..]) {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+        return;
+    }
+    create_rocket(context).launch();
 }
\ No newline at end of file
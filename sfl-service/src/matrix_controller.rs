@@ -0,0 +1,174 @@
+use models::coverage_matrix::CoverageMatrix;
+use models::coverage_matrix::MatrixFormat;
+use rocket::http::Accept;
+use rocket::http::ContentType;
+use rocket::response::content::Content;
+use rocket::response::status::BadRequest;
+use rocket::Rocket;
+use app_context::AppContext;
+use app_context::Context;
+use analysis_service::configured_similarity_coefficient;
+use models::error_signature::rank_per_error_signature;
+use models::error_signature::SignatureRanking;
+use rocket_contrib::Json;
+use models::tag_filter::TagFilter;
+use models::flakiness::FlakyTrace;
+use models::prioritisation::rank_by_diagnostic_gain;
+use models::prioritisation::reduce_suite;
+use models::prioritisation::SuiteReduction;
+use models::prioritisation::TestGain;
+
+pub fn mount(rocket_instance: Rocket) -> Rocket {
+    rocket_instance.mount("/data", routes![
+        //This is a list of all controller functions that are mounted
+        matrix,
+        filtered_matrix,
+        import_matrix,
+        signatures,
+        flaky,
+        prioritised,
+        top_prioritised,
+        reduced,
+        ])
+}
+
+///Rank the ten most suspicious components for every error signature of the failing tests in the coverage matrix
+#[get("/matrix/signatures")]
+fn signatures(context: Context) -> Json<Vec<SignatureRanking>> {
+    let coefficient = configured_similarity_coefficient(&context.settings());
+    let storage_service = context.storage();
+    let rankings = storage_service.coverage_matrix.as_ref().map(|coverage_matrix| rank_per_error_signature(coverage_matrix, &coefficient, 10)).unwrap_or(vec![]);
+    Json(rankings)
+}
+
+///The traces that both passed and failed during the last training
+#[get("/matrix/flaky")]
+fn flaky(context: Context) -> Json<Vec<FlakyTrace>> {
+    Json(context.storage().flaky_traces.clone())
+}
+
+#[derive(FromForm)]
+struct PrioritisationQuery {
+    top: usize,
+}
+
+///The ten stored tests that are expected to disambiguate the most between the suspicious components when they are run again
+#[get("/matrix/prioritised", rank = 2)]
+fn prioritised(context: Context) -> Json<Vec<TestGain>> {
+    top_prioritised(context, PrioritisationQuery { top: 10 })
+}
+
+///The stored tests that are expected to disambiguate the most between the suspicious components when they are run again, best first
+#[get("/matrix/prioritised?<query>")]
+fn top_prioritised(context: Context, query: PrioritisationQuery) -> Json<Vec<TestGain>> {
+    let coefficient = configured_similarity_coefficient(&context.settings());
+    let storage_service = context.storage();
+    Json(storage_service.coverage_matrix.as_ref().map(|coverage_matrix| rank_by_diagnostic_gain(coverage_matrix, &coefficient, query.top)).unwrap_or(vec![]))
+}
+
+///A small subset of the stored tests that covers the same components as all stored tests
+#[get("/matrix/reduced")]
+fn reduced(context: Context) -> Option<Json<SuiteReduction>> {
+    context.storage().coverage_matrix.as_ref().map(|coverage_matrix| Json(reduce_suite(coverage_matrix)))
+}
+
+#[derive(FromForm)]
+struct MatrixQuery {
+    prefix: Option<String>,
+    format: Option<String>,
+    include: Option<String>,
+    exclude: Option<String>,
+}
+
+///Export the coverage matrix. The format (csv, json, binary or arff) is taken from the format query parameter or otherwise from the Accept header. CSV is used by default.
+/// The include and exclude query parameters give the matrix of only the tests with (or without) these tags.
+#[get("/matrix?<query>")]
+fn filtered_matrix(context: Context, accept: Option<&Accept>, query: MatrixQuery) -> Option<Content<Vec<u8>>> {
+    export_matrix(&context, accept, query.prefix, query.format, TagFilter { include: query.include, exclude: query.exclude })
+}
+
+#[get("/matrix", rank = 2)]
+fn matrix(context: Context, accept: Option<&Accept>) -> Option<Content<Vec<u8>>> {
+    export_matrix(&context, accept, None, None, TagFilter::default())
+}
+
+fn export_matrix(context: &AppContext, accept: Option<&Accept>, prefix: Option<String>, format: Option<String>, tag_filter: TagFilter) -> Option<Content<Vec<u8>>> {
+    let format = resolve_format(accept, format);
+    let coverage_matrix = context.storage().tagged_coverage_matrix(&tag_filter);
+    let exported = coverage_matrix.as_ref().map(|coverage_matrix| {
+        let filtered_matrix = match prefix {
+            Some(ref prefix) => coverage_matrix.filter_components(prefix),
+            None => coverage_matrix.clone()
+        };
+        Content(content_type(&format), filtered_matrix.export(&format))
+    });
+    exported
+}
+
+///Import a coverage matrix from a Weka ARFF document, which replaces the current coverage matrix
+#[post("/matrix", data = "<arff>")]
+fn import_matrix(context: Context, arff: String) -> Result<&'static str, BadRequest<String>> {
+    match CoverageMatrix::from_arff(&arff) {
+        Ok(coverage_matrix) => {
+            context.storage().set_coverage_matrix(coverage_matrix);
+            Ok("coverage matrix received")
+        },
+        Err(error) => Err(BadRequest(Some(error)))
+    }
+}
+
+///Find the requested format, where an explicitly given format takes precedence over the Accept header
+fn resolve_format(accept: Option<&Accept>, format: Option<String>) -> MatrixFormat {
+    let requested = format.and_then(|it| MatrixFormat::from_name(&it));
+    if let Some(requested) = requested {
+        return requested;
+    }
+    accept.and_then(|it| {
+        let media_type = it.preferred().media_type();
+        MatrixFormat::from_name(&format!("{}/{}", media_type.top(), media_type.sub()))
+    }).unwrap_or(MatrixFormat::Csv)
+}
+
+fn content_type(format: &MatrixFormat) -> ContentType {
+    match format {
+        MatrixFormat::Csv => ContentType::CSV,
+        MatrixFormat::Json => ContentType::JSON,
+        MatrixFormat::Binary => ContentType::Binary,
+        MatrixFormat::Arff => ContentType::new("text", "x-arff"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::create_rocket;
+    use rocket::local::Client;
+    use rocket::http::Status;
+    use rocket::http::Accept;
+    use app_context::AppContext;
+    use models::coverage_matrix::CoverageMatrix;
+    use models::coverage_matrix::CoverageMatrixExport;
+    use serde_json::from_str;
+    use std::collections::HashMap;
+
+    #[test]
+    fn check_matrix_negotiation() {
+        //Given
+        let mut data = HashMap::new();
+        data.insert("?a".to_string(), vec![true, false]);
+        data.insert("!b".to_string(), vec![true, true]);
+        let context = AppContext::from_settings_file();
+        context.storage().set_coverage_matrix(CoverageMatrix::new(vec![true, false], vec![1, 2], data));
+        let client = Client::new(create_rocket(context)).expect("valid rocket instance");
+
+        //When
+        let mut json_response = client.get("/data/matrix").header(Accept::JSON).dispatch();
+        let mut csv_response = client.get("/data/matrix?format=csv&prefix=%3F").dispatch();
+
+        //Then
+        assert_eq!(json_response.status(), Status::Ok);
+        let export: CoverageMatrixExport = from_str(&json_response.body_string().unwrap()).unwrap();
+        assert_eq!(export.tests, vec![1, 2]);
+        assert_eq!(csv_response.status(), Status::Ok);
+        assert_eq!(csv_response.body_string(), Some("id,\"?a\",errored\n1,true,true\n2,false,false\n".to_string()));
+    }
+}
@@ -0,0 +1,170 @@
+use analysis_service::configured_similarity_coefficient;
+use app_context::AppContext;
+use models::model::TestModel;
+use models::model::Transition;
+use models::similarity::ComponentCounts;
+use models::test_case::Step;
+use models::test_case::TestCase;
+use std::collections::HashSet;
+
+///Minimise a failing test case, so it still covers its most suspicious components
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MinimisationRequest {
+    pub test_case: TestCase,
+    ///The number of most suspicious components of the test case that the minimised test case must cover
+    #[serde(default = "default_top_components")]
+    pub top_components: usize,
+}
+
+fn default_top_components() -> usize {
+    3
+}
+
+///The minimised test case, which can be replayed instead of the original test case
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MinimisationResult {
+    pub test_case: TestCase,
+    pub original_length: usize,
+    pub minimised_length: usize,
+    ///The most suspicious components of the test case, which are all covered by the minimised test case
+    pub suspicious_components: Vec<String>,
+}
+
+///Minimise a failing test case with the trained coverage matrix and the stored model.
+/// The minimised test case ends with the failing step and still covers the top-ranked suspicious components of the original test case.
+/// Cycles in the trace (steps that return to a state the trace was in before) are removed, longest first, as long as the trace keeps covering these components.
+/// Without a model, the states are unknown, so only the steps after the failing step are removed.
+pub fn minimise(context: &AppContext, request: &MinimisationRequest) -> Result<MinimisationResult, String> {
+    let settings = context.settings().clone();
+    let (model, coverage_matrix) = {
+        let storage_service = context.storage();
+        (storage_service.model.clone(), storage_service.coverage_matrix.clone())
+    };
+    let coverage_matrix = match coverage_matrix {
+        Some(coverage_matrix) => coverage_matrix,
+        None => return Err(String::from("There is no coverage matrix yet, so the suspicious components are unknown"))
+    };
+    let test_case = &request.test_case;
+    let end = match test_case.failure_index() {
+        Some(end) => end,
+        None => return Err(String::from("The test case has no steps"))
+    };
+
+    //Rank the components of the test case
+    let use_steps = settings.analysis.use_steps_instead_of_transitions_for_analysis;
+    let use_transition_data = settings.analysis.use_transition_data;
+    let components = test_case.steps.iter().map(|step| match model {
+        Some(ref model) if !use_steps => step.to_transition(model, use_transition_data).map(|it| it.to_string_with_data(use_transition_data)),
+        _ => Some(step.get_full_label_with_params(use_transition_data))
+    }).collect::<Vec<Option<String>>>();
+    let coefficient = configured_similarity_coefficient(&settings);
+    let mut ranking = components[..end + 1].iter().filter_map(|it| it.clone()).collect::<HashSet<String>>().into_iter()
+        .map(|component| {
+            let similarity = ComponentCounts::from_matrix(&component, &coverage_matrix).map(|it| it.similarity(&coefficient)).unwrap_or(0.0);
+            (component, similarity)
+        })
+        .filter(|it| it.1 > 0.0)
+        .collect::<Vec<(String, f32)>>();
+    ranking.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+    let suspicious_components = ranking.into_iter().take(request.top_components).map(|it| it.0).collect::<Vec<String>>();
+
+    let states = states_before_steps(model.as_ref(), &test_case.steps, use_transition_data);
+    let kept = minimise_steps(&states, &components, &suspicious_components, end);
+
+    //Number the kept steps from the number of the first step, so the minimised test case can be replayed
+    let first_number = test_case.steps[0].step_number;
+    let steps = kept.iter().enumerate().map(|(i, index)| {
+        let mut step = test_case.steps[*index].clone();
+        step.step_number = first_number + i as i32;
+        step
+    }).collect::<Vec<Step>>();
+    let last_step = first_number + steps.len() as i32 - 1;
+    let tags = if test_case.tags().is_empty() { None } else { Some(test_case.tags().to_vec()) };
+    Ok(MinimisationResult {
+        test_case: TestCase::new(test_case.id, test_case.verdict.clone(), test_case.error_message.clone(), steps, last_step, test_case.expected_labels().cloned(), tags),
+        original_length: test_case.steps.len(),
+        minimised_length: kept.len(),
+        suspicious_components,
+    })
+}
+
+///The state of the model before every step, by following the transitions from the start state, and the state after the last step.
+/// Once a step does not match an outgoing transition, the states are unknown.
+fn states_before_steps(model: Option<&TestModel>, steps: &[Step], use_transition_data: bool) -> Vec<Option<String>> {
+    let model = match model {
+        Some(model) => model,
+        None => return vec![None; steps.len() + 1]
+    };
+    let transitions = model.all_transitions();
+    let mut state = model.stss.first().and_then(|it| it.start_states().first()).map(|it| it.id().clone());
+    let mut states = vec![state.clone()];
+    for step in steps {
+        state = state.and_then(|state| transitions.iter().find(|it| it.source == state && takes_transition(step, it, use_transition_data)).map(|it| it.target.clone()));
+        states.push(state.clone());
+    }
+    states
+}
+
+fn takes_transition(step: &Step, transition: &Transition, use_transition_data: bool) -> bool {
+    let label = &transition.attributes.label;
+    let full_label = step.get_full_label_with_params(use_transition_data);
+    label == &step.get_full_label_with_params(true) || label == &full_label || label.starts_with(&format!("{} ", full_label))
+}
+
+///Find the indices of the steps to keep. The states are the states before every step and the state after the last step, the components are the component of every step.
+/// The steps after the end are always removed and the step at the end is always kept. Then the longest cycle whose removal keeps all target components covered is removed, until there is no such cycle.
+fn minimise_steps(states: &[Option<String>], components: &[Option<String>], targets: &[String], end: usize) -> Vec<usize> {
+    let mut kept = (0..end + 1).collect::<Vec<usize>>();
+    loop {
+        let n = kept.len();
+        let kept_states = (0..n + 1).map(|p| if p < n { states[kept[p]].clone() } else { states[end + 1].clone() }).collect::<Vec<Option<String>>>();
+        //For every target, the number of kept steps that cover it before every position
+        let counts = targets.iter().map(|target| {
+            let mut count = vec![0; n + 1];
+            for p in 0..n {
+                count[p + 1] = count[p] + if components[kept[p]].as_ref() == Some(target) { 1 } else { 0 };
+            }
+            count
+        }).collect::<Vec<Vec<usize>>>();
+
+        let mut removal = None;
+        'search: for length in (1..n).rev() {
+            for a in 0..(n - length) {
+                let b = a + length;
+                if kept_states[a].is_some() && kept_states[a] == kept_states[b] && counts.iter().all(|count| count[b] - count[a] < count[n]) {
+                    removal = Some((a, b));
+                    break 'search;
+                }
+            }
+        }
+        match removal {
+            Some((a, b)) => {
+                kept.drain(a..b);
+            },
+            None => return kept
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_minimise_steps() {
+        //Given
+        let state = |id: &str| Some(id.to_string());
+        //s0 -a-> s1 -b-> s0 -a-> s1 -c-> s2 -d-> s1 -e-> s3 (fails) -f-> s0
+        let states = vec![state("s0"), state("s1"), state("s0"), state("s1"), state("s2"), state("s1"), state("s3"), state("s0")];
+        let components = ["a", "b", "a", "c", "d", "e", "f"].iter().map(|it| Some(it.to_string())).collect::<Vec<Option<String>>>();
+
+        //When
+        let without_targets = minimise_steps(&states, &components, &[], 5);
+        let with_target = minimise_steps(&states, &components, &[String::from("c")], 5);
+
+        //Then
+        assert_eq!(vec![0, 5], without_targets);
+        assert_eq!(vec![2, 3, 4, 5], with_target);
+        assert_eq!(vec![0, 1, 2], minimise_steps(&vec![None; 4], &components[..3], &[], 2));
+    }
+}
@@ -6,6 +6,9 @@ extern crate serde_json;
 use models::model::Transition;
 use models::model::State;
 use models::test_case::Step;
+use models::explanation::Explanation;
+use models::deviation::Deviation;
+use models::reproduction_path::ReproductionPath;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct AnalysisResult {
@@ -17,6 +20,12 @@ pub struct AnalysisResult {
     root_cause_transition: Option<Transition>,
     #[serde(rename = "rootCauseSteps")]
     pub root_cause_steps: Vec<Step>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<Explanation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deviation: Option<Deviation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reproduction_path: Option<ReproductionPath>,
 }
 
 impl AnalysisResult {
@@ -29,7 +38,37 @@ impl AnalysisResult {
             group_name,
             root_cause_state,
             root_cause_transition,
-            root_cause_steps: rcs
+            root_cause_steps: rcs,
+            explanation: None,
+            deviation: None,
+            reproduction_path: None,
         }
     }
-}
\ No newline at end of file
+
+    pub fn group_name(&self) -> &String {
+        &self.group_name
+    }
+
+    pub fn root_cause_state(&self) -> Option<&State> {
+        self.root_cause_state.as_ref()
+    }
+
+    pub fn root_cause_transition(&self) -> Option<&Transition> {
+        self.root_cause_transition.as_ref()
+    }
+
+    pub fn with_explanation(mut self, explanation: Explanation) -> AnalysisResult {
+        self.explanation = Some(explanation);
+        self
+    }
+
+    pub fn with_deviation(mut self, deviation: Option<Deviation>) -> AnalysisResult {
+        self.deviation = deviation;
+        self
+    }
+
+    pub fn with_reproduction_path(mut self, reproduction_path: Option<ReproductionPath>) -> AnalysisResult {
+        self.reproduction_path = reproduction_path;
+        self
+    }
+}
@@ -3,13 +3,66 @@ use models::model::Transition;
 use prettytable::cell::Cell;
 use prettytable::row::Row;
 use prettytable::Table;
-use send_progress;
 use std::collections::HashMap;
 use std::vec::Vec;
 use models::test_case::TestCase;
 use models::test_case::Step;
 use std::collections::HashSet;
 use models::test_case::TestResult;
+use serde_json;
+
+///The formats in which a coverage matrix can be exported
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatrixFormat {
+    Csv,
+    Json,
+    Binary,
+    Arff,
+}
+
+impl MatrixFormat {
+    ///Find the format by its name (as used in the settings) or by its media type
+    pub fn from_name(name: &str) -> Option<MatrixFormat> {
+        match name.trim().to_lowercase().as_str() {
+            "csv" | "text/csv" => Some(MatrixFormat::Csv),
+            "json" | "application/json" => Some(MatrixFormat::Json),
+            "binary" | "bin" | "application/octet-stream" => Some(MatrixFormat::Binary),
+            "arff" | "text/x-arff" | "application/x-arff" => Some(MatrixFormat::Arff),
+            _ => None
+        }
+    }
+}
+
+///How a coverage matrix treats a test with the same verdict and coverage as an earlier test
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DuplicateRows {
+    Keep, //Every test gets a row of its own
+    Count, //The test is counted in the row of the earlier test, which counts once for every test it stands for. This gives the same coefficients as the raw rows.
+    CollapseOnce, //The test is counted in the row of the earlier test, which counts once however many tests it stands for
+}
+
+///A serializable representation of the coverage matrix, as it is exported to JSON.
+/// The coverage contains a row for every test with a value for every component, in the same order as the components.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CoverageMatrixExport {
+    pub components: Vec<String>,
+    pub tests: Vec<i64>,
+    pub error_vector: Vec<bool>,
+    pub coverage: Vec<Vec<bool>>,
+    pub number_of_tests: usize,
+    pub number_of_components: usize,
+    pub number_of_failing_tests: usize,
+    ///For every row, the number of identical tests it counts for
+    #[serde(default)]
+    pub multiplicities: Vec<u32>,
+}
+
+///A user-supplied weight of a test, which tells how much the test counts during the computation of the coefficients
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TestWeight {
+    pub test_id: i64,
+    pub weight: f32,
+}
 
 #[derive(Debug, Clone, Derivative)]
 #[derivative(PartialEq, Eq)]
@@ -19,17 +72,392 @@ pub struct CoverageMatrix {
     pub data: HashMap<String, Vec<bool>>, //The different steps and for each test if the step was covered by that test.
     number_of_tests: usize,
     number_of_steps: usize,
+    #[derivative(PartialEq = "ignore")]
+    error_signatures: HashMap<i64, String>, //The normalised error message of the failing tests, by test id.
+    #[derivative(PartialEq = "ignore")]
+    test_weights: HashMap<i64, f32>, //How much a test counts during the computation of the coefficients, by test id. Tests without a weight count once.
+    #[derivative(PartialEq = "ignore")]
+    separate_class_tests: HashSet<i64>, //The tests that count in a separate class instead of by their verdict, e.g. flaky tests.
+    #[derivative(PartialEq = "ignore")]
+    multiplicities: HashMap<i64, u32>, //The number of identical tests that a deduplicated row counts for, by the id of the test of the row. Other rows count once.
+    #[derivative(PartialEq = "ignore")]
+    duplicate_test_ids: HashMap<i64, Vec<i64>>, //The ids of the tests that were collapsed into the row of the test with this id.
 }
 
 impl CoverageMatrix {
     pub fn new(error_vector: Vec<bool>, test_id_vector: Vec<i64>, data: HashMap<String, Vec<bool>>) -> CoverageMatrix {
         CoverageMatrix {
-            number_of_tests: data.clone().values().next().unwrap_or(&vec![]).len(),
-            number_of_steps: test_id_vector.clone().len(),
+            number_of_tests: test_id_vector.len(),
+            number_of_steps: data.len(),
             error_vector,
             test_id_vector,
             data,
+            error_signatures: HashMap::new(),
+            test_weights: HashMap::new(),
+            separate_class_tests: HashSet::new(),
+            multiplicities: HashMap::new(),
+            duplicate_test_ids: HashMap::new(),
+        }
+    }
+
+    ///Return the ids of the tests in the coverage matrix, in the order of the rows
+    pub fn test_ids(&self) -> &Vec<i64> {
+        &self.test_id_vector
+    }
+
+    ///Return all components of the coverage matrix, sorted by name
+    pub fn components(&self) -> Vec<String> {
+        let mut components = self.data.keys().cloned().collect::<Vec<String>>();
+        components.sort();
+        components
+    }
+
+    ///Set the error signature of a failing test
+    pub fn set_error_signature(&mut self, test_id: i64, signature: String) {
+        self.error_signatures.insert(test_id, signature);
+    }
+
+    ///Set how much a test counts during the computation of the coefficients
+    pub fn set_test_weight(&mut self, test_id: i64, weight: f32) {
+        self.test_weights.insert(test_id, weight);
+    }
+
+    ///Count a test in a separate class during the computation of the coefficients, instead of by its verdict
+    pub fn set_separate_class(&mut self, test_id: i64) {
+        self.separate_class_tests.insert(test_id);
+    }
+
+    ///Check if the test at the given row counts in a separate class instead of by its verdict
+    pub fn is_separate_class(&self, row: usize) -> bool {
+        self.separate_class_tests.contains(&self.test_id_vector[row])
+    }
+
+    ///The weight of a single test, which is 1 unless a weight was set
+    pub fn test_weight(&self, test_id: i64) -> f32 {
+        *self.test_weights.get(&test_id).unwrap_or(&1.0)
+    }
+
+    ///The number of identical tests that the row counts for
+    pub fn multiplicity(&self, row: usize) -> u32 {
+        *self.multiplicities.get(&self.test_id_vector[row]).unwrap_or(&1)
+    }
+
+    ///The ids of all tests that the row stands for: the test of the row and the tests that were collapsed into it
+    pub fn represented_test_ids(&self, row: usize) -> Vec<i64> {
+        let test_id = self.test_id_vector[row];
+        let mut test_ids = vec![test_id];
+        test_ids.extend(self.duplicate_test_ids.get(&test_id).cloned().unwrap_or(vec![]));
+        test_ids
+    }
+
+    ///The row of a test, which is the row it was collapsed into if the matrix is deduplicated
+    pub fn row_of_test(&self, test_id: i64) -> Option<usize> {
+        self.test_id_vector.iter().position(|it| *it == test_id)
+            .or_else(|| self.duplicate_test_ids.iter().find(|&(_, duplicates)| duplicates.contains(&test_id)).and_then(|(row_test_id, _)| self.test_id_vector.iter().position(|it| it == row_test_id)))
+    }
+
+    ///How much the test at the given row counts as a passing or failing test: its weight times its multiplicity. Tests in a separate class are not counted by their verdict, so their row weight is 0.
+    pub fn row_weight(&self, row: usize) -> f32 {
+        if self.is_separate_class(row) {
+            return 0.0;
+        }
+        self.test_weight(self.test_id_vector[row]) * self.multiplicity(row) as f32
+    }
+
+    ///Create a new coverage matrix without the tests with the given ids
+    pub fn without_test_ids(&self, test_ids: &HashSet<i64>) -> CoverageMatrix {
+        self.retain_tests(|test_id| !test_ids.contains(&test_id))
+    }
+
+    ///The multi-class error vector: for every test the error signature, or None if the test passed or has no error message
+    pub fn error_signature_vector(&self) -> Vec<Option<String>> {
+        self.test_id_vector.iter().zip(self.error_vector.iter()).map(|(test_id, errored)| if *errored { self.error_signatures.get(test_id).cloned() } else { None }).collect()
+    }
+
+    ///Return all distinct error signatures of the failing tests, sorted
+    pub fn error_classes(&self) -> Vec<String> {
+        let mut classes = self.error_signature_vector().into_iter().filter_map(|it| it).collect::<Vec<String>>();
+        classes.sort();
+        classes.dedup();
+        classes
+    }
+
+    ///Create a new coverage matrix with all passing tests and only the failing tests with the given error signature.
+    /// This gives the spectra of a single failure mode, so it gets its own suspiciousness ranking.
+    pub fn for_error_signature(&self, signature: &str) -> CoverageMatrix {
+        let signature_vector = self.error_signature_vector();
+        let rows = (0..self.error_vector.len()).filter(|i| !self.error_vector[*i] || signature_vector[*i].as_ref().map(|it| it.as_str()) == Some(signature)).collect::<Vec<usize>>();
+        self.select_rows(&rows)
+    }
+
+    ///Create a new coverage matrix with only the tests with the given ids, e.g. the tests with a certain tag
+    pub fn for_test_ids(&self, test_ids: &HashSet<i64>) -> CoverageMatrix {
+        self.retain_tests(|test_id| test_ids.contains(&test_id))
+    }
+
+    ///Create a new coverage matrix with only the tests that are retained. A deduplicated row is kept as long as one of the tests it stands for is retained.
+    /// The first retained test becomes the test of the row and the multiplicity is recomputed for the retained tests.
+    fn retain_tests<F: Fn(i64) -> bool>(&self, retain: F) -> CoverageMatrix {
+        let mut groups = vec![];
+        for row in 0..self.test_id_vector.len() {
+            let test_ids = self.represented_test_ids(row).into_iter().filter(|it| retain(*it)).collect::<Vec<i64>>();
+            if !test_ids.is_empty() {
+                groups.push((row, test_ids));
+            }
+        }
+        self.regroup_tests(groups)
+    }
+
+    ///Give the tests that were collapsed into one row a row of their own again if they differ in their error signature, weight or class, or in the given key.
+    /// Tests are collapsed while the matrix is built, before these attributes are known.
+    pub fn separate_different_tests<K: PartialEq, F: Fn(i64) -> K>(&self, key: F) -> CoverageMatrix {
+        let mut groups = vec![];
+        for row in 0..self.test_id_vector.len() {
+            let mut row_groups: Vec<((Option<String>, u32, bool, K), Vec<i64>)> = vec![];
+            for test_id in self.represented_test_ids(row) {
+                let test_key = (self.error_signatures.get(&test_id).cloned(), self.test_weight(test_id).to_bits(), self.separate_class_tests.contains(&test_id), key(test_id));
+                let group = row_groups.iter().position(|it| it.0 == test_key);
+                match group {
+                    Some(index) => row_groups[index].1.push(test_id),
+                    None => row_groups.push((test_key, vec![test_id]))
+                }
+            }
+            groups.extend(row_groups.into_iter().map(|(_, test_ids)| (row, test_ids)));
+        }
+        self.regroup_tests(groups)
+    }
+
+    ///Create a new coverage matrix with a row for every group of tests, with the coverage of the given row. The first test of a group becomes the test of the row.
+    /// The multiplicity of a group is its number of tests if the original row counted its duplicates, and 1 otherwise.
+    fn regroup_tests(&self, groups: Vec<(usize, Vec<i64>)>) -> CoverageMatrix {
+        let rows = groups.iter().map(|it| it.0).collect::<Vec<usize>>();
+        let mut regrouped = self.select_rows(&rows);
+        regrouped.multiplicities = HashMap::new();
+        regrouped.duplicate_test_ids = HashMap::new();
+        for (index, (row, test_ids)) in groups.into_iter().enumerate() {
+            let count_duplicates = self.multiplicity(row) as usize == self.represented_test_ids(row).len();
+            regrouped.test_id_vector[index] = test_ids[0];
+            if test_ids.len() > 1 {
+                regrouped.multiplicities.insert(test_ids[0], if count_duplicates { test_ids.len() as u32 } else { 1 });
+                regrouped.duplicate_test_ids.insert(test_ids[0], test_ids[1..].to_vec());
+            }
+        }
+        regrouped
+    }
+
+    ///Create a new coverage matrix with only the tests at the given row indices
+    fn select_rows(&self, rows: &[usize]) -> CoverageMatrix {
+        let data = self.data.iter().map(|(component, values)| (component.clone(), rows.iter().map(|i| values[*i]).collect::<Vec<bool>>())).collect::<HashMap<String, Vec<bool>>>();
+        CoverageMatrix {
+            error_vector: rows.iter().map(|i| self.error_vector[*i]).collect(),
+            test_id_vector: rows.iter().map(|i| self.test_id_vector[*i]).collect(),
+            number_of_tests: rows.len(),
+            number_of_steps: self.number_of_steps,
+            data,
+            error_signatures: self.error_signatures.clone(),
+            test_weights: self.test_weights.clone(),
+            separate_class_tests: self.separate_class_tests.clone(),
+            multiplicities: self.multiplicities.clone(),
+            duplicate_test_ids: self.duplicate_test_ids.clone(),
+        }
+    }
+
+    ///Create a new coverage matrix that only contains the components that start with the given prefix
+    pub fn filter_components(&self, prefix: &str) -> CoverageMatrix {
+        let data = self.data.iter().filter(|&(component, _)| component.starts_with(prefix)).map(|(component, values)| (component.clone(), values.clone())).collect::<HashMap<String, Vec<bool>>>();
+        CoverageMatrix {
+            error_vector: self.error_vector.clone(),
+            test_id_vector: self.test_id_vector.clone(),
+            number_of_tests: self.number_of_tests,
+            number_of_steps: data.len(),
+            data,
+            error_signatures: self.error_signatures.clone(),
+            test_weights: self.test_weights.clone(),
+            separate_class_tests: self.separate_class_tests.clone(),
+            multiplicities: self.multiplicities.clone(),
+            duplicate_test_ids: self.duplicate_test_ids.clone(),
+        }
+    }
+
+    ///Export the coverage matrix in the given format
+    pub fn export(&self, format: &MatrixFormat) -> Vec<u8> {
+        match format {
+            MatrixFormat::Csv => self.to_csv().into_bytes(),
+            MatrixFormat::Json => serde_json::to_vec(&self.to_export()).unwrap(),
+            MatrixFormat::Binary => self.to_binary(),
+            MatrixFormat::Arff => self.to_arff().into_bytes(),
+        }
+    }
+
+    ///Convert the coverage matrix to CSV, with a row for every test and a column for every component
+    pub fn to_csv(&self) -> String {
+        let components = self.components();
+        let mut csv = String::new();
+        //Header
+        let mut header = vec!["id".to_string()];
+        header.append(&mut components.iter().map(|it| escape_csv_field(it)).collect::<Vec<String>>());
+        header.push("errored".to_string());
+        csv.push_str(&header.join(","));
+        csv.push('\n');
+        //Data
+        for (i, test_id) in self.test_id_vector.iter().enumerate() {
+            let mut row = vec![test_id.to_string()];
+            row.append(&mut components.iter().map(|it| self.data[it][i].to_string()).collect::<Vec<String>>());
+            row.push(self.error_vector[i].to_string());
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    ///Convert the coverage matrix to a serializable representation
+    pub fn to_export(&self) -> CoverageMatrixExport {
+        let components = self.components();
+        let coverage = (0..self.number_of_tests).map(|i| components.iter().map(|it| self.data[it][i]).collect::<Vec<bool>>()).collect::<Vec<Vec<bool>>>();
+        CoverageMatrixExport {
+            number_of_tests: self.number_of_tests,
+            number_of_components: components.len(),
+            number_of_failing_tests: self.error_vector.iter().filter(|it| **it).count(),
+            multiplicities: (0..self.number_of_tests).map(|row| self.multiplicity(row)).collect(),
+            components,
+            tests: self.test_id_vector.clone(),
+            error_vector: self.error_vector.clone(),
+            coverage,
+        }
+    }
+
+    ///Convert the coverage matrix to a compact binary form. All numbers are little endian. The layout is:
+    /// - the magic bytes `SFLM` followed by a version byte (1)
+    /// - the number of components and the number of tests, both as u32
+    /// - for every component, the length of its name as u32 followed by the UTF-8 bytes of the name
+    /// - for every test, its id as i64
+    /// - the error vector as a bitset (one bit per test, padded to whole bytes)
+    /// - for every component, the coverage as a bitset in the same way as the error vector
+    pub fn to_binary(&self) -> Vec<u8> {
+        let components = self.components();
+        let mut bytes: Vec<u8> = b"SFLM".to_vec();
+        bytes.push(1);
+        push_u32(&mut bytes, components.len() as u32);
+        push_u32(&mut bytes, self.number_of_tests as u32);
+        for component in components.iter() {
+            push_u32(&mut bytes, component.len() as u32);
+            bytes.extend_from_slice(component.as_bytes());
+        }
+        for test_id in self.test_id_vector.iter() {
+            push_u64(&mut bytes, *test_id as u64);
+        }
+        bytes.append(&mut to_bitset(&self.error_vector));
+        for component in components.iter() {
+            bytes.append(&mut to_bitset(&self.data[component]));
+        }
+        bytes
+    }
+
+    ///Convert the coverage matrix to the Weka ARFF format. Every component is a boolean attribute, the test id is a numeric attribute and the verdict is the nominal class attribute.
+    pub fn to_arff(&self) -> String {
+        let components = self.components();
+        let mut arff = String::from("@relation coverage_matrix\n\n@attribute test_id numeric\n");
+        for component in components.iter() {
+            arff.push_str(&format!("@attribute {} {{false,true}}\n", quote_arff(component)));
+        }
+        arff.push_str("@attribute verdict {passed,failed}\n\n@data\n");
+        for (i, test_id) in self.test_id_vector.iter().enumerate() {
+            let mut row = vec![test_id.to_string()];
+            row.append(&mut components.iter().map(|it| self.data[it][i].to_string()).collect::<Vec<String>>());
+            row.push(if self.error_vector[i] { "failed".to_string() } else { "passed".to_string() });
+            arff.push_str(&row.join(","));
+            arff.push('\n');
         }
+        arff
+    }
+
+    ///Create a coverage matrix from a (dense) Weka ARFF document.
+    /// The attribute `test_id` is used as the id of the test and the attribute `verdict` (or otherwise the last attribute) as the class.
+    /// The class must be nominal and declare `passed`. Every test with another declared verdict is considered to be failing. All other attributes are components, where `true`, `t`, `yes` and `1` mean covered and `false`, `f`, `no` and `0` mean not covered.
+    /// Any other value is an error, including a missing or undeclared verdict and a test id that is not a whole number, as is a document without a class attribute or with an attribute that is declared more than once.
+    pub fn from_arff(arff: &str) -> Result<CoverageMatrix, String> {
+        let mut attributes: Vec<String> = vec![];
+        let mut declarations: Vec<String> = vec![];
+        let mut rows: Vec<(usize, Vec<String>)> = vec![];
+        let mut in_data = false;
+        for (line_number, line) in arff.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('%') {
+                continue;
+            }
+            if in_data {
+                if line.starts_with('{') {
+                    return Err(format!("Sparse ARFF data is not supported (line {})", line_number + 1));
+                }
+                let values = split_arff_values(line);
+                if values.len() != attributes.len() {
+                    return Err(format!("Expected {} values, but found {} on line {}", attributes.len(), values.len(), line_number + 1));
+                }
+                rows.push((line_number + 1, values));
+            } else if line.to_lowercase().starts_with("@attribute") {
+                let (name, declaration) = parse_arff_attribute(&line["@attribute".len()..]).ok_or(format!("No attribute name found on line {}", line_number + 1))?;
+                if attributes.contains(&name) {
+                    return Err(format!("The attribute {} is declared more than once (line {})", name, line_number + 1));
+                }
+                attributes.push(name);
+                declarations.push(declaration);
+            } else if line.to_lowercase().starts_with("@data") {
+                in_data = true;
+            }
+        }
+        if attributes.is_empty() {
+            return Err("No attributes found in the ARFF document".to_string());
+        }
+
+        //Find the special attributes
+        let test_id_index = attributes.iter().position(|it| it == "test_id");
+        let verdict_index = match attributes.iter().position(|it| it == "verdict") {
+            Some(index) => index,
+            None if test_id_index == Some(attributes.len() - 1) => return Err("There is no verdict attribute and the last attribute is the test id".to_string()),
+            None => attributes.len() - 1
+        };
+        //Only the declared values of the verdict are accepted, of which passed is the only passing verdict
+        let verdict_values = parse_arff_nominal_values(&declarations[verdict_index]).ok_or(format!("The verdict attribute {} is not nominal", attributes[verdict_index]))?;
+        if !verdict_values.iter().any(|it| it == "passed") {
+            return Err(format!("The verdict attribute {} does not declare the value passed", attributes[verdict_index]));
+        }
+
+        let mut error_vector = vec![];
+        let mut test_id_vector = vec![];
+        let mut data: HashMap<String, Vec<bool>> = HashMap::new();
+        for (index, attribute) in attributes.iter().enumerate() {
+            if Some(index) != test_id_index && index != verdict_index {
+                data.insert(attribute.to_string(), vec![]);
+            }
+        }
+        for &(line_number, ref row) in rows.iter() {
+            for (index, value) in row.iter().enumerate() {
+                if Some(index) == test_id_index {
+                    match value.parse::<f64>() {
+                        Ok(test_id) if test_id.fract() == 0.0 => test_id_vector.push(test_id as i64),
+                        _ => return Err(format!("The test id {} is not a whole number (line {})", value, line_number))
+                    }
+                } else if index == verdict_index {
+                    if value == "?" {
+                        return Err(format!("The verdict is missing (line {})", line_number));
+                    }
+                    if !verdict_values.contains(value) {
+                        return Err(format!("The verdict {} is not one of the declared values {:?} (line {})", value, verdict_values, line_number));
+                    }
+                    error_vector.push(value != "passed");
+                } else if let Some(values) = data.get_mut(&attributes[index]) {
+                    match value.to_lowercase().as_str() {
+                        "true" | "t" | "yes" | "1" => values.push(true),
+                        "false" | "f" | "no" | "0" => values.push(false),
+                        _ => return Err(format!("The value {} of {} is not a boolean (line {})", value, attributes[index], line_number))
+                    }
+                }
+            }
+            if test_id_index.is_none() {
+                test_id_vector.push(-1);
+            }
+        }
+        Ok(CoverageMatrix::new(error_vector, test_id_vector, data))
     }
 
     /// This method converts the coverage matrix to a table with a header
@@ -74,152 +502,105 @@ impl CoverageMatrix {
     }
 
     /// Create a coverage matrix from test traces (a vec of test cases).
-    /// Optionally some steps can be specified to be included in the coverage matrix. This feature is used to add step-pairs to the coverage matrix.
-    /// If send progress updates is set to true, the min and max progress floats are used to send progress updates.
+    /// Optionally some steps can be specified to be included in the coverage matrix. This feature is used to add step-pairs to the coverage matrix, next to the single steps.
+    /// The duplicate rows tell whether a test with the same verdict and coverage as an earlier test gets a row of its own, see `DuplicateRows`.
+    /// The labels of the steps include the data of the steps if use transition data is set.
+    /// The progress is sent as the fraction of the tests that have been added.
     /// See also `from_model`.
-    pub fn from_traces(test_cases: Vec<TestCase>, steps_to_include: Option<Vec<Vec<Step>>>, min_progress: f32, max_progress: f32) -> CoverageMatrix {
-        let send_progress_updates = max_progress > min_progress;
-        let mut coverage_matrix: HashMap<String, Vec<bool>> = HashMap::new();
-        let mut error_vector: Vec<bool> = vec![];
-        let mut test_id_vector: Vec<i64> = vec![];
-        if send_progress_updates { send_progress(min_progress); }
-
-        //Check if there are steps to include specified
-        match steps_to_include {
-            Some(mut step_pairs) => {
-                //remove duplicates by the label of the step
-                step_pairs.sort_by_key(|step_pair|step_pair.iter().map(|it| it.get_full_label()).collect::<Vec<_>>().join(" -> "));
-                step_pairs.dedup_by_key(|step_pair|step_pair.iter().map(|it| it.get_full_label()).collect::<Vec<_>>().join(" -> "));
-                //Loop over the test cases and the unique steps (by labels) to create pairs and add these to the coverage matrix.
-                for test in test_cases.iter() {
-                    for step_pair in step_pairs.iter() {
-                        let step_pair_covered = step_pair.iter().all(|s| test.steps.contains(s));
-                        let string_pair = step_pair.iter().map(|it| it.get_full_label()).collect::<Vec<_>>().join(" -> ");
-                        let mut cov_vec = coverage_matrix.entry(string_pair).or_insert(vec![]);
-                        cov_vec.push(step_pair_covered);
-                    }
-                }
-            },
-            None => {
-                //Just go over the test cases and the steps to create the coverage matrix
-                let mut steps: HashSet<&Step> = HashSet::new();
-                //Fill a set with all steps
-                for test in test_cases.iter() {
-                    for step in &test.steps {
-                        steps.insert(step);
-                    }
-                }
-                //Find the unique labels for the set of steps
-                let mut step_labels: HashSet<String> = steps.iter().map(|it| it.get_full_label()).collect();
-
-                //For each test case, fill the error vector, the test id vector and finally the matrix itself for each step_label
-                for (i, test) in test_cases.iter().enumerate() {
-                    error_vector.push((&test).verdict.as_ref().unwrap_or(&TestResult::unknown) != &TestResult::passed);
-                    test_id_vector.push(test.id.unwrap_or(-1));
-
-                    for step in step_labels.iter() {
-                        let is_covered = test.steps.iter().find(|s| s.get_full_label() == *step).is_some();
-                        let mut cov_vec = coverage_matrix.entry(step.to_string()).or_insert(vec![]);
-                        cov_vec.push(is_covered);
-                    }
+    pub fn from_traces(test_cases: Vec<TestCase>, steps_to_include: Option<Vec<Vec<Step>>>, use_transition_data: bool, duplicate_rows: DuplicateRows, send_progress: &Fn(f32)) -> CoverageMatrix {
+        send_progress(0.0);
 
-                    if send_progress_updates { send_progress((i as f32 / test_cases.len() as f32) * (max_progress - min_progress) + min_progress); }
-                }
+        //Find the unique labels of all steps
+        let step_labels: HashSet<String> = test_cases.iter().flat_map(|test| test.steps.iter()).map(|it| it.get_full_label_with_params(use_transition_data)).collect();
+        //Remove duplicate step pairs by the label of the steps
+        let mut step_pairs = steps_to_include.unwrap_or(vec![]);
+        step_pairs.sort_by_key(|step_pair|step_pair.iter().map(|it| it.get_full_label_with_params(use_transition_data)).collect::<Vec<_>>().join(" -> "));
+        step_pairs.dedup_by_key(|step_pair|step_pair.iter().map(|it| it.get_full_label_with_params(use_transition_data)).collect::<Vec<_>>().join(" -> "));
+        let pair_labels = step_pairs.iter().map(|step_pair| step_pair.iter().map(|it| it.get_full_label_with_params(use_transition_data)).collect::<Vec<_>>().join(" -> ")).collect::<Vec<String>>();
+
+        //For each test case, add a row with the coverage of every step label and every step pair
+        let mut rows = RowCollector::new(duplicate_rows);
+        for (i, test) in test_cases.iter().enumerate() {
+            let test_labels: HashSet<String> = test.steps.iter().map(|it| it.get_full_label_with_params(use_transition_data)).collect();
+            let mut coverage = step_labels.iter().map(|label| (label.to_string(), test_labels.contains(label))).collect::<Vec<(String, bool)>>();
+            for (step_pair, pair_label) in step_pairs.iter().zip(pair_labels.iter()) {
+                coverage.push((pair_label.to_string(), step_pair.iter().all(|s| test.steps.contains(s))));
             }
-        }
-        if send_progress_updates { send_progress(max_progress); }
+            rows.add_test(test.id, test.verdict.as_ref().unwrap_or(&TestResult::unknown) != &TestResult::passed, coverage);
 
-        //Do some checks to validate the integrity of the data
-        let number_of_tests = coverage_matrix.values().next().unwrap_or(&vec![]).len();
-        let number_of_steps = coverage_matrix.keys().len();
-        for tests in coverage_matrix.values() {
-            assert_eq!(tests.len(), number_of_tests);
+            send_progress(i as f32 / test_cases.len() as f32);
         }
+        send_progress(1.0);
 
-        CoverageMatrix {
-            error_vector,
-            test_id_vector,
-            data: coverage_matrix,
-            number_of_tests,
-            number_of_steps,
-        }
+        rows.into_coverage_matrix()
     }
 
     /// Create a coverage matrix from a set of coverage information models.
-    /// Optionally some transitions can be specified to be included in the coverage matrix. This feature is used to add transition-pairs to the coverage matrix.
-    /// If send progress updates is set to true, the min and max progress floats are used to send progress updates.
+    /// Optionally some transitions can be specified to be included in the coverage matrix. This feature is used to add transition-pairs to the coverage matrix, next to the single transitions.
+    /// Duplicate tests are treated in the same way as in `from_traces`.
+    /// The labels of the transitions and the progress are handled in the same way as in `from_traces`.
     /// See also `from_traces`.
-    pub fn from_model(val: Vec<TestModel>, transitions_to_include: Option<Vec<Vec<Transition>>>, min_progress: f32, max_progress: f32) -> CoverageMatrix {
-        let send_progress_updates = max_progress > min_progress;
-        let mut coverage_matrix: HashMap<String, Vec<bool>> = HashMap::new();
-        let mut error_vector: Vec<bool> = vec![];
-        let mut test_id_vector: Vec<i64> = vec![];
-        if send_progress_updates { send_progress(min_progress); }
-
-        //Check if there are transitions to include specified
-        match transitions_to_include {
-            Some(mut transition_pairs) => {
-                //remove duplicates by the label of the transition
-                transition_pairs.sort_by_key(|transition_pair|transition_pair.iter().map(|it| it.to_string()).collect::<Vec<_>>().join(" "));
-                transition_pairs.dedup_by_key(|transition_pair|transition_pair.iter().map(|it| it.to_string()).collect::<Vec<_>>().join(" "));
-                //Loop over the test cases and the unique transitions (by labels) to create pairs and add these to the coverage matrix.
-                for transition_pair in transition_pairs.iter() {
-                    for test in val.iter() {
-                        //Check if the current transition is actually fully covered
-                        let transition_pair_covered = test.all_transitions().iter().filter(|transition| transition_pair.contains(transition)).map(|transition| transition.attributes.covered.unwrap_or(false))
-                            .all(|cov| cov == true);
-                        let string_pair = transition_pair.iter().map(|it| it.to_string()).collect::<Vec<_>>().join(" ");
-                        let mut cov_vec = coverage_matrix.entry(string_pair).or_insert(vec![]);
-                        cov_vec.push(transition_pair_covered);
+    pub fn from_model(val: Vec<TestModel>, transitions_to_include: Option<Vec<Vec<Transition>>>, use_transition_data: bool, duplicate_rows: DuplicateRows, send_progress: &Fn(f32)) -> CoverageMatrix {
+        send_progress(0.0);
+
+        //Remove duplicate transition pairs by the label of the transitions
+        let mut transition_pairs = transitions_to_include.unwrap_or(vec![]);
+        transition_pairs.sort_by_key(|transition_pair|transition_pair.iter().map(|it| it.to_string_with_data(use_transition_data)).collect::<Vec<_>>().join(" "));
+        transition_pairs.dedup_by_key(|transition_pair|transition_pair.iter().map(|it| it.to_string_with_data(use_transition_data)).collect::<Vec<_>>().join(" "));
+
+        //Loop over the models that contain coverage information
+        let mut rows = RowCollector::new(duplicate_rows);
+        for (i, test) in val.iter().enumerate() {
+            let mut passed = true;
+            let mut coverage: Vec<(String, bool)> = vec![];
+            for sts in &test.stss {
+                if let Some(ref props) = sts.trace_properties {
+                    //Check if every sts shows that the test has passed
+                    if let Some(props_passed) = props.passed {
+                        passed = passed && props_passed
                     }
                 }
-            },
-            None => {
-                //Loop over the models that contain coverage information
-                for (i, test) in val.iter().enumerate() {
-                    let mut passed = true;
-                    for sts in &test.stss {
-                        if let Some(ref props) = sts.trace_properties {
-                            //Check if every sts shows that the test has passed
-                            if let Some(props_passed) = props.passed {
-                                passed = passed && props_passed
-                            }
-                        }
-
-                        //Create a unique vector of transitions that are unique by their label
-                        let mut relevant_transitions = (&sts).transitions.clone();
-                        relevant_transitions.sort_by_key(|it|it.to_string());
-                        relevant_transitions.dedup_by_key(|it|it.to_string());
-                        //Check the coverage of the pairs
-                        for transition in relevant_transitions.iter() {
-                            let is_covered = transition.attributes.covered.unwrap_or(false);
-                            let mut cov_vec = coverage_matrix.entry(transition.to_string()).or_insert(vec![]);
-                            cov_vec.push(is_covered);
-                        }
-                    }
-                    error_vector.push(!passed);
-                    test_id_vector.push(test.testcase_id.unwrap_or(-1));
 
-                    if send_progress_updates { send_progress((i as f32 / val.len() as f32) * (max_progress - min_progress) + min_progress); }
-                }
+                //Create a unique vector of transitions that are unique by their label
+                let mut relevant_transitions = (&sts).transitions.clone();
+                relevant_transitions.sort_by_key(|it|it.to_string_with_data(use_transition_data));
+                relevant_transitions.dedup_by_key(|it|it.to_string_with_data(use_transition_data));
+                coverage.extend(relevant_transitions.iter().map(|transition| (transition.to_string_with_data(use_transition_data), transition.attributes.covered.unwrap_or(false))));
             }
-        }
-        if send_progress_updates { send_progress(max_progress); }
+            //Check if the transition pairs are fully covered
+            let all_transitions = test.all_transitions();
+            for transition_pair in transition_pairs.iter() {
+                let transition_pair_covered = all_transitions.iter().filter(|transition| transition_pair.contains(transition)).all(|transition| transition.attributes.covered.unwrap_or(false));
+                coverage.push((transition_pair.iter().map(|it| it.to_string_with_data(use_transition_data)).collect::<Vec<_>>().join(" "), transition_pair_covered));
+            }
+            rows.add_test(test.testcase_id, !passed, coverage);
 
-        //Do some checks to validate the integrity of the data
-        let number_of_tests = coverage_matrix.values().next().unwrap_or(&vec![]).len();
-        let number_of_steps = coverage_matrix.keys().len();
-        for tests in coverage_matrix.values() {
-            assert_eq!(tests.len(), number_of_tests);
+            send_progress(i as f32 / val.len() as f32);
         }
+        send_progress(1.0);
 
-        CoverageMatrix {
-            error_vector,
-            test_id_vector,
-            data: coverage_matrix,
-            number_of_tests,
-            number_of_steps,
+        rows.into_coverage_matrix()
+    }
+
+    ///Add a single test to the coverage matrix. Every existing component gets a new entry that tells whether the component is in the set of covered components.
+    /// Components that were not in the matrix yet are added as a new column, which is filled with false for all older tests.
+    pub fn add_test(&mut self, test_id: i64, errored: bool, covered_components: &HashSet<String>) {
+        //Add the new row for the existing components
+        for (component, values) in self.data.iter_mut() {
+            values.push(covered_components.contains(component));
         }
+        //Add the new components as columns
+        for component in covered_components.iter() {
+            if !self.data.contains_key(component) {
+                let mut values = vec![false; self.number_of_tests];
+                values.push(true);
+                self.data.insert(component.to_string(), values);
+            }
+        }
+        self.error_vector.push(errored);
+        self.test_id_vector.push(test_id);
+        self.number_of_tests += 1;
+        self.number_of_steps = self.data.len();
     }
 
     ///Append a coverage matrix to the current coverage matrix which results in a new coverage matrix while the original remains untouched
@@ -232,25 +613,198 @@ impl CoverageMatrix {
         self.data.iter().chain(to_append.data.iter()).for_each(|entry| {
             new_data.insert(entry.0.to_string(), entry.1.clone());
         });
-        return Ok(CoverageMatrix{
-            number_of_tests: self.number_of_tests,
-            number_of_steps: self.number_of_steps,
-            test_id_vector: self.test_id_vector.clone(),
-            error_vector: self.error_vector.clone(),
-            data: new_data,
-        });
+        let mut appended = self.clone();
+        appended.number_of_steps = new_data.len();
+        appended.data = new_data;
+        Ok(appended)
+    }
+
+    ///Add a component as a new column, with for every test whether the test covers the component. An existing column with the same name is replaced.
+    pub fn add_component(&mut self, component: String, values: Vec<bool>) {
+        assert_eq!(values.len(), self.number_of_tests);
+        self.data.insert(component, values);
+        self.number_of_steps = self.data.len();
+    }
+}
+
+///Quote a CSV field, so that separators and quotes in component names do not break the CSV
+fn escape_csv_field(field: &str) -> String {
+    format!("\"{}\"", field.replace("\"", "\"\""))
+}
+
+///Quote a name for use in ARFF, escaping backslashes and single quotes
+fn quote_arff(name: &str) -> String {
+    format!("'{}'", name.replace("\\", "\\\\").replace("'", "\\'"))
+}
+
+///Read a (possibly quoted) ARFF value from the start of the input. Returns the value and the remaining input.
+fn read_arff_value(input: &str) -> (String, &str) {
+    let input = input.trim_left();
+    let mut chars = input.char_indices();
+    match chars.next() {
+        Some((_, quote)) if quote == '\'' || quote == '"' => {
+            let mut value = String::new();
+            let mut escaped = false;
+            for (index, c) in chars {
+                if escaped {
+                    value.push(c);
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == quote {
+                    return (value, &input[index + 1..]);
+                } else {
+                    value.push(c);
+                }
+            }
+            (value, "")
+        },
+        _ => {
+            let end = input.find(|c: char| c.is_whitespace() || c == ',').unwrap_or(input.len());
+            (input[..end].to_string(), &input[end..])
+        }
+    }
+}
+
+///Find the name and the type declaration of an attribute in the remainder of an `@attribute` line
+fn parse_arff_attribute(declaration: &str) -> Option<(String, String)> {
+    let (name, rest) = read_arff_value(declaration);
+    if name.is_empty() { None } else { Some((name, rest.trim().to_string())) }
+}
+
+///Find the values of a nominal type declaration, e.g. `{passed,failed}`. Other types have no values.
+fn parse_arff_nominal_values(declaration: &str) -> Option<Vec<String>> {
+    if declaration.starts_with('{') && declaration.ends_with('}') {
+        Some(split_arff_values(&declaration[1..declaration.len() - 1]))
+    } else {
+        None
+    }
+}
+
+///Split a line of ARFF data in its (unquoted) values
+fn split_arff_values(line: &str) -> Vec<String> {
+    let mut values = vec![];
+    let mut remaining = line;
+    loop {
+        let (value, rest) = read_arff_value(remaining);
+        values.push(value.trim().to_string());
+        match rest.find(',') {
+            Some(index) => remaining = &rest[index + 1..],
+            None => break
+        }
+    }
+    values
+}
+
+fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+    for i in 0..4 {
+        bytes.push((value >> (8 * i)) as u8);
+    }
+}
+
+fn push_u64(bytes: &mut Vec<u8>, value: u64) {
+    for i in 0..8 {
+        bytes.push((value >> (8 * i)) as u8);
+    }
+}
+
+///Pack a vector of booleans into bytes, where the first boolean is the least significant bit of the first byte
+fn to_bitset(values: &Vec<bool>) -> Vec<u8> {
+    let mut bytes = vec![0 as u8; (values.len() + 7) / 8];
+    for (i, value) in values.iter().enumerate() {
+        if *value {
+            bytes[i / 8] |= 1 << (i % 8);
+        }
+    }
+    bytes
+}
+
+///Collects the rows of a coverage matrix test by test, while the matrix is built.
+/// Unless duplicate rows are kept, a test with the same verdict and coverage as an earlier test does not get a row of its own, but is counted in the row of the earlier test. Tests without an id are never collapsed.
+struct RowCollector {
+    duplicate_rows: DuplicateRows,
+    components: Vec<String>,
+    columns: HashMap<String, usize>,
+    rows: Vec<Vec<bool>>, //The coverage of every row, by column. Rows that were added before a column existed are shorter.
+    error_vector: Vec<bool>,
+    test_id_vector: Vec<i64>,
+    row_keys: HashMap<(bool, Vec<bool>), usize>,
+    duplicate_test_ids: HashMap<i64, Vec<i64>>,
+}
+
+impl RowCollector {
+    fn new(duplicate_rows: DuplicateRows) -> RowCollector {
+        RowCollector {
+            duplicate_rows,
+            components: vec![],
+            columns: HashMap::new(),
+            rows: vec![],
+            error_vector: vec![],
+            test_id_vector: vec![],
+            row_keys: HashMap::new(),
+            duplicate_test_ids: HashMap::new(),
+        }
+    }
+
+    ///Add a test with the coverage of its components. A component that was not seen before gets a new column, which is not covered by the earlier rows.
+    fn add_test(&mut self, test_id: Option<i64>, errored: bool, coverage: Vec<(String, bool)>) {
+        let mut row = vec![false; self.components.len()];
+        for (component, covered) in coverage {
+            let existing_column = self.columns.get(&component).cloned();
+            let column = match existing_column {
+                Some(column) => column,
+                None => {
+                    self.columns.insert(component.to_string(), self.components.len());
+                    self.components.push(component);
+                    row.push(false);
+                    self.components.len() - 1
+                }
+            };
+            row[column] = row[column] || covered;
+        }
+        if let (true, Some(test_id)) = (self.duplicate_rows != DuplicateRows::Keep, test_id) {
+            //Trailing columns that are not covered are left out, because the earlier rows are shorter
+            let mut key_row = row.clone();
+            while key_row.last() == Some(&false) {
+                key_row.pop();
+            }
+            let key = (errored, key_row);
+            let first_row = self.row_keys.get(&key).cloned();
+            match first_row {
+                Some(first_row) => {
+                    self.duplicate_test_ids.entry(self.test_id_vector[first_row]).or_insert(vec![]).push(test_id);
+                    return;
+                },
+                None => { self.row_keys.insert(key, self.rows.len()); }
+            }
+        }
+        self.rows.push(row);
+        self.error_vector.push(errored);
+        self.test_id_vector.push(test_id.unwrap_or(-1));
+    }
+
+    fn into_coverage_matrix(self) -> CoverageMatrix {
+        let rows = self.rows;
+        let data = self.components.into_iter().enumerate()
+            .map(|(column, component)| (component, rows.iter().map(|row| row.get(column).cloned().unwrap_or(false)).collect::<Vec<bool>>()))
+            .collect::<HashMap<String, Vec<bool>>>();
+        let count_duplicates = self.duplicate_rows == DuplicateRows::Count;
+        let mut coverage_matrix = CoverageMatrix::new(self.error_vector, self.test_id_vector, data);
+        coverage_matrix.multiplicities = self.duplicate_test_ids.iter().map(|(test_id, duplicates)| (*test_id, if count_duplicates { duplicates.len() as u32 + 1 } else { 1 })).collect();
+        coverage_matrix.duplicate_test_ids = self.duplicate_test_ids;
+        coverage_matrix
     }
 }
 
 impl From<Vec<TestModel>> for CoverageMatrix {
     fn from(val: Vec<TestModel>) -> CoverageMatrix {
-        CoverageMatrix::from_model(val, None, 0.0,0.0)
+        CoverageMatrix::from_model(val, None, false, DuplicateRows::Keep, &|_| {})
     }
 }
 
 impl From<Vec<TestCase>> for CoverageMatrix {
     fn from(val: Vec<TestCase>) -> CoverageMatrix {
-        CoverageMatrix::from_traces(val, None, 0.0,0.0)
+        CoverageMatrix::from_traces(val, None, false, DuplicateRows::Keep, &|_| {})
     }
 }
 
@@ -258,7 +812,14 @@ impl From<Vec<TestCase>> for CoverageMatrix {
 #[cfg(test)]
 pub mod tests {
     use models::coverage_matrix::CoverageMatrix;
+    use models::coverage_matrix::MatrixFormat;
+    use models::coverage_matrix::DuplicateRows;
     use std::collections::HashMap;
+    use std::collections::HashSet;
+    use models::similarity::ComponentCounts;
+    use models::test_case::TestCase;
+    use models::test_case::TestResult;
+    use adapters::TestRecord;
 
     #[test]
     fn test_append() {
@@ -287,4 +848,215 @@ pub mod tests {
 
         assert_eq!(expected, first.append(second).unwrap());
     }
+
+    #[test]
+    fn test_add_test() {
+        //Given
+        let mut data = HashMap::new();
+        data.insert("a".to_string(), vec![true, false]);
+        data.insert("b".to_string(), vec![false, true]);
+        let mut matrix = CoverageMatrix::new(vec![false, true], vec![1, 2], data);
+
+        //When
+        let mut covered = HashSet::new();
+        covered.insert("b".to_string());
+        covered.insert("c".to_string());
+        matrix.add_test(3, true, &covered);
+
+        //Then
+        let mut expected_data = HashMap::new();
+        expected_data.insert("a".to_string(), vec![true, false, false]);
+        expected_data.insert("b".to_string(), vec![false, true, true]);
+        expected_data.insert("c".to_string(), vec![false, false, true]);
+        let expected = CoverageMatrix::new(vec![false, true, true], vec![1, 2, 3], expected_data);
+
+        assert_eq!(expected, matrix);
+    }
+
+    #[test]
+    fn test_export() {
+        //Given
+        let mut data = HashMap::new();
+        data.insert("!b".to_string(), vec![true, false, true]);
+        data.insert("?a".to_string(), vec![true, true, false]);
+        let matrix = CoverageMatrix::new(vec![true, false, false], vec![1, 5, 3], data);
+
+        //When
+        let csv = matrix.to_csv();
+        let export = matrix.to_export();
+        let binary = matrix.export(&MatrixFormat::Binary);
+        let filtered = matrix.filter_components("?");
+
+        //Then
+        assert_eq!("id,\"!b\",\"?a\",errored\n1,true,true,true\n5,false,true,false\n3,true,false,false\n", csv);
+        assert_eq!(vec!["!b".to_string(), "?a".to_string()], export.components);
+        assert_eq!(vec![vec![true, true], vec![false, true], vec![true, false]], export.coverage);
+        assert_eq!(1, export.number_of_failing_tests);
+        //header, two component names, three test ids and three bitsets
+        assert_eq!(13 + (4 + 2) * 2 + 8 * 3 + 3, binary.len());
+        assert_eq!(b"SFLM", &binary[0..4]);
+        assert_eq!(vec!["?a".to_string()], filtered.components());
+        assert_eq!(Some(MatrixFormat::Csv), MatrixFormat::from_name("text/csv"));
+        assert_eq!(None, MatrixFormat::from_name("text/html"));
+    }
+
+    #[test]
+    fn test_arff_round_trip() {
+        //Given
+        let mut data = HashMap::new();
+        data.insert("!b if ((x == \"it's\"))".to_string(), vec![true, false, true]);
+        data.insert("?a".to_string(), vec![true, true, false]);
+        let matrix = CoverageMatrix::new(vec![true, false, false], vec![1, 5, 3], data);
+
+        //When
+        let arff = matrix.to_arff();
+
+        //Then
+        assert!(arff.contains("@attribute '!b if ((x == \"it\\'s\"))' {false,true}"));
+        assert_eq!(matrix, CoverageMatrix::from_arff(&arff).unwrap());
+    }
+
+    #[test]
+    fn test_from_arff() {
+        //Given
+        let arff = "% Exported by Weka\n@RELATION testcases\n\n@ATTRIBUTE ?a {0,1}\n@ATTRIBUTE '!b' {0,1}\n@ATTRIBUTE verdict {passed,failed}\n\n@DATA\n1, 0, failed\n0,1,passed\n";
+
+        //When
+        let matrix = CoverageMatrix::from_arff(arff).unwrap();
+
+        //Then
+        let mut expected_data = HashMap::new();
+        expected_data.insert("?a".to_string(), vec![true, false]);
+        expected_data.insert("!b".to_string(), vec![false, true]);
+        assert_eq!(CoverageMatrix::new(vec![true, false], vec![-1, -1], expected_data), matrix);
+        assert!(CoverageMatrix::from_arff("@attribute a {0,1}\n@data\n1,0\n").is_err());
+    }
+
+    #[test]
+    fn test_from_arff_errors() {
+        assert_eq!(Err("There is no verdict attribute and the last attribute is the test id".to_string()), CoverageMatrix::from_arff("@attribute a {0,1}\n@attribute test_id numeric\n@data\n1,3\n"));
+        assert_eq!(Err("The value maybe of a is not a boolean (line 4)".to_string()), CoverageMatrix::from_arff("@attribute a {0,1}\n@attribute verdict {passed,failed}\n@data\nmaybe,failed\n"));
+        assert!(CoverageMatrix::from_arff("@attribute test_id numeric\n@attribute verdict {0,1}\n@attribute verdict {passed,failed}\n@data\n1,1,failed\n").is_err());
+        assert!(CoverageMatrix::from_arff("@attribute test_id numeric\n@attribute test_id numeric\n@attribute verdict {passed,failed}\n@data\n1,1,failed\n").is_err());
+        assert_eq!(Err("The attribute a is declared more than once (line 2)".to_string()), CoverageMatrix::from_arff("@attribute a {0,1}\n@attribute a {0,1}\n@attribute verdict {passed,failed}\n@data\n1,1,failed\n"));
+        assert_eq!(Err("The test id x1 is not a whole number (line 5)".to_string()), CoverageMatrix::from_arff("@attribute test_id numeric\n@attribute a {0,1}\n@attribute verdict {passed,failed}\n@data\nx1,1,failed\n"));
+        assert_eq!(Err("The verdict is missing (line 4)".to_string()), CoverageMatrix::from_arff("@attribute a {0,1}\n@attribute verdict {passed,failed}\n@data\n1,?\n"));
+        assert_eq!(Err("The verdict error is not one of the declared values [\"passed\", \"failed\"] (line 4)".to_string()), CoverageMatrix::from_arff("@attribute a {0,1}\n@attribute verdict {passed,failed}\n@data\n1,error\n"));
+        assert_eq!(Err("The verdict attribute verdict is not nominal".to_string()), CoverageMatrix::from_arff("@attribute a {0,1}\n@attribute verdict string\n@data\n1,failed\n"));
+    }
+
+    #[test]
+    fn test_for_error_signature() {
+        //Given
+        let mut data = HashMap::new();
+        data.insert("a".to_string(), vec![true, true, false, true]);
+        let mut matrix = CoverageMatrix::new(vec![false, true, true, true], vec![1, 2, 3, 4], data);
+        matrix.set_error_signature(2, "timeout".to_string());
+        matrix.set_error_signature(3, "crash".to_string());
+        matrix.set_error_signature(4, "timeout".to_string());
+
+        //When
+        let timeout_matrix = matrix.for_error_signature("timeout");
+
+        //Then
+        assert_eq!(vec!["crash".to_string(), "timeout".to_string()], matrix.error_classes());
+        assert_eq!(vec![None, Some("timeout".to_string()), Some("crash".to_string()), Some("timeout".to_string())], matrix.error_signature_vector());
+        assert_eq!(vec![1, 2, 4], *timeout_matrix.test_ids());
+        assert_eq!(vec![true, true, true], timeout_matrix.data["a"]);
+        assert_eq!(vec![false, true, true], timeout_matrix.error_vector);
+    }
+
+    fn duplicate_test_cases() -> Vec<TestCase> {
+        vec![
+            TestRecord::new(1, TestResult::passed, vec!["a".to_string()], None).to_test_case(),
+            TestRecord::new(2, TestResult::passed, vec!["a".to_string()], None).to_test_case(),
+            TestRecord::new(3, TestResult::passed, vec!["a".to_string()], None).to_test_case(),
+            TestRecord::new(4, TestResult::passed, vec!["b".to_string()], None).to_test_case(),
+            TestRecord::new(5, TestResult::failed, vec!["a".to_string(), "b".to_string()], None).to_test_case(),
+        ]
+    }
+
+    #[test]
+    fn test_from_traces_with_duplicates() {
+        //Given
+        let test_cases = duplicate_test_cases();
+
+        //When
+        let matrix = CoverageMatrix::from_traces(test_cases.clone(), None, false, DuplicateRows::Keep, &|_| {});
+        let counted = CoverageMatrix::from_traces(test_cases.clone(), None, false, DuplicateRows::Count, &|_| {});
+        let distinct = CoverageMatrix::from_traces(test_cases, None, false, DuplicateRows::CollapseOnce, &|_| {});
+
+        //Then
+        assert_eq!(vec![1, 2, 3, 4, 5], *matrix.test_ids());
+        assert_eq!(vec![1, 4, 5], *counted.test_ids());
+        assert_eq!(vec![true, false, true], counted.data["a"]);
+        assert_eq!(vec![false, true, true], counted.data["b"]);
+        assert_eq!(vec![1, 2, 3], counted.represented_test_ids(0));
+        assert_eq!(3.0, counted.row_weight(0));
+        assert_eq!(1.0, distinct.row_weight(0));
+        assert_eq!(ComponentCounts::from_matrix("a", &matrix), ComponentCounts::from_matrix("a", &counted));
+        assert_eq!(vec![3, 1, 1], counted.to_export().multiplicities);
+        assert_eq!(Some(0), counted.row_of_test(3));
+    }
+
+    #[test]
+    fn test_from_traces_collapses_tests_with_the_same_pairs() {
+        //Given
+        let first = TestRecord::new(1, TestResult::passed, vec!["a".to_string(), "b".to_string()], None).to_test_case();
+        let mut second = first.clone();
+        second.id = Some(2);
+        let third = TestRecord::new(3, TestResult::passed, vec!["b".to_string(), "a".to_string()], None).to_test_case();
+        let pairs = vec![first.steps.clone(), third.steps.clone()];
+
+        //When
+        let counted = CoverageMatrix::from_traces(vec![first, second, third], Some(pairs), false, DuplicateRows::Count, &|_| {});
+
+        //Then
+        assert_eq!(vec!["a", "a -> b", "b", "b -> a"], counted.components());
+        assert_eq!(vec![1, 3], *counted.test_ids());
+        assert_eq!(vec![1, 2], counted.represented_test_ids(0));
+        assert_eq!(vec![true, false], counted.data["a -> b"]);
+        assert_eq!(vec![false, true], counted.data["b -> a"]);
+    }
+
+    #[test]
+    fn test_separate_different_tests() {
+        //Given
+        let mut counted = CoverageMatrix::from_traces(duplicate_test_cases(), None, false, DuplicateRows::Count, &|_| {});
+        counted.set_test_weight(2, 2.0);
+
+        //When
+        let separated = counted.separate_different_tests(|_| ());
+        let separated_by_key = counted.separate_different_tests(|test_id| test_id == 3);
+
+        //Then
+        assert_eq!(vec![1, 2, 4, 5], *separated.test_ids());
+        assert_eq!(vec![1, 3], separated.represented_test_ids(0));
+        assert_eq!(2.0, separated.row_weight(0));
+        assert_eq!(2.0, separated.row_weight(1));
+        assert_eq!(vec![true, true, false, true], separated.data["a"]);
+        assert_eq!(vec![1, 2, 3, 4, 5], *separated_by_key.test_ids());
+    }
+
+    #[test]
+    fn test_for_test_ids_of_deduplicated_matrix() {
+        //Given
+        let matrix = CoverageMatrix::from_traces(duplicate_test_cases(), None, false, DuplicateRows::Keep, &|_| {});
+        let counted = CoverageMatrix::from_traces(duplicate_test_cases(), None, false, DuplicateRows::Count, &|_| {});
+        let distinct = CoverageMatrix::from_traces(duplicate_test_cases(), None, false, DuplicateRows::CollapseOnce, &|_| {});
+
+        //When
+        let test_ids = [2, 3, 5].iter().cloned().collect::<HashSet<i64>>();
+        let selected = counted.for_test_ids(&test_ids);
+        let without = counted.without_test_ids(&test_ids);
+
+        //Then
+        assert_eq!(vec![2, 5], *selected.test_ids());
+        assert_eq!(vec![2, 3], selected.represented_test_ids(0));
+        assert_eq!(2.0, selected.row_weight(0));
+        assert_eq!(ComponentCounts::from_matrix("a", &matrix.for_test_ids(&test_ids)), ComponentCounts::from_matrix("a", &selected));
+        assert_eq!(vec![1, 4], *without.test_ids());
+        assert_eq!(1.0, without.row_weight(0));
+        assert_eq!(1.0, distinct.for_test_ids(&test_ids).row_weight(0));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,83 @@
+use models::coverage_matrix::CoverageMatrix;
+use models::model::TestModel;
+use models::similarity::ComponentCounts;
+use models::similarity::SimilarityCoefficient;
+use models::test_case::TestCase;
+
+///The observation where a failing test deviated from the model: an unexpected output where other labels were expected, from a certain state.
+/// The deviation is also a component of the coverage matrix, covered by every failing test with the same deviation.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Deviation {
+    ///The state of the model right before the failing step, if it could be found
+    pub state: Option<String>,
+    ///The label that was observed at the failing step, or None if one of the expected labels was observed (e.g. when nothing was received in time)
+    pub observed: Option<String>,
+    pub expected: Vec<String>,
+    ///The similarity coefficient of the deviation component in the coverage matrix
+    pub coefficient: f32,
+}
+
+impl Deviation {
+    ///Create the deviation of a failing test case from its last step and expected labels. If the test case has no expected labels, there is no deviation.
+    /// The observed label includes the data of the step if use transition data is set.
+    pub fn from_test_case(testcase: &TestCase, model: Option<&TestModel>, use_transition_data: bool) -> Option<Deviation> {
+        let expected = match testcase.expected_labels() {
+            Some(expected_labels) if !expected_labels.is_empty() => expected_labels.iter().map(|it| it.label().get_full_label()).collect::<Vec<String>>(),
+            _ => return None
+        };
+        let failure_index = testcase.failure_index();
+        let observed = failure_index.map(|index| testcase.steps[index].get_full_label_with_params(use_transition_data)).filter(|it| !expected.contains(it));
+
+        //The state is the target of the step before the failing step, or the start state if the test failed at its first step
+        let state = match (failure_index, model) {
+            (Some(index), Some(model)) if index > 0 => testcase.steps[index - 1].to_transition(model, use_transition_data).map(|it| it.target),
+            (_, Some(model)) => model.stss.first().and_then(|sts| sts.start_states().first()).map(|it| it.id().clone()),
+            _ => None
+        };
+        Some(Deviation { state, observed, expected, coefficient: 0.0 })
+    }
+
+    ///The name of the deviation component in the coverage matrix
+    pub fn component(&self) -> String {
+        format!("{} instead of {} in {}", self.observed.as_ref().map(|it| it.as_str()).unwrap_or("nothing"), self.expected.join(" or "), self.state.as_ref().map(|it| it.as_str()).unwrap_or("unknown state"))
+    }
+
+    ///Set the coefficient of the deviation component in the coverage matrix
+    pub fn with_coefficient(mut self, coverage_matrix: &CoverageMatrix, coefficient: &SimilarityCoefficient) -> Deviation {
+        self.coefficient = ComponentCounts::from_matrix(&self.component(), coverage_matrix).map(|it| it.similarity(coefficient)).unwrap_or(0.0);
+        self
+    }
+}
+
+///Weigh a coefficient by the distance of the step to the failing step. With a weight of 0, the coefficient is not changed.
+/// With a weight of 1, the coefficient of the first step of a long test case approaches 0, while the coefficient of the failing step is unchanged.
+pub fn weigh_by_proximity(coefficient: f32, step_index: usize, failure_index: Option<usize>, number_of_steps: usize, weight: f32) -> f32 {
+    match failure_index {
+        Some(failure_index) if weight > 0.0 && number_of_steps > 0 => {
+            let distance = if step_index > failure_index { step_index - failure_index } else { failure_index - step_index };
+            let factor = 1.0 - weight * distance as f32 / number_of_steps as f32;
+            coefficient * factor.max(0.0)
+        },
+        _ => coefficient
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_weigh_by_proximity() {
+        assert_eq!(0.8, weigh_by_proximity(0.8, 3, Some(4), 4, 0.0));
+        assert_eq!(0.8, weigh_by_proximity(0.8, 4, Some(4), 4, 1.0));
+        assert_eq!(0.4, weigh_by_proximity(0.8, 2, Some(4), 4, 1.0));
+        assert_eq!(0.8, weigh_by_proximity(0.8, 2, None, 4, 1.0));
+    }
+
+    #[test]
+    fn check_component() {
+        let deviation = Deviation { state: Some(String::from("s1")), observed: Some(String::from("!x")), expected: vec![String::from("!y"), String::from("!z")], coefficient: 0.0 };
+
+        assert_eq!("!x instead of !y or !z in s1", deviation.component());
+    }
+}
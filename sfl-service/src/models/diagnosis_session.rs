@@ -0,0 +1,169 @@
+use models::model::TestModel;
+use models::model::Transition;
+use models::model_diff::transition_key;
+use models::reproduction_path::main_start_states;
+use models::reproduction_path::shortest_path_avoiding;
+use models::test_case::TestResult;
+use uuid::Uuid;
+
+///The number of follow-up tests that are suggested at the same time
+const NUMBER_OF_SUGGESTIONS: usize = 3;
+
+///A transition that may be the root cause, with the verdicts of the follow-up tests that covered it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Candidate {
+    pub transition: Transition,
+    ///The coefficient of the transition in the coverage matrix when the session started
+    pub coefficient: f32,
+    pub passed: u32,
+    pub failed: u32,
+    ///The Ochiai coefficient of the transition over the failing test of the session and its follow-up tests
+    pub score: f32,
+}
+
+///A path through the model that can be run as a follow-up test, because it covers some of the most suspicious candidates but not all of them
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FollowUpTest {
+    pub id: usize,
+    pub path: Vec<Transition>,
+    ///The candidates that the path covers
+    pub covered_candidates: Vec<String>,
+    ///The verdict that was posted back, or None if the test was not run yet
+    pub verdict: Option<TestResult>,
+}
+
+///The verdict of a follow-up test that was run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FollowUpVerdict {
+    pub follow_up_test: usize,
+    pub verdict: TestResult,
+}
+
+///A diagnosis that keeps the candidates that could not be told apart, and suggests follow-up tests until a single candidate is the most suspicious
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiagnosisSession {
+    pub id: Uuid,
+    pub test_id: Option<i64>,
+    ///The candidates, from the most to the least suspicious
+    pub candidates: Vec<Candidate>,
+    pub follow_up_tests: Vec<FollowUpTest>,
+    ///Whether a single candidate is more suspicious than all others
+    pub resolved: bool,
+}
+
+impl DiagnosisSession {
+    ///Start a session for the candidate transitions of a failing test, with their coefficients
+    pub fn new(test_id: Option<i64>, candidates: Vec<(Transition, f32)>, model: &TestModel) -> DiagnosisSession {
+        let mut session = DiagnosisSession {
+            id: Uuid::new_v4(),
+            test_id,
+            candidates: candidates.into_iter().map(|(transition, coefficient)| Candidate { transition, coefficient, passed: 0, failed: 0, score: 1.0 }).collect(),
+            follow_up_tests: vec![],
+            resolved: false,
+        };
+        session.update(model);
+        session
+    }
+
+    ///Process the verdict of a follow-up test: the candidates it covered get the verdict, the ranking is updated and new follow-up tests are suggested
+    pub fn add_verdict(&mut self, verdict: &FollowUpVerdict, model: &TestModel) -> Result<(), String> {
+        let failed = match verdict.verdict {
+            TestResult::passed => false,
+            TestResult::failed | TestResult::error => true,
+            TestResult::unknown => return Err(String::from("An unknown verdict does not tell the candidates apart"))
+        };
+        let index = match self.follow_up_tests.iter().position(|it| it.id == verdict.follow_up_test) {
+            Some(index) => index,
+            None => return Err(format!("There is no follow-up test {}", verdict.follow_up_test))
+        };
+        if self.follow_up_tests[index].verdict.is_some() {
+            return Err(format!("The verdict of follow-up test {} was already posted", verdict.follow_up_test));
+        }
+        self.follow_up_tests[index].verdict = Some(verdict.verdict.clone());
+        let covered_candidates = self.follow_up_tests[index].covered_candidates.clone();
+        for candidate in self.candidates.iter_mut().filter(|it| covered_candidates.contains(&transition_key(&it.transition))) {
+            if failed { candidate.failed += 1 } else { candidate.passed += 1 }
+        }
+        self.update(model);
+        Ok(())
+    }
+
+    ///Recompute the scores and the ranking of the candidates, and replace the follow-up tests that were not run yet by new suggestions
+    fn update(&mut self, model: &TestModel) {
+        let failed_follow_ups = self.follow_up_tests.iter().filter(|it| it.verdict.as_ref().map(|verdict| verdict != &TestResult::passed).unwrap_or(false)).count() as f32;
+        for candidate in self.candidates.iter_mut() {
+            //The failing test of the session covered every candidate
+            let a_11 = 1.0 + candidate.failed as f32;
+            let a_10 = candidate.passed as f32;
+            let a_01 = failed_follow_ups - candidate.failed as f32;
+            candidate.score = a_11 / ((a_11 + a_01) * (a_11 + a_10)).sqrt();
+        }
+        self.candidates.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap().then(b.coefficient.partial_cmp(&a.coefficient).unwrap()));
+        self.follow_up_tests.retain(|it| it.verdict.is_some());
+
+        let group = self.ambiguity_group();
+        self.resolved = group.len() <= 1;
+        if self.resolved {
+            return;
+        }
+        let start_states = main_start_states(model);
+        let mut suggestions: Vec<(Vec<Transition>, Vec<String>)> = vec![];
+        for candidate in group.iter() {
+            //Prefer a path that only covers this candidate, so its verdict tells the most about it
+            let others = group.iter().filter(|it| *it != candidate).cloned().collect::<Vec<Transition>>();
+            let path = shortest_path_avoiding(model, &start_states, &candidate.source, &others).or_else(|| shortest_path_avoiding(model, &start_states, &candidate.source, &[]));
+            if let Some((_, mut path)) = path {
+                path.push(candidate.clone());
+                let mut covered = group.iter().filter(|it| path.contains(*it)).map(|it| transition_key(it)).collect::<Vec<String>>();
+                covered.sort();
+                if covered.len() < group.len() && !suggestions.iter().any(|it| it.1 == covered) {
+                    suggestions.push((path, covered));
+                }
+            }
+        }
+        //The most even split of the candidates first, then the shortest path
+        suggestions.sort_by_key(|it| ((2 * it.1.len() as i64 - group.len() as i64).abs(), it.0.len()));
+        let first_id = self.follow_up_tests.iter().map(|it| it.id + 1).max().unwrap_or(0);
+        for (i, (path, covered_candidates)) in suggestions.into_iter().take(NUMBER_OF_SUGGESTIONS).enumerate() {
+            self.follow_up_tests.push(FollowUpTest { id: first_id + i, path, covered_candidates, verdict: None });
+        }
+    }
+
+    ///The transitions of the candidates with the highest score, which cannot be told apart yet
+    fn ambiguity_group(&self) -> Vec<Transition> {
+        let best = self.candidates.first().map(|it| it.score).unwrap_or(0.0);
+        self.candidates.iter().filter(|it| best - it.score < 1e-6).map(|it| it.transition.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::model::State;
+    use models::model::StateAttribute;
+    use models::model::Sts;
+    use models::model::TransitionAttribute;
+
+    #[test]
+    fn check_diagnosis_session() {
+        //Given
+        let state = |id: &str| State::new(id.to_string(), StateAttribute::new(id.to_string(), String::from("state"), None));
+        let transition = |source: &str, label: &str, target: &str| Transition::new(source.to_string(), target.to_string(), TransitionAttribute::new(label.to_string(), None, None));
+        let s0 = state("s0");
+        let model = TestModel::new(Uuid::nil(), vec![Sts::new(String::from("main"), vec![s0.clone(), state("s1")], vec![], vec![s0.to_start_state()],
+                                                            vec![transition("s0", "?a", "s1"), transition("s0", "?b", "s1"), transition("s1", "!c", "s0")], vec![], None, None, None)], None);
+        let mut session = DiagnosisSession::new(Some(1), vec![(transition("s0", "?a", "s1"), 0.8), (transition("s0", "?b", "s1"), 0.8)], &model);
+        assert!(!session.resolved);
+        assert_eq!(2, session.follow_up_tests.len());
+        let follow_up_test = session.follow_up_tests.iter().find(|it| it.covered_candidates == vec![String::from("s0--?a-->s1")]).unwrap().id;
+
+        //When
+        session.add_verdict(&FollowUpVerdict { follow_up_test, verdict: TestResult::passed }, &model).unwrap();
+
+        //Then
+        assert!(session.resolved);
+        assert_eq!("?b", session.candidates[0].transition.attributes.label);
+        assert_eq!(1, session.candidates[1].passed);
+        assert!(session.add_verdict(&FollowUpVerdict { follow_up_test, verdict: TestResult::failed }, &model).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use models::coverage_matrix::CoverageMatrix;
+use models::similarity::ComponentCounts;
+use models::similarity::SimilarityCoefficient;
+
+///The most suspicious components for the failing tests with the same error signature
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignatureRanking {
+    pub signature: String,
+    pub test_ids: Vec<i64>,
+    pub ranking: Vec<(String, f32)>,
+}
+
+///Normalise an error message into a stable signature. Timestamps, ids, base64 payloads and numbers are replaced by placeholders,
+/// so that the same failure in different runs gets the same signature, e.g. `Timeout after 1500 ms at 2018-05-01T12:00:00Z` becomes `timeout after <n> ms at <time>`.
+pub fn normalise_error_message(error_message: &str) -> String {
+    error_message.split_whitespace().map(|word| {
+        //Keep the punctuation around a word, so e.g. quotes and colons remain in the signature
+        let start = word.find(|c: char| c.is_alphanumeric()).unwrap_or(word.len());
+        let end = word.rfind(|c: char| c.is_alphanumeric() || c == '=').map(|it| it + word[it..].chars().next().unwrap().len_utf8()).unwrap_or(start);
+        if start >= end {
+            return word.to_string();
+        }
+        format!("{}{}{}", &word[..start], normalise_word(&word[start..end]), &word[end..])
+    }).collect::<Vec<String>>().join(" ").to_lowercase()
+}
+
+///Replace a single word by a placeholder if it is a timestamp, an id, a base64 payload or contains numbers
+fn normalise_word(word: &str) -> String {
+    let digits = word.chars().filter(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        //Long words without digits are only base64 if they also contain `+`, `/` or `=`, so names like `ConcurrentModificationException` are kept
+        return if is_base64(word) && word.contains(|c: char| "+/=".contains(c)) && word.len() >= 16 { String::from("<data>") } else { word.to_string() };
+    }
+    if is_timestamp(word) {
+        String::from("<time>")
+    } else if is_id(word) {
+        String::from("<id>")
+    } else if is_base64(word) && word.len() >= 16 {
+        String::from("<data>")
+    } else {
+        //Replace every sequence of digits by a placeholder
+        let mut normalised = String::new();
+        let mut in_number = false;
+        for c in word.chars() {
+            if c.is_ascii_digit() || (in_number && c == '.') {
+                if !in_number {
+                    normalised.push_str("<n>");
+                    in_number = true;
+                }
+            } else {
+                normalised.push(c);
+                in_number = false;
+            }
+        }
+        normalised
+    }
+}
+
+///Dates and times like 2018-05-01, 12:00:00.123 or 2018-05-01T12:00:00Z
+fn is_timestamp(word: &str) -> bool {
+    let has_separator = word.contains(':') || word.matches('-').count() >= 2 || word.matches('/').count() >= 2;
+    has_separator && word.chars().all(|c| c.is_ascii_digit() || ":-/.+TZtz".contains(c))
+}
+
+///Hexadecimal ids and uuids of at least 8 characters that contain a digit
+fn is_id(word: &str) -> bool {
+    let hex = word.trim_left_matches("0x");
+    hex.len() >= 8 && hex.chars().all(|c| c.is_ascii_hexdigit() || c == '-') && hex.chars().any(|c| c.is_ascii_digit())
+}
+
+fn is_base64(word: &str) -> bool {
+    word.trim_right_matches('=').chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/')
+}
+
+///Rank the components of the coverage matrix for every error signature, where only the failing tests with that signature count as failing.
+/// Only the given number of most suspicious components is included per signature.
+pub fn rank_per_error_signature(coverage_matrix: &CoverageMatrix, coefficient: &SimilarityCoefficient, number_of_components: usize) -> Vec<SignatureRanking> {
+    coverage_matrix.error_classes().into_iter().map(|signature| {
+        let signature_matrix = coverage_matrix.for_error_signature(&signature);
+        let test_ids = (0..signature_matrix.error_vector.len()).filter(|row| signature_matrix.error_vector[*row]).map(|row| signature_matrix.represented_test_ids(row)).flatten().collect::<Vec<i64>>();
+        let mut ranking = signature_matrix.components().into_iter()
+            .filter_map(|component| ComponentCounts::from_matrix(&component, &signature_matrix).map(|counts| (component, counts.similarity(coefficient))))
+            .collect::<Vec<(String, f32)>>();
+        ranking.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+        ranking.truncate(number_of_components);
+        SignatureRanking { signature, test_ids, ranking }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_normalise_error_message() {
+        assert_eq!("timeout after <n> ms at <time>", normalise_error_message("Timeout after 1500 ms at 2018-05-01T12:00:00Z"));
+        assert_eq!("timeout after <n> ms at <time>", normalise_error_message("Timeout  after 20 ms at 2018-06-11T08:13:59Z"));
+        assert_eq!("session <id> closed: \"<data>\"", normalise_error_message("Session 3f2a9c1e-77b0-4c5e-9f00-1a2b3c4d5e6f closed: \"SGVsbG8gd29ybGQgMTIzNDU2Nzg=\""));
+        assert_eq!("expected !out<n> but received nothing.", normalise_error_message("Expected !out12 but received nothing."));
+        assert_eq!("value <n> is out of range", normalise_error_message("Value 3.14 is out of range"));
+        assert_eq!("java.util.concurrentmodificationexception: concurrentmodificationexception", normalise_error_message("java.util.ConcurrentModificationException: ConcurrentModificationException"));
+        assert_eq!("payload <data> rejected", normalise_error_message("Payload abcDEF+ghiJKL/mnoPQR== rejected"));
+    }
+}
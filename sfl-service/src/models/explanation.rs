@@ -0,0 +1,135 @@
+use models::coverage_matrix::CoverageMatrix;
+use models::model::Transition;
+use models::similarity::ComponentCounts;
+use models::similarity::SimilarityCoefficient;
+use models::test_case::Step;
+
+///Explains how an analysis result was found, so it is clear why a test ended up in its group
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Explanation {
+    pub similarity_coefficient: SimilarityCoefficient,
+    pub similarity_threshold: f32,
+    ///The components (transitions, steps or pairs of them) that were considered, in the order of the analysis
+    pub components: Vec<ComponentExplanation>,
+    ///True if no component crossed the threshold and the component with the highest coefficient was returned instead
+    pub max_similarity_fallback_used: bool,
+    ///How the steps of the test case were mapped on the transitions of the model
+    pub mappings: Vec<StepMapping>,
+    ///Why a state was or was not extracted from the problematic transitions
+    pub state_extraction: Option<String>,
+    ///Whether the components and mappings are recorded, which is only needed if the explanation is returned
+    #[serde(skip)]
+    recording: bool,
+}
+
+///A single component that was considered during the analysis
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ComponentExplanation {
+    pub component: String,
+    pub coefficient: f32,
+    ///The counts in the coverage matrix, or None if the component has no column in the coverage matrix
+    pub counts: Option<ComponentCounts>,
+    pub crossed_threshold: bool,
+}
+
+///The mapping of a step on a transition of the model. A mapping is fuzzy when the labels do not match exactly, but the best alternative was used.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StepMapping {
+    pub step: String,
+    pub transition: Option<String>,
+    pub fuzzy: bool,
+}
+
+impl Explanation {
+    pub fn new(similarity_coefficient: SimilarityCoefficient, similarity_threshold: f32) -> Explanation {
+        Explanation {
+            similarity_coefficient,
+            similarity_threshold,
+            components: vec![],
+            max_similarity_fallback_used: false,
+            mappings: vec![],
+            state_extraction: None,
+            recording: true,
+        }
+    }
+
+    ///Set whether the components and mappings are recorded. If the explanation is not returned, nothing has to be recorded, so the analysis does not spend time on it.
+    pub fn recording(mut self, recording: bool) -> Explanation {
+        self.recording = recording;
+        self
+    }
+
+    ///Add a component with its coefficient and its counts in the coverage matrix
+    pub fn consider(&mut self, component: &str, coefficient: f32, coverage_matrix: &CoverageMatrix) {
+        if !self.recording {
+            return;
+        }
+        self.components.push(ComponentExplanation {
+            component: component.to_string(),
+            coefficient,
+            counts: ComponentCounts::from_matrix(component, coverage_matrix),
+            crossed_threshold: coefficient > self.similarity_threshold,
+        });
+    }
+
+    ///Add the mapping of a step on a transition, unless the same mapping was already added. The transition is labelled as its component, so with its data if transition data is used.
+    pub fn map(&mut self, step: &Step, transition: Option<&Transition>, use_transition_data: bool) {
+        if !self.recording {
+            return;
+        }
+        let mapping = StepMapping {
+            step: step.get_full_label_with_params(true),
+            transition: transition.map(|it| it.to_string_with_data(use_transition_data)),
+            fuzzy: transition.map(|it| it.attributes.label != step.get_full_label_with_params(true)).unwrap_or(true),
+        };
+        if !self.mappings.contains(&mapping) {
+            self.mappings.push(mapping);
+        }
+    }
+
+    ///The components that crossed the threshold
+    pub fn crossed_threshold(&self) -> Vec<&ComponentExplanation> {
+        self.components.iter().filter(|it| it.crossed_threshold).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn check_consider() {
+        //Given
+        let mut data = HashMap::new();
+        data.insert(String::from("a"), vec![true, false]);
+        data.insert(String::from("b"), vec![true, true]);
+        let matrix = CoverageMatrix::new(vec![true, false], vec![1, 2], data);
+        let mut explanation = Explanation::new(SimilarityCoefficient::ochiai, 0.75);
+
+        //When
+        explanation.consider("a", 1.0, &matrix);
+        explanation.consider("b", 0.71, &matrix);
+        explanation.consider("c", 0.0, &matrix);
+
+        //Then
+        assert_eq!(vec!["a".to_string()], explanation.crossed_threshold().iter().map(|it| it.component.clone()).collect::<Vec<_>>());
+        assert_eq!(Some(ComponentCounts { a_11: 1.0, a_10: 1.0, a_01: 0.0, a_00: 0.0, a_s1: 0.0, a_s0: 0.0 }), explanation.components[1].counts);
+        assert_eq!(None, explanation.components[2].counts);
+    }
+
+    #[test]
+    fn check_not_recording() {
+        //Given
+        let mut data = HashMap::new();
+        data.insert(String::from("a"), vec![true, false]);
+        let matrix = CoverageMatrix::new(vec![true, false], vec![1, 2], data);
+        let mut explanation = Explanation::new(SimilarityCoefficient::ochiai, 0.75).recording(false);
+
+        //When
+        explanation.consider("a", 1.0, &matrix);
+
+        //Then
+        assert!(explanation.components.is_empty());
+    }
+}
\ No newline at end of file
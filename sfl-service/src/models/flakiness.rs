@@ -0,0 +1,217 @@
+use models::test_case::TestCase;
+use models::test_case::TestResult;
+use std::cmp::max;
+use std::cmp::min;
+
+///What to do with the tests of a flaky trace during the computation of the coefficients
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[allow(non_camel_case_types)]
+pub enum FlakyPolicy {
+    ///Only report the flaky traces, the coverage matrix is not changed
+    report,
+    ///Remove the flaky tests from the coverage matrix
+    drop,
+    ///Count the flaky tests with a lower weight
+    weight,
+    ///Count the flaky tests as a class of their own, which is partly failing and partly passing whatever the verdict, see `ComponentCounts::similarity`
+    separate_class,
+}
+
+impl FlakyPolicy {
+    pub fn from_name(name: &str) -> Option<FlakyPolicy> {
+        match name.trim().to_lowercase().as_str() {
+            "report" => Some(FlakyPolicy::report),
+            "drop" => Some(FlakyPolicy::drop),
+            "weight" => Some(FlakyPolicy::weight),
+            "separate_class" => Some(FlakyPolicy::separate_class),
+            _ => None
+        }
+    }
+}
+
+///A trace that failed in some tests and passed in other tests with the same (or nearly the same) steps
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FlakyTrace {
+    pub steps: Vec<String>,
+    pub failing_test_ids: Vec<Option<i64>>,
+    pub passing_test_ids: Vec<Option<i64>>,
+    ///The lowest similarity between the failing trace and the traces of the passing tests, where 1 means identical steps
+    pub similarity: f32,
+}
+
+impl FlakyTrace {
+    ///The ids of both the failing and the passing tests of the trace. Tests without an id are left out, see `number_of_tests_without_id`.
+    pub fn test_ids(&self) -> Vec<i64> {
+        self.failing_test_ids.iter().chain(self.passing_test_ids.iter()).filter_map(|it| *it).collect()
+    }
+
+    ///The number of tests of the trace without an id, to which no flaky test policy can be applied
+    pub fn number_of_tests_without_id(&self) -> usize {
+        self.failing_test_ids.iter().chain(self.passing_test_ids.iter()).filter(|it| it.is_none()).count()
+    }
+}
+
+///Find the traces of failing tests that also occur in passing tests. Failing tests with identical steps are reported as one trace.
+/// A passing test has the same trace if the similarity of the steps is at least the minimal similarity, so with 1.0 only identical traces are found.
+/// The steps include their data if use transition data is set.
+pub fn detect_flaky_traces(passing: &[TestCase], failing: &[TestCase], min_similarity: f32, use_transition_data: bool) -> Vec<FlakyTrace> {
+    let passing_traces = passing.iter().map(|it| (it.id, it.get_steps(use_transition_data))).collect::<Vec<(Option<i64>, Vec<String>)>>();
+    let mut flaky_traces: Vec<FlakyTrace> = vec![];
+    for testcase in failing {
+        let steps = testcase.get_steps(use_transition_data);
+        let trace_index = flaky_traces.iter().position(|it| it.steps == steps);
+        if let Some(index) = trace_index {
+            flaky_traces[index].failing_test_ids.push(testcase.id);
+            continue;
+        }
+        let similar = passing_traces.iter().map(|&(id, ref passing_steps)| (id, trace_similarity(&steps, passing_steps))).filter(|&(_, similarity)| similarity >= min_similarity).collect::<Vec<(Option<i64>, f32)>>();
+        if !similar.is_empty() {
+            flaky_traces.push(FlakyTrace {
+                steps,
+                failing_test_ids: vec![testcase.id],
+                passing_test_ids: similar.iter().map(|it| it.0).collect(),
+                similarity: similar.iter().map(|it| it.1).fold(1.0, f32::min),
+            });
+        }
+    }
+    flaky_traces
+}
+
+///Update the flaky traces with a single new test, so they match what `detect_flaky_traces` finds on all tests.
+/// The passing and failing tests are all stored tests, including the new one.
+pub fn add_to_flaky_traces(flaky_traces: &mut Vec<FlakyTrace>, testcase: &TestCase, passing: &[TestCase], failing: &[TestCase], min_similarity: f32, use_transition_data: bool) {
+    let steps = testcase.get_steps(use_transition_data);
+    if testcase.verdict != Some(TestResult::passed) {
+        if let Some(flaky_trace) = flaky_traces.iter_mut().find(|it| it.steps == steps) {
+            flaky_trace.failing_test_ids.push(testcase.id);
+            return;
+        }
+        flaky_traces.append(&mut detect_flaky_traces(passing, &[testcase.clone()], min_similarity, use_transition_data));
+        return;
+    }
+    for flaky_trace in flaky_traces.iter_mut() {
+        let similarity = trace_similarity(&flaky_trace.steps, &steps);
+        if similarity >= min_similarity {
+            flaky_trace.passing_test_ids.push(testcase.id);
+            flaky_trace.similarity = flaky_trace.similarity.min(similarity);
+        }
+    }
+
+    //Failing tests that had no similar passing test before may have one now
+    let newly_flaky = failing.iter()
+        .filter(|it| !flaky_traces.iter().any(|flaky_trace| flaky_trace.steps == it.get_steps(use_transition_data)))
+        .filter(|it| trace_similarity(&it.get_steps(use_transition_data), &steps) >= min_similarity)
+        .cloned().collect::<Vec<TestCase>>();
+    flaky_traces.append(&mut detect_flaky_traces(passing, &newly_flaky, min_similarity, use_transition_data));
+}
+
+///The similarity of two traces, based on the edit distance of the steps. Identical traces have a similarity of 1.
+pub fn trace_similarity(a: &[String], b: &[String]) -> f32 {
+    let longest = max(a.len(), b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - edit_distance(a, b) as f32 / longest as f32
+}
+
+///The number of steps that have to be inserted, removed or replaced to turn one trace into the other
+fn edit_distance(a: &[String], b: &[String]) -> usize {
+    let mut previous = (0..b.len() + 1).collect::<Vec<usize>>();
+    for (i, step_a) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, step_b) in b.iter().enumerate() {
+            let substitution = previous[j] + if step_a == step_b { 0 } else { 1 };
+            current.push(min(substitution, min(previous[j + 1], current[j]) + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use adapters::TestRecord;
+
+    fn trace(steps: &[&str]) -> Vec<String> {
+        steps.iter().map(|it| it.to_string()).collect()
+    }
+
+    #[test]
+    fn check_trace_similarity() {
+        assert_eq!(1.0, trace_similarity(&trace(&["?a", "!b", "?c"]), &trace(&["?a", "!b", "?c"])));
+        assert_eq!(0.75, trace_similarity(&trace(&["?a", "!b", "?c", "!d"]), &trace(&["?a", "!x", "?c", "!d"])));
+        assert_eq!(0.75, trace_similarity(&trace(&["?a", "!b", "?c"]), &trace(&["?a", "!b", "?c", "!d"])));
+        assert_eq!(0.0, trace_similarity(&trace(&["?a"]), &trace(&["!b"])));
+        assert_eq!(1.0, trace_similarity(&trace(&[]), &trace(&[])));
+    }
+
+    #[test]
+    fn check_detect_flaky_traces() {
+        //Given
+        let record = |id: i64, verdict: TestResult, steps: &[&str]| TestRecord::new(id, verdict, trace(steps), None).to_test_case();
+        let passing = vec![record(1, TestResult::passed, &["?a", "!b", "?c", "!d"]), record(2, TestResult::passed, &["?a", "!x", "?c", "!d"])];
+        let failing = vec![record(3, TestResult::failed, &["?a", "!b", "?c", "!d"]), record(4, TestResult::failed, &["?a", "!b", "?c", "!d"]), record(5, TestResult::failed, &["?e"])];
+
+        //When
+        let identical = detect_flaky_traces(&passing, &failing, 1.0, false);
+        let near_identical = detect_flaky_traces(&passing, &failing, 0.75, false);
+
+        //Then
+        assert_eq!(1, identical.len());
+        assert_eq!(vec![Some(3), Some(4)], identical[0].failing_test_ids);
+        assert_eq!(vec![Some(1)], identical[0].passing_test_ids);
+        assert_eq!(vec![3, 4, 1], identical[0].test_ids());
+        assert_eq!(vec![Some(1), Some(2)], near_identical[0].passing_test_ids);
+        assert_eq!(0.75, near_identical[0].similarity);
+    }
+
+    #[test]
+    fn check_add_to_flaky_traces() {
+        //Given
+        let record = |id: i64, verdict: TestResult, steps: &[&str]| TestRecord::new(id, verdict, trace(steps), None).to_test_case();
+        let tests = vec![
+            record(3, TestResult::failed, &["?a", "!b", "?c", "!d"]),
+            record(1, TestResult::passed, &["?a", "!b", "?c", "!d"]),
+            record(4, TestResult::failed, &["?a", "!b", "?c", "!d"]),
+            record(2, TestResult::passed, &["?a", "!x", "?c", "!d"]),
+            record(5, TestResult::failed, &["?e"]),
+        ];
+
+        //When
+        let mut flaky_traces = vec![];
+        for (index, testcase) in tests.iter().enumerate() {
+            let passing = tests[..index + 1].iter().filter(|it| it.verdict == Some(TestResult::passed)).cloned().collect::<Vec<TestCase>>();
+            let failing = tests[..index + 1].iter().filter(|it| it.verdict != Some(TestResult::passed)).cloned().collect::<Vec<TestCase>>();
+            add_to_flaky_traces(&mut flaky_traces, testcase, &passing, &failing, 0.75, false);
+        }
+
+        //Then
+        let passing = tests.iter().filter(|it| it.verdict == Some(TestResult::passed)).cloned().collect::<Vec<TestCase>>();
+        let failing = tests.iter().filter(|it| it.verdict != Some(TestResult::passed)).cloned().collect::<Vec<TestCase>>();
+        assert_eq!(detect_flaky_traces(&passing, &failing, 0.75, false), flaky_traces);
+        assert_eq!(vec![3, 4, 1, 2], flaky_traces[0].test_ids());
+        assert_eq!(0.75, flaky_traces[0].similarity);
+    }
+
+    #[test]
+    fn check_flaky_tests_without_id() {
+        //Given
+        let passing = vec![TestRecord::new(1, TestResult::passed, trace(&["?a"]), None).to_test_case()];
+        let mut failing = TestRecord::new(2, TestResult::failed, trace(&["?a"]), None).to_test_case();
+        failing.id = None;
+
+        //When
+        let flaky_traces = detect_flaky_traces(&passing, &[failing], 1.0, false);
+
+        //Then
+        assert_eq!(vec![1], flaky_traces[0].test_ids());
+        assert_eq!(1, flaky_traces[0].number_of_tests_without_id());
+    }
+
+    #[test]
+    fn check_flaky_policy() {
+        assert_eq!(Some(FlakyPolicy::separate_class), FlakyPolicy::from_name("Separate_Class"));
+        assert_eq!(None, FlakyPolicy::from_name("ignore"));
+    }
+}
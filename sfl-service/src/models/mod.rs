@@ -3,4 +3,15 @@
 pub mod model;
 pub mod test_case;
 pub mod analysis_result;
-pub mod coverage_matrix;
\ No newline at end of file
+pub mod coverage_matrix;
+pub mod similarity;
+pub mod threshold_calibration;
+pub mod explanation;
+pub mod deviation;
+pub mod error_signature;
+pub mod tag_filter;
+pub mod flakiness;
+pub mod model_diff;
+pub mod reproduction_path;
+pub mod prioritisation;
+pub mod diagnosis_session;
@@ -1,4 +1,3 @@
-use get_settings;
 use models::test_case::Step;
 use serde_json::Value;
 use std::collections::HashMap;
@@ -25,6 +24,30 @@ impl TestModel {
     pub fn all_transitions(&self) -> Vec<Transition>{
         self.stss.iter().map(|it| it.transitions.clone()).flatten().collect::<Vec<_>>()
     }
+
+    ///The child behaviour that an STS implements. The id of a child is the name of its STS followed by a number, e.g. `ss_off_1` for the STS `ss_off`.
+    pub fn child_of(&self, sts: &Sts) -> Option<&ChildModel> {
+        self.stss.iter().map(|it| it.children.iter()).flatten().find(|child| self.implements_child(sts, &child.id))
+    }
+
+    ///The STS that implements the child with the given id
+    pub fn sts_of_child(&self, child_id: &str) -> Option<&Sts> {
+        self.stss.iter().find(|sts| self.implements_child(sts, child_id))
+    }
+
+    ///Check if the STS implements the child with the given id. If several STSs have the same name, as the error handlers that are inlined for every child,
+    /// the number of the child is also in the ids of their states (e.g. `_general_errors_6_0` for `general_errors_6`).
+    fn implements_child(&self, sts: &Sts, child_id: &str) -> bool {
+        let prefix = format!("{}_", sts.name);
+        if !child_id.starts_with(&prefix) {
+            return false;
+        }
+        let number = &child_id[prefix.len()..];
+        if number.is_empty() || !number.chars().all(|it| it.is_ascii_digit()) {
+            return false;
+        }
+        self.stss.iter().filter(|it| it.name == sts.name).count() == 1 || sts.states.iter().any(|state| state.id.starts_with(&format!("_{}{}_", prefix, number)))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Derivative)]
@@ -56,6 +79,28 @@ impl Sts {
             trace_properties,
         }
     }
+
+    pub fn start_states(&self) -> &Vec<StartState> {
+        &self.start_states
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn children(&self) -> &Vec<ChildModel> {
+        &self.children
+    }
+
+    ///The transitions back from the children of the STS, whose source is the id of the child
+    pub fn return_transitions(&self) -> &Vec<Transition> {
+        &self.return_transitions
+    }
+
+    ///The state in which the STS returns to the STS of its parent
+    pub fn return_state(&self) -> Option<&String> {
+        self.return_state.as_ref()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Derivative)]
@@ -76,6 +121,18 @@ impl State {
     pub fn to_start_state(&self) -> StartState {
         StartState { id: self.id.clone(), covered: self.attributes.covered.clone() }
     }
+    pub fn label(&self) -> &String {
+        &self.attributes.label
+    }
+
+    ///Whether the state was covered, which is only known for the states in coverage information
+    pub fn covered(&self) -> Option<bool> {
+        self.attributes.covered
+    }
+
+    pub fn set_covered(&mut self, covered: Option<bool>) {
+        self.attributes.covered = covered;
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Derivative)]
@@ -86,6 +143,16 @@ pub struct StartState {
     covered: Option<bool>,
 }
 
+impl StartState {
+    pub fn id(&self) -> &String {
+        &self.id
+    }
+
+    pub fn covered(&self) -> Option<bool> {
+        self.covered
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Derivative)]
 #[derivative(PartialEq)]
 pub struct StateAttribute {
@@ -113,6 +180,27 @@ pub struct ChildModel {
     attributes: ChildModelAttribute,
 }
 
+impl ChildModel {
+    pub fn new(id: String, attributes: ChildModelAttribute) -> ChildModel {
+        ChildModel {
+            id,
+            attributes,
+        }
+    }
+    pub fn id(&self) -> &String {
+        &self.id
+    }
+
+    pub fn label(&self) -> &String {
+        &self.attributes.label
+    }
+
+    ///Whether some of the behaviour of the child was covered, which is only known for the children in coverage information
+    pub fn partially_covered(&self) -> Option<bool> {
+        self.attributes.partially_covered
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Derivative)]
 #[derivative(PartialEq)]
 pub struct ChildModelAttribute {
@@ -124,6 +212,17 @@ pub struct ChildModelAttribute {
     partially_covered: Option<bool>,
 }
 
+impl ChildModelAttribute {
+    pub fn new(label: String, _type: String, hex_id: String, partially_covered: Option<bool>) -> ChildModelAttribute {
+        ChildModelAttribute {
+            label,
+            _type,
+            hex_id,
+            partially_covered,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, Hash, Derivative, Ord, PartialOrd)]
 #[derivative(PartialEq)]
 pub struct Transition {
@@ -149,8 +248,8 @@ impl Transition {
     }
 
     ///Create a string representation of the current transition. Depending on whether data should be included, the label consists of only the first word, or the full label.
-    pub fn to_string(&self) -> String {
-        if get_settings().analysis.use_transition_data {
+    pub fn to_string_with_data(&self, include_data: bool) -> String {
+        if include_data {
             format!("{}--{}-->{}", self.source, self.attributes.label, self.target)
         } else {
             let dataless_label = self.attributes.label.split_whitespace().next();
@@ -160,7 +259,8 @@ impl Transition {
 
     //noinspection RsTypeCheck
     ///Convert the transition in a step, if possible, by finding the matching step based on as many parameters as possible.
-    pub fn to_step(&self, steps: &Vec<Step>) -> Option<Step> {
+    /// Without an exact match, the steps whose label starts the label of the transition are used, which includes the parameters if include parameters is set.
+    pub fn to_step(&self, steps: &Vec<Step>, include_parameters: bool) -> Option<Step> {
         //First check if there is a full match
         for step in steps {
             if self.attributes.label == step.get_full_label_with_params(true) {
@@ -168,7 +268,7 @@ impl Transition {
             }
         }
         //If there is not a full match, check if there are steps that start with the transition's label.
-        let mut alternatives = steps.iter().filter(|it|self.attributes.label.starts_with(&it.get_full_label())).collect::<Vec<&Step>>();
+        let mut alternatives = steps.iter().filter(|it|self.attributes.label.starts_with(&it.get_full_label_with_params(include_parameters))).collect::<Vec<&Step>>();
         alternatives.dedup();
         //Rank the alternatives based on how much the labels match (based on the label parameters)
         let alternatives_counted: HashMap<Step, usize> = alternatives.iter().map(|it|{
@@ -232,4 +332,39 @@ impl TraceProperties {
             passed,
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::from_reader;
+    use std::fs::File;
+    use std::path::PathBuf;
+
+    fn bundled_model() -> TestModel {
+        let mut model_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        model_path.push("resources");
+        model_path.push("model.json");
+        from_reader(File::open(model_path).expect("file not found")).unwrap()
+    }
+
+    #[test]
+    fn check_child_of() {
+        //Given
+        let model = bundled_model();
+
+        //When
+        let child_ids = model.stss.iter().map(|sts| model.child_of(sts).map(|it| it.id().clone())).collect::<Vec<Option<String>>>();
+
+        //Then
+        assert_eq!(None, child_ids[0]);
+        assert_eq!(Some(String::from("general_errors_6")), child_ids[1]);
+        assert_eq!(Some(String::from("ss_off_1")), child_ids[2]);
+        assert_eq!(Some(String::from("as_idle_2")), child_ids[3]);
+        assert_eq!(Some(String::from("general_errors_28")), child_ids[4]);
+        assert!(child_ids[1..].iter().all(|it| it.is_some()));
+        assert_eq!("_general_errors_7_0", model.sts_of_child("general_errors_7").unwrap().start_states()[0].id());
+        assert_eq!("_ss_off_0", model.sts_of_child("ss_off_1").unwrap().start_states()[0].id());
+        assert!(model.sts_of_child("ss_off").is_none());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,252 @@
+use models::model::State;
+use models::model::TestModel;
+use models::model::Transition;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+///An element of the old model that was matched with a different element of the new model
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ElementMapping {
+    pub old: String,
+    pub new: String,
+    ///How similar the elements are, based on their labels and their neighbours, where 1 means identical
+    pub similarity: f32,
+}
+
+///The differences between two versions of a model. States and transitions that got a new id or label, but are similar enough, are reported as renamed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ModelDiff {
+    pub added_states: Vec<String>,
+    pub removed_states: Vec<String>,
+    pub renamed_states: Vec<ElementMapping>,
+    pub added_transitions: Vec<String>,
+    pub removed_transitions: Vec<String>,
+    pub renamed_transitions: Vec<ElementMapping>,
+    ///The id of the new state for every state of the old model that was matched, including the unchanged states
+    #[serde(skip)]
+    state_mapping: HashMap<String, String>,
+    ///The new transition for every transition of the old model that was matched, including the unchanged transitions
+    #[serde(skip)]
+    transition_mapping: Vec<(Transition, Transition)>,
+}
+
+///The result of re-mapping the stored coverage information on a new model
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelRemapResult {
+    pub diff: ModelDiff,
+    ///The number of coverage models that were translated to the new model
+    pub remapped: usize,
+    ///The tests whose coverage refers to states or transitions that no longer exist. Their coverage is no longer used for training.
+    pub stale_test_ids: Vec<Option<i64>>,
+}
+
+impl ModelDiff {
+    ///Compare the old model with the new model. Elements are only matched if their similarity is at least the minimal similarity.
+    pub fn new(old: &TestModel, new: &TestModel, min_similarity: f32) -> ModelDiff {
+        let old_states = all_states(old);
+        let new_states = all_states(new);
+        let old_transitions = old.all_transitions();
+        let new_transitions = new.all_transitions();
+        let mut diff = ModelDiff::default();
+
+        //States with the same id and label are unchanged, the other states are matched on the similarity of their labels and neighbours
+        for old_state in old_states.iter() {
+            if new_states.iter().any(|it| it.id == old_state.id && it.label() == old_state.label()) {
+                diff.state_mapping.insert(old_state.id.clone(), old_state.id.clone());
+            }
+        }
+        let mut candidates = vec![];
+        for old_state in old_states.iter().filter(|it| !diff.state_mapping.contains_key(&it.id)) {
+            for new_state in new_states.iter().filter(|it| !diff.state_mapping.values().any(|id| id == &it.id)) {
+                let similarity = (label_similarity(old_state.label(), new_state.label()) + neighbour_similarity(&neighbours(&old_state.id, &old_transitions), &neighbours(&new_state.id, &new_transitions))) / 2.0;
+                if similarity >= min_similarity {
+                    candidates.push((old_state.id.clone(), new_state.id.clone(), similarity));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+        for (old_id, new_id, similarity) in candidates {
+            if diff.state_mapping.contains_key(&old_id) || diff.state_mapping.values().any(|it| it == &new_id) {
+                continue;
+            }
+            diff.state_mapping.insert(old_id.clone(), new_id.clone());
+            diff.renamed_states.push(ElementMapping { old: old_id, new: new_id, similarity });
+        }
+        let removed_states = old_states.iter().filter(|it| !diff.state_mapping.contains_key(&it.id)).map(|it| it.id.clone()).collect();
+        let added_states = new_states.iter().filter(|it| !diff.state_mapping.values().any(|id| id == &it.id)).map(|it| it.id.clone()).collect();
+        diff.removed_states = removed_states;
+        diff.added_states = added_states;
+
+        //A transition is matched with a transition between the matched states, preferably with the same label
+        let mut used = vec![false; new_transitions.len()];
+        for old_transition in old_transitions.iter() {
+            let mapped_states = (diff.state_mapping.get(&old_transition.source).cloned(), diff.state_mapping.get(&old_transition.target).cloned());
+            let (source, target) = match mapped_states {
+                (Some(source), Some(target)) => (source, target),
+                _ => {
+                    diff.removed_transitions.push(transition_key(old_transition));
+                    continue;
+                }
+            };
+            let best = new_transitions.iter().enumerate()
+                .filter(|&(i, it)| !used[i] && it.source == source && it.target == target)
+                .map(|(i, it)| (i, label_similarity(&old_transition.attributes.label, &it.attributes.label)))
+                .filter(|&(_, similarity)| similarity >= min_similarity)
+                .fold(None, |best: Option<(usize, f32)>, it| match best {
+                    Some(best) if best.1 >= it.1 => Some(best),
+                    _ => Some(it)
+                });
+            match best {
+                Some((index, similarity)) => {
+                    used[index] = true;
+                    let new_transition = &new_transitions[index];
+                    if transition_key(old_transition) != transition_key(new_transition) {
+                        diff.renamed_transitions.push(ElementMapping { old: transition_key(old_transition), new: transition_key(new_transition), similarity });
+                    }
+                    diff.transition_mapping.push((old_transition.clone(), new_transition.clone()));
+                },
+                None => diff.removed_transitions.push(transition_key(old_transition))
+            }
+        }
+        diff.added_transitions = new_transitions.iter().enumerate().filter(|&(i, _)| !used[i]).map(|(_, it)| transition_key(it)).collect();
+        diff
+    }
+
+    ///Check if the models have the same states and transitions
+    pub fn is_empty(&self) -> bool {
+        self.added_states.is_empty() && self.removed_states.is_empty() && self.renamed_states.is_empty()
+            && self.added_transitions.is_empty() && self.removed_transitions.is_empty() && self.renamed_transitions.is_empty()
+    }
+
+    ///The new transition for every matched transition of the old model
+    pub fn transition_mapping(&self) -> &Vec<(Transition, Transition)> {
+        &self.transition_mapping
+    }
+
+    ///Translate the coverage information of a test on the old model to the new model.
+    /// If the test covered a state or transition that has no counterpart in the new model, the coverage can no longer be used and the covered elements are returned as error.
+    pub fn remap(&self, coverage: &TestModel, new_model: &TestModel) -> Result<TestModel, Vec<String>> {
+        let covered_states = all_states(coverage).iter().filter(|it| it.covered().unwrap_or(false)).map(|it| it.id.clone()).collect::<Vec<String>>();
+        let covered_transitions = coverage.all_transitions().into_iter().filter(|it| it.attributes.covered.unwrap_or(false)).collect::<Vec<Transition>>();
+
+        let mut unmapped = covered_states.iter().filter(|it| !self.state_mapping.contains_key(*it)).cloned().collect::<Vec<String>>();
+        unmapped.extend(covered_transitions.iter().filter(|it| !self.transition_mapping.iter().any(|&(ref old, _)| old == *it)).map(|it| transition_key(it)));
+        if !unmapped.is_empty() {
+            return Err(unmapped);
+        }
+
+        let new_covered_states = covered_states.iter().filter_map(|it| self.state_mapping.get(it)).collect::<HashSet<&String>>();
+        let new_covered_transitions = self.transition_mapping.iter().filter(|&&(ref old, _)| covered_transitions.contains(old)).map(|&(_, ref new)| new).collect::<Vec<&Transition>>();
+        let mut remapped = new_model.clone();
+        remapped.test_run_id = coverage.test_run_id;
+        remapped.testcase_id = coverage.testcase_id;
+        for sts in remapped.stss.iter_mut() {
+            for state in sts.states.iter_mut() {
+                let covered = new_covered_states.contains(&state.id);
+                state.set_covered(Some(covered));
+            }
+            for transition in sts.transitions.iter_mut() {
+                transition.attributes.covered = Some(new_covered_transitions.contains(&&*transition));
+            }
+            sts.trace_properties = coverage.stss.iter().filter_map(|it| it.trace_properties.clone()).next();
+        }
+        Ok(remapped)
+    }
+}
+
+///A string that identifies a transition, with the full label
+pub fn transition_key(transition: &Transition) -> String {
+    format!("{}--{}-->{}", transition.source, transition.attributes.label, transition.target)
+}
+
+fn all_states(model: &TestModel) -> Vec<State> {
+    model.stss.iter().map(|it| it.states.clone()).flatten().collect()
+}
+
+///The labels of the incoming and outgoing transitions of a state, without their data
+fn neighbours(state_id: &str, transitions: &[Transition]) -> HashSet<String> {
+    transitions.iter().filter_map(|it| {
+        let gate = it.attributes.label.split_whitespace().next().unwrap_or("").to_string();
+        if it.source == state_id {
+            Some(format!("out {}", gate))
+        } else if it.target == state_id {
+            Some(format!("in {}", gate))
+        } else {
+            None
+        }
+    }).collect()
+}
+
+fn neighbour_similarity(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    jaccard(a, b)
+}
+
+///The similarity of two labels, based on the words they have in common
+pub fn label_similarity(a: &str, b: &str) -> f32 {
+    if a == b {
+        return 1.0;
+    }
+    jaccard(&a.split_whitespace().map(String::from).collect(), &b.split_whitespace().map(String::from).collect())
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f32 / union as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::model::StateAttribute;
+    use models::model::Sts;
+    use models::model::TransitionAttribute;
+    use uuid::Uuid;
+
+    fn state(id: &str, label: &str) -> State {
+        State::new(id.to_string(), StateAttribute::new(label.to_string(), String::from("state"), None))
+    }
+
+    fn transition(source: &str, label: &str, target: &str) -> Transition {
+        Transition::new(source.to_string(), target.to_string(), TransitionAttribute::new(label.to_string(), None, None))
+    }
+
+    fn model(states: Vec<State>, transitions: Vec<Transition>) -> TestModel {
+        TestModel::new(Uuid::nil(), vec![Sts::new(String::from("main"), states, vec![], vec![], transitions, vec![], None, None, None)], None)
+    }
+
+    #[test]
+    fn check_model_diff_and_remap() {
+        //Given
+        let old = model(vec![state("_1", "start"), state("_2", "logged in"), state("_3", "logged out")],
+                        vec![transition("_1", "?login user", "_2"), transition("_2", "?logout", "_3")]);
+        let new = model(vec![state("_1", "start"), state("_10", "logged in"), state("_11", "error")],
+                        vec![transition("_1", "?login user password", "_10"), transition("_1", "?fail", "_11")]);
+        let mut coverage = old.clone();
+        coverage.testcase_id = Some(7);
+        coverage.stss[0].states[1].set_covered(Some(true));
+        coverage.stss[0].transitions[0].attributes.covered = Some(true);
+
+        //When
+        let diff = ModelDiff::new(&old, &new, 0.5);
+        let remapped = diff.remap(&coverage, &new).unwrap();
+
+        //Then
+        assert_eq!(vec![ElementMapping { old: String::from("_2"), new: String::from("_10"), similarity: 0.75 }], diff.renamed_states);
+        assert_eq!(vec![String::from("_3")], diff.removed_states);
+        assert_eq!(vec![String::from("_11")], diff.added_states);
+        assert_eq!(vec![String::from("_1--?login user-->_2")], diff.renamed_transitions.iter().map(|it| it.old.clone()).collect::<Vec<String>>());
+        assert_eq!(vec![String::from("_2--?logout-->_3")], diff.removed_transitions);
+        assert_eq!(vec![String::from("_1--?fail-->_11")], diff.added_transitions);
+        assert_eq!(Some(7), remapped.testcase_id);
+        assert_eq!(Some(true), remapped.stss[0].states[1].covered());
+        assert_eq!(Some(true), remapped.stss[0].transitions[0].attributes.covered);
+        assert_eq!(Some(false), remapped.stss[0].transitions[1].attributes.covered);
+
+        //Coverage of a removed transition can no longer be used
+        coverage.stss[0].transitions[1].attributes.covered = Some(true);
+        assert_eq!(Err(vec![String::from("_2--?logout-->_3")]), diff.remap(&coverage, &new));
+    }
+}
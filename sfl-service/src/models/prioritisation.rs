@@ -0,0 +1,122 @@
+use models::coverage_matrix::CoverageMatrix;
+use models::similarity::ComponentCounts;
+use models::similarity::SimilarityCoefficient;
+use std::collections::HashSet;
+
+///How much re-running a test is expected to reduce the uncertainty about the faulty component
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TestGain {
+    pub test_id: i64,
+    ///The expected reduction of the entropy of the suspiciousness distribution, in bits
+    pub gain: f32,
+    ///The probability that the test fails, which is the suspiciousness of the components it covers
+    pub failure_probability: f32,
+}
+
+///A subset of the tests that covers the same components as all tests together
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SuiteReduction {
+    pub test_ids: Vec<i64>,
+    pub number_of_tests: usize,
+    pub number_of_components: usize,
+}
+
+///Rank the tests of the coverage matrix by their expected diagnostic gain and return the best tests first.
+/// The normalised coefficients of the components are used as the probability that a component is the faulty one.
+/// If a test fails, the fault is among the components it covers, otherwise it is among the other components. The gain is the entropy of the distribution minus the expected entropy after re-running the test.
+pub fn rank_by_diagnostic_gain(coverage_matrix: &CoverageMatrix, coefficient: &SimilarityCoefficient, top: usize) -> Vec<TestGain> {
+    let components = coverage_matrix.components();
+    let coefficients = components.iter().map(|it| ComponentCounts::from_matrix(it, coverage_matrix).map(|counts| counts.similarity(coefficient)).unwrap_or(0.0)).collect::<Vec<f32>>();
+    let total = coefficients.iter().sum::<f32>();
+    if total <= 0.0 {
+        return vec![];
+    }
+    let probabilities = coefficients.iter().map(|it| it / total).collect::<Vec<f32>>();
+    let current_entropy = entropy(&probabilities);
+
+    let mut gains = coverage_matrix.test_ids().iter().enumerate().map(|(row, test_id)| {
+        let covers = components.iter().map(|it| coverage_matrix.data[it][row]).collect::<Vec<bool>>();
+        let failing = probabilities.iter().zip(covers.iter()).map(|(p, covered)| if *covered { *p } else { 0.0 }).collect::<Vec<f32>>();
+        let passing = probabilities.iter().zip(covers.iter()).map(|(p, covered)| if *covered { 0.0 } else { *p }).collect::<Vec<f32>>();
+        let failure_probability = failing.iter().sum::<f32>();
+        let expected_entropy = failure_probability * conditional_entropy(&failing) + (1.0 - failure_probability) * conditional_entropy(&passing);
+        TestGain { test_id: *test_id, gain: current_entropy - expected_entropy, failure_probability }
+    }).collect::<Vec<TestGain>>();
+    gains.sort_by(|a, b| b.gain.partial_cmp(&a.gain).unwrap().then(a.test_id.cmp(&b.test_id)));
+    gains.truncate(top);
+    gains
+}
+
+///Find a small subset of the tests that covers every component that is covered by at least one test.
+/// The test that covers the most uncovered components is added until all components are covered, which gives a subset that is at most a logarithmic factor larger than the minimal subset.
+pub fn reduce_suite(coverage_matrix: &CoverageMatrix) -> SuiteReduction {
+    let components = coverage_matrix.components();
+    let test_ids = coverage_matrix.test_ids();
+    let covered_per_test = (0..test_ids.len()).map(|row| (0..components.len()).filter(|j| coverage_matrix.data[&components[*j]][row]).collect::<HashSet<usize>>()).collect::<Vec<HashSet<usize>>>();
+    let mut uncovered = covered_per_test.iter().flat_map(|it| it.iter().cloned()).collect::<HashSet<usize>>();
+    let number_of_components = uncovered.len();
+    let mut selected = vec![];
+    while !uncovered.is_empty() {
+        //The first test wins a tie, so the result does not depend on the order of the hash sets
+        let (best_row, _) = covered_per_test.iter().enumerate().fold((0, 0), |best, (row, covered)| {
+            let count = covered.intersection(&uncovered).count();
+            if count > best.1 { (row, count) } else { best }
+        });
+        for component in covered_per_test[best_row].iter() {
+            uncovered.remove(component);
+        }
+        selected.push(test_ids[best_row]);
+    }
+    SuiteReduction { test_ids: selected, number_of_tests: test_ids.len(), number_of_components }
+}
+
+///The entropy in bits of a probability distribution
+fn entropy(probabilities: &[f32]) -> f32 {
+    probabilities.iter().filter(|it| **it > 0.0).map(|it| -it * it.log2()).sum()
+}
+
+///The entropy of the part of a distribution, after normalising it
+fn conditional_entropy(probabilities: &[f32]) -> f32 {
+    let total = probabilities.iter().sum::<f32>();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    entropy(&probabilities.iter().map(|it| it / total).collect::<Vec<f32>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn matrix() -> CoverageMatrix {
+        let mut data = HashMap::new();
+        data.insert(String::from("a"), vec![true, true, false, true]);
+        data.insert(String::from("b"), vec![true, false, true, false]);
+        data.insert(String::from("c"), vec![false, false, true, true]);
+        CoverageMatrix::new(vec![true, false, false, true], vec![1, 2, 3, 4], data)
+    }
+
+    #[test]
+    fn check_rank_by_diagnostic_gain() {
+        //When
+        let gains = rank_by_diagnostic_gain(&matrix(), &SimilarityCoefficient::ochiai, 2);
+
+        //Then
+        //Tests 2 and 3 split the most suspicious component a from b and c, which is the most even split
+        let mut test_ids = gains.iter().map(|it| it.test_id).collect::<Vec<i64>>();
+        test_ids.sort();
+        assert_eq!(vec![2, 3], test_ids);
+        assert!(gains[0].gain > 0.9);
+    }
+
+    #[test]
+    fn check_reduce_suite() {
+        //When
+        let reduction = reduce_suite(&matrix());
+
+        //Then
+        assert_eq!(vec![1, 3], reduction.test_ids);
+        assert_eq!(3, reduction.number_of_components);
+    }
+}
@@ -0,0 +1,207 @@
+use models::analysis_result::AnalysisResult;
+use models::model::TestModel;
+use models::model::Transition;
+use models::test_case::Step;
+use models::test_case::TestCase;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+
+///A transition of the path through the model, together with the step of the failing test that took this transition, if the test did
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PathStep {
+    pub transition: Transition,
+    pub step: Option<Step>,
+}
+
+///The shortest path through the model from a start state to the root cause, which can be used as a minimal recipe to reproduce the failure
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReproductionPath {
+    pub start_state: String,
+    pub path: Vec<PathStep>,
+    ///The steps of the failing test up to and including the step that reached the root cause
+    pub test_prefix: Vec<Step>,
+}
+
+impl ReproductionPath {
+    ///Find the path to the root cause of the analysis result. The root cause is the root-cause transition, or else the root-cause state, or else the transition of the first root-cause step.
+    /// The path starts in a start state of the main STS. If the root cause cannot be reached, there is no path.
+    /// The steps are mapped to transitions with their data if use transition data is set.
+    pub fn to_root_cause(model: &TestModel, result: &AnalysisResult, testcase: &TestCase, use_transition_data: bool) -> Option<ReproductionPath> {
+        let root_cause_transition = result.root_cause_transition().cloned().or_else(|| result.root_cause_steps.first().and_then(|it| it.to_transition(model, use_transition_data)));
+        let start_states = main_start_states(model);
+        let (start_state, path) = match (root_cause_transition.as_ref(), result.root_cause_state()) {
+            (Some(transition), _) => {
+                let (start_state, mut path) = shortest_path(model, &start_states, &transition.source)?;
+                path.push(transition.clone());
+                (start_state, path)
+            },
+            (None, Some(state)) => shortest_path(model, &start_states, &state.id)?,
+            (None, None) => return None
+        };
+
+        //Align the steps of the failing test with the path, in order
+        let step_transitions = testcase.steps.iter().map(|it| it.to_transition(model, use_transition_data)).collect::<Vec<Option<Transition>>>();
+        let mut next_step = 0;
+        let path = path.into_iter().map(|transition| {
+            let index = (next_step..testcase.steps.len()).find(|i| step_transitions[*i].as_ref() == Some(&transition));
+            let step = index.map(|index| {
+                next_step = index + 1;
+                testcase.steps[index].clone()
+            });
+            PathStep { transition, step }
+        }).collect::<Vec<PathStep>>();
+
+        let reached_root_cause = match (root_cause_transition.as_ref(), result.root_cause_state()) {
+            (Some(transition), _) => step_transitions.iter().position(|it| it.as_ref() == Some(transition)),
+            (None, Some(state)) => step_transitions.iter().position(|it| it.as_ref().map(|it| it.target == state.id).unwrap_or(false)),
+            (None, None) => None
+        };
+        let test_prefix = match reached_root_cause {
+            Some(index) => testcase.steps[..index + 1].to_vec(),
+            None => vec![]
+        };
+        Some(ReproductionPath { start_state, path, test_prefix })
+    }
+}
+
+///The start states of the main STS, which is the first STS of the model
+pub fn main_start_states(model: &TestModel) -> Vec<String> {
+    model.stss.first().map(|it| it.start_states().iter().map(|it| it.id().clone()).collect::<Vec<String>>()).unwrap_or_default()
+}
+
+///Find the shortest path from one of the start states to the target state with a breadth-first search. The path is empty if the target is a start state.
+fn shortest_path(model: &TestModel, start_states: &[String], target: &str) -> Option<(String, Vec<Transition>)> {
+    shortest_path_avoiding(model, start_states, target, &[])
+}
+
+///Find the shortest path from one of the start states to the target state that does not take any of the transitions to avoid.
+/// A transition to a child (e.g. `ss_off_1`) continues in the start states of the STS of the child, and the return transitions of the child are taken from the return state of that STS.
+pub fn shortest_path_avoiding(model: &TestModel, start_states: &[String], target: &str, avoid: &[Transition]) -> Option<(String, Vec<Transition>)> {
+    let edges = model_edges(model).into_iter().filter(|edge| edge.transition.as_ref().map(|it| !avoid.contains(it)).unwrap_or(true)).collect::<Vec<Edge>>();
+    //For every reached state, the edge that reached it first (None for the start states)
+    let mut reached_by: HashMap<String, Option<usize>> = HashMap::new();
+    let mut queue = VecDeque::new();
+    for start_state in start_states {
+        if !reached_by.contains_key(start_state) {
+            reached_by.insert(start_state.clone(), None);
+            queue.push_back(start_state.clone());
+        }
+    }
+    while let Some(state) = queue.pop_front() {
+        if state == target {
+            let mut path = vec![];
+            let mut current = state;
+            while let Some(Some(index)) = reached_by.get(&current).cloned() {
+                path.extend(edges[index].transition.clone());
+                current = edges[index].source.clone();
+            }
+            path.reverse();
+            return Some((current, path));
+        }
+        for (index, edge) in edges.iter().enumerate() {
+            if edge.source == state && !reached_by.contains_key(&edge.target) {
+                reached_by.insert(edge.target.clone(), Some(index));
+                queue.push_back(edge.target.clone());
+            }
+        }
+    }
+    None
+}
+
+///A step from one state to another: a transition, or the entry of the STS of a child, which does not take a transition
+struct Edge {
+    source: String,
+    transition: Option<Transition>,
+    target: String,
+}
+
+///All edges between the states of the model: the transitions, the entries of the STSs of the children and the return transitions from the return states of these STSs
+fn model_edges(model: &TestModel) -> Vec<Edge> {
+    let mut edges = model.all_transitions().into_iter().map(|it| Edge { source: it.source.clone(), target: it.target.clone(), transition: Some(it) }).collect::<Vec<Edge>>();
+    for sts in model.stss.iter() {
+        for child in sts.children() {
+            let child_sts = match model.sts_of_child(child.id()) {
+                Some(child_sts) => child_sts,
+                None => continue
+            };
+            edges.extend(child_sts.start_states().iter().map(|it| Edge { source: child.id().clone(), transition: None, target: it.id().clone() }));
+            if let Some(return_state) = child_sts.return_state() {
+                edges.extend(sts.return_transitions().iter().filter(|it| &it.source == child.id()).map(|it| Edge { source: return_state.clone(), target: it.target.clone(), transition: Some(it.clone()) }));
+            }
+        }
+    }
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use adapters::TestRecord;
+    use models::model::State;
+    use models::model::StateAttribute;
+    use models::model::Sts;
+    use models::model::TransitionAttribute;
+    use models::test_case::TestResult;
+    use serde_json::from_reader;
+    use std::fs::File;
+    use std::path::PathBuf;
+    use uuid::Uuid;
+
+    fn bundled_model() -> TestModel {
+        let mut model_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        model_path.push("resources");
+        model_path.push("model.json");
+        from_reader(File::open(model_path).expect("file not found")).unwrap()
+    }
+
+    #[test]
+    fn check_path_to_root_cause() {
+        //Given
+        let state = |id: &str| State::new(id.to_string(), StateAttribute::new(id.to_string(), String::from("state"), None));
+        let transition = |source: &str, label: &str, target: &str| Transition::new(source.to_string(), target.to_string(), TransitionAttribute::new(label.to_string(), None, None));
+        let s0 = state("s0");
+        let model = TestModel::new(Uuid::nil(), vec![Sts::new(String::from("main"), vec![s0.clone(), state("s1"), state("s2")], vec![], vec![s0.to_start_state()],
+                                                            vec![transition("s0", "?a", "s1"), transition("s1", "?b", "s0"), transition("s1", "?c", "s2"), transition("s2", "!d", "s0")], vec![], None, None, None)], None);
+        let testcase = TestRecord::new(1, TestResult::failed, vec![String::from("?a"), String::from("?b"), String::from("?a"), String::from("?c"), String::from("!d")], None).to_test_case();
+        let result = AnalysisResult::new(String::from("Transition ?c"), None, Some(transition("s1", "?c", "s2")), None);
+
+        //When
+        let path = ReproductionPath::to_root_cause(&model, &result, &testcase, false).unwrap();
+
+        //Then
+        assert_eq!("s0", path.start_state);
+        assert_eq!(vec![String::from("?a"), String::from("?c")], path.path.iter().map(|it| it.transition.attributes.label.clone()).collect::<Vec<String>>());
+        assert_eq!(vec![Some(String::from("?a")), Some(String::from("?c"))], path.path.iter().map(|it| it.step.as_ref().map(|step| step.get_full_label_with_params(true))).collect::<Vec<Option<String>>>());
+        assert_eq!(4, path.test_prefix.len());
+    }
+
+    #[test]
+    fn check_path_into_child() {
+        //Given
+        let model = bundled_model();
+
+        //When
+        let (start_state, path) = shortest_path(&model, &main_start_states(&model), "_as_idle_0").unwrap();
+
+        //Then
+        assert_eq!("unconnected", start_state);
+        assert!(path.iter().any(|it| it.source == "_13" && it.target == "ss_off_1"));
+        assert_eq!("_ss_off_0", path.iter().find(|it| it.source.starts_with("_ss_off_")).unwrap().source);
+        assert_eq!("as_idle_2", path.last().unwrap().target);
+    }
+
+    #[test]
+    fn check_path_through_return_transition() {
+        //Given
+        let model = bundled_model();
+        let avoid = model.stss[0].transitions.iter().filter(|it| it.target == "_10").cloned().collect::<Vec<Transition>>();
+
+        //When
+        let (_, path) = shortest_path_avoiding(&model, &main_start_states(&model), "_10", &avoid).unwrap();
+
+        //Then
+        assert_eq!(vec!["_1", "_2", "_11", "general_errors_6", "_general_errors_6_1", "_10"], path.iter().map(|it| it.target.as_str()).collect::<Vec<&str>>());
+        //The STS of the child leaves its return state with a transition of its own
+        assert_eq!("_general_errors_6_1", path.last().unwrap().source);
+    }
+}
\ No newline at end of file
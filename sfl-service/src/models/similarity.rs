@@ -0,0 +1,151 @@
+use models::coverage_matrix::CoverageMatrix;
+
+//The share of a test in the separate class that counts as failing. Such a test both passed and failed, so it is as likely to fail as to pass.
+const SEPARATE_CLASS_FAILURE_SHARE: f32 = 0.5;
+
+///The similarity coefficients that can be used to calculate how suspicious a component is
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[allow(non_camel_case_types)]
+pub enum SimilarityCoefficient {
+    ochiai,
+    tarantula,
+    jaccard,
+}
+
+impl SimilarityCoefficient {
+    pub fn from_name(name: &str) -> Option<SimilarityCoefficient> {
+        match name.trim().to_lowercase().as_str() {
+            "ochiai" => Some(SimilarityCoefficient::ochiai),
+            "tarantula" => Some(SimilarityCoefficient::tarantula),
+            "jaccard" => Some(SimilarityCoefficient::jaccard),
+            _ => None
+        }
+    }
+}
+
+///The counts of a single component in the coverage matrix.
+/// a_pq(j) = |{ i | o_ij = p ∧ e_i = q }|, where p and q are either 0 or 1.
+/// o_ij is either 0 or 1, depending on if component j is covered by test run i.
+/// Every test counts with its weight in the coverage matrix. Tests in a separate class (e.g. flaky tests) are counted in a_s1 and a_s0 instead, by whether they cover the component.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ComponentCounts {
+    pub a_11: f32,
+    pub a_10: f32,
+    pub a_01: f32,
+    pub a_00: f32,
+    #[serde(default)]
+    pub a_s1: f32,
+    #[serde(default)]
+    pub a_s0: f32,
+}
+
+impl ComponentCounts {
+    ///Count the combinations of coverage and errors of a component, if the component is in the coverage matrix
+    pub fn from_matrix(component: &str, coverage_matrix: &CoverageMatrix) -> Option<ComponentCounts> {
+        coverage_matrix.data.get(component).map(|col| {
+            let mut counts = ComponentCounts { a_11: 0.0, a_10: 0.0, a_01: 0.0, a_00: 0.0, a_s1: 0.0, a_s0: 0.0 };
+            for (row, (o_ij, e)) in col.iter().zip(coverage_matrix.error_vector.iter()).enumerate() {
+                if coverage_matrix.is_separate_class(row) {
+                    let multiplicity = coverage_matrix.multiplicity(row) as f32;
+                    if *o_ij { counts.a_s1 += multiplicity } else { counts.a_s0 += multiplicity }
+                    continue;
+                }
+                let weight = coverage_matrix.row_weight(row);
+                match (*o_ij, *e) {
+                    (true, true) => counts.a_11 += weight,
+                    (false, true) => counts.a_01 += weight,
+                    (true, false) => counts.a_10 += weight,
+                    (false, false) => counts.a_00 += weight,
+                }
+            }
+            counts
+        })
+    }
+
+    ///Calculate the similarity coefficient of the counts. If the coefficient is undefined (a division by zero), 0 is returned.
+    /// The verdict of a test in the separate class cannot be trusted, so such a test counts partly as failing and partly as passing, whatever its verdict was.
+    /// Unlike dropping the flaky tests, they still count; unlike weighing them, a flaky test that happened to pass still counts as partly failing.
+    pub fn similarity(&self, coefficient: &SimilarityCoefficient) -> f32 {
+        let (a_11, a_01) = (self.a_11 + self.a_s1 * SEPARATE_CLASS_FAILURE_SHARE, self.a_01 + self.a_s0 * SEPARATE_CLASS_FAILURE_SHARE);
+        let (a_10, a_00) = (self.a_10 + self.a_s1 * (1.0 - SEPARATE_CLASS_FAILURE_SHARE), self.a_00 + self.a_s0 * (1.0 - SEPARATE_CLASS_FAILURE_SHARE));
+        let similarity = match coefficient {
+            SimilarityCoefficient::ochiai => a_11 / (((a_11 + a_01) * (a_11 + a_10)).sqrt()),
+            SimilarityCoefficient::tarantula => {
+                let failed = a_11 / (a_11 + a_01);
+                let passed = a_10 / (a_10 + a_00);
+                failed / (failed + if passed.is_nan() { 0.0 } else { passed })
+            },
+            SimilarityCoefficient::jaccard => a_11 / (a_11 + a_01 + a_10),
+        };
+        if similarity.is_nan() { 0.0 } else { similarity }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::collections::HashSet;
+
+    #[test]
+    fn check_coefficients() {
+        let counts = ComponentCounts { a_11: 2.0, a_10: 1.0, a_01: 0.0, a_00: 3.0, a_s1: 0.0, a_s0: 0.0 };
+
+        assert_eq!(0.82, (counts.similarity(&SimilarityCoefficient::ochiai) * 100.0).round() / 100.0);
+        assert_eq!(0.80, (counts.similarity(&SimilarityCoefficient::tarantula) * 100.0).round() / 100.0);
+        assert_eq!(0.67, (counts.similarity(&SimilarityCoefficient::jaccard) * 100.0).round() / 100.0);
+        assert_eq!(0.0, ComponentCounts { a_11: 0.0, a_10: 0.0, a_01: 0.0, a_00: 3.0, a_s1: 0.0, a_s0: 0.0 }.similarity(&SimilarityCoefficient::ochiai));
+    }
+
+    #[test]
+    fn check_weighted_counts() {
+        //Given
+        let mut data = HashMap::new();
+        data.insert(String::from("a"), vec![true, true, true, false]);
+        let mut matrix = CoverageMatrix::new(vec![true, false, false, true], vec![1, 2, 3, 4], data);
+
+        //When
+        matrix.set_test_weight(2, 0.5);
+        matrix.set_separate_class(3);
+
+        //Then
+        assert_eq!(Some(ComponentCounts { a_11: 1.0, a_10: 0.5, a_01: 1.0, a_00: 0.0, a_s1: 1.0, a_s0: 0.0 }), ComponentCounts::from_matrix("a", &matrix));
+    }
+
+    #[test]
+    fn check_separate_class_coefficients() {
+        //Given
+        let counts = ComponentCounts { a_11: 2.0, a_10: 0.0, a_01: 0.0, a_00: 2.0, a_s1: 0.0, a_s0: 0.0 };
+        let flaky_counts = ComponentCounts { a_s1: 2.0, ..counts };
+
+        //Then
+        assert_eq!(1.0, counts.similarity(&SimilarityCoefficient::ochiai));
+        assert_eq!(0.87, (flaky_counts.similarity(&SimilarityCoefficient::ochiai) * 100.0).round() / 100.0);
+        assert_eq!(0.75, (flaky_counts.similarity(&SimilarityCoefficient::tarantula) * 100.0).round() / 100.0);
+        assert_eq!(0.75, flaky_counts.similarity(&SimilarityCoefficient::jaccard));
+        assert_eq!(0.75, ComponentCounts { a_s0: 2.0, ..flaky_counts }.similarity(&SimilarityCoefficient::ochiai));
+    }
+
+    #[test]
+    fn check_flaky_test_policies() {
+        //Given
+        let mut data = HashMap::new();
+        data.insert(String::from("a"), vec![true, true, true, false]);
+        let matrix = CoverageMatrix::new(vec![true, false, true, false], vec![1, 2, 3, 4], data);
+        let flaky_test_ids = [3, 4].iter().cloned().collect::<HashSet<i64>>();
+
+        //When
+        let dropped = matrix.without_test_ids(&flaky_test_ids);
+        let mut weighted = matrix.clone();
+        flaky_test_ids.iter().for_each(|test_id| weighted.set_test_weight(*test_id, 0.5));
+        let mut separated = matrix.clone();
+        flaky_test_ids.iter().for_each(|test_id| separated.set_separate_class(*test_id));
+        let ochiai = |matrix: &CoverageMatrix| (ComponentCounts::from_matrix("a", matrix).unwrap().similarity(&SimilarityCoefficient::ochiai) * 100.0).round() / 100.0;
+
+        //Then
+        assert_eq!(0.82, ochiai(&matrix));
+        assert_eq!(0.71, ochiai(&dropped));
+        assert_eq!(0.77, ochiai(&weighted));
+        assert_eq!(0.61, ochiai(&separated));
+    }
+}
@@ -0,0 +1,82 @@
+use models::test_case::TestCase;
+
+///Select test cases by their tags. A test case matches if it has at least one of the included tags (or nothing is included) and none of the excluded tags.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, FromForm)]
+#[serde(default)]
+pub struct TagFilter {
+    ///Comma separated tags of which a test case must have at least one, e.g. `smoke,regression`. Empty means all test cases.
+    pub include: Option<String>,
+    ///Comma separated tags that a test case may not have, e.g. `flaky`
+    pub exclude: Option<String>,
+}
+
+impl TagFilter {
+    pub fn new(include: &str, exclude: &str) -> TagFilter {
+        TagFilter {
+            include: if include.trim().is_empty() { None } else { Some(include.to_string()) },
+            exclude: if exclude.trim().is_empty() { None } else { Some(exclude.to_string()) },
+        }
+    }
+
+    pub fn included_tags(&self) -> Vec<String> {
+        split_tags(&self.include)
+    }
+
+    pub fn excluded_tags(&self) -> Vec<String> {
+        split_tags(&self.exclude)
+    }
+
+    ///True if the filter selects all test cases
+    pub fn is_empty(&self) -> bool {
+        self.included_tags().is_empty() && self.excluded_tags().is_empty()
+    }
+
+    pub fn matches(&self, testcase: &TestCase) -> bool {
+        self.matches_tags(testcase.tags())
+    }
+
+    pub fn matches_tags(&self, tags: &[String]) -> bool {
+        let included_tags = self.included_tags();
+        (included_tags.is_empty() || included_tags.iter().any(|it| tags.contains(it))) && !self.excluded_tags().iter().any(|it| tags.contains(it))
+    }
+
+    ///A name for the subset of test cases that is selected by this filter, which is the same for equivalent filters. E.g. `+regression+smoke-flaky`.
+    pub fn key(&self) -> String {
+        let mut included_tags = self.included_tags();
+        included_tags.sort();
+        included_tags.dedup();
+        let mut excluded_tags = self.excluded_tags();
+        excluded_tags.sort();
+        excluded_tags.dedup();
+        included_tags.iter().map(|it| format!("+{}", it)).chain(excluded_tags.iter().map(|it| format!("-{}", it))).collect::<Vec<String>>().join("")
+    }
+}
+
+fn split_tags(tags: &Option<String>) -> Vec<String> {
+    tags.as_ref().map(|it| it.split(',').map(|tag| tag.trim().to_string()).filter(|tag| !tag.is_empty()).collect::<Vec<String>>()).unwrap_or(vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_matches_tags() {
+        let filter = TagFilter::new("smoke, regression", "flaky");
+
+        assert!(filter.matches_tags(&vec![String::from("smoke")]));
+        assert!(filter.matches_tags(&vec![String::from("nightly"), String::from("regression")]));
+        assert!(!filter.matches_tags(&vec![String::from("smoke"), String::from("flaky")]));
+        assert!(!filter.matches_tags(&vec![String::from("exploratory")]));
+        assert!(!filter.matches_tags(&vec![]));
+        assert!(TagFilter::new("", "flaky").matches_tags(&vec![]));
+        assert!(TagFilter::default().is_empty());
+    }
+
+    #[test]
+    fn check_key() {
+        assert_eq!("+regression+smoke-flaky", TagFilter::new("smoke,regression", " flaky ").key());
+        assert_eq!(TagFilter::new("regression,smoke", "flaky").key(), TagFilter::new("smoke,regression", "flaky").key());
+        assert_eq!("", TagFilter::default().key());
+    }
+}
@@ -7,12 +7,10 @@ use std::hash::Hash;
 use std::hash::Hasher;
 use std::path::PathBuf;
 use std::fs::File;
-use storage_service::get_storage_service;
 use std::collections::HashMap;
 use uuid::Uuid;
 use serde_json::value::Value;
 use std::cmp::Ordering;
-use get_settings;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TestRun {
@@ -44,9 +42,9 @@ impl TestCase {
         }
     }
 
-    ///Returns all steps as their string representation
-    pub fn get_steps(&self) -> Vec<String> {
-        self.steps.iter().map(|it| it.get_full_label()).collect()
+    ///Returns all steps as their string representation, optionally with parameters
+    pub fn get_steps(&self, include_parameters: bool) -> Vec<String> {
+        self.steps.iter().map(|it| it.get_full_label_with_params(include_parameters)).collect()
     }
 
     #[deprecated( note="please use `analysis_service#get_pairs_with_length` instead")]
@@ -74,10 +72,24 @@ impl TestCase {
         step_combos
     }
 
+    pub fn expected_labels(&self) -> Option<&Vec<ExpectedLabel>> {
+        self.expected_labels.as_ref()
+    }
+
+    ///The tags of the test case, e.g. `smoke` or `flaky`. A test case without tags gives an empty slice.
+    pub fn tags(&self) -> &[String] {
+        self.tags.as_ref().map(|it| it.as_slice()).unwrap_or(&[])
+    }
+
+    ///The index of the step where the test failed. This is the step with the number of `last_step`, or the last step if there is no such step.
+    pub fn failure_index(&self) -> Option<usize> {
+        self.steps.iter().position(|it| it.step_number == self.last_step).or(if self.steps.is_empty() { None } else { Some(self.steps.len() - 1) })
+    }
+
     //noinspection RsTypeCheck
-    ///Find the matching coverage model by comparing test case ids
-    pub fn to_coverage_model(&self) -> Option<TestModel> {
-        get_storage_service().coverage_information.iter().find(|it| it.testcase_id == self.id).map(|it| it.to_owned())
+    ///Find the matching coverage model in the given coverage information by comparing test case ids
+    pub fn to_coverage_model(&self, coverage_information: &[TestModel]) -> Option<TestModel> {
+        coverage_information.iter().find(|it| it.testcase_id == self.id).map(|it| it.to_owned())
     }
 }
 
@@ -107,8 +119,8 @@ impl Step {
         }
     }
 
-    ///Map the current step to a transition in the given test model
-    pub fn to_transition(&self, model: &TestModel) -> Option<Transition> {
+    ///Map the current step to a transition in the given test model. Without an exact match, the transitions that start with the label of the step are used, which includes the parameters if include parameters is set.
+    pub fn to_transition(&self, model: &TestModel, include_parameters: bool) -> Option<Transition> {
         //Check if there is an exact match
         for sts in &model.stss {
             for transition in &sts.transitions {
@@ -118,7 +130,7 @@ impl Step {
             }
         }
         //If there is not a full match, check if there are steps that start with the transition's label.
-        let mut alternatives = model.stss.iter().map(|it|&it.transitions).flatten().filter(|it|it.attributes.covered.unwrap_or(false) && it.attributes.label.starts_with(&self.get_full_label())).collect::<Vec<&Transition>>();
+        let mut alternatives = model.stss.iter().map(|it|&it.transitions).flatten().filter(|it|it.attributes.covered.unwrap_or(false) && it.attributes.label.starts_with(&self.get_full_label_with_params(include_parameters))).collect::<Vec<&Transition>>();
         alternatives.dedup();
         //Rank the alternatives based on how much the labels match (based on the label parameters)
         let alternatives_counted: HashMap<Transition, usize> = alternatives.iter().map(|it|{
@@ -151,13 +163,7 @@ impl Step {
 
     ///Return the full label of the step, optionally with parameters.
     pub fn get_full_label_with_params(&self, include_parameters: bool) -> String {
-        let mut full_label = String::new();
-        if &self.label.direction == "in" || &self.label.direction == "stimulus" {
-            full_label.push('?')
-        } else if self.label.direction == "out" || self.label.direction == "response" {
-            full_label.push('!')
-        }
-        full_label.push_str(self.label.name.as_ref());
+        let mut full_label = self.label.get_full_label();
 
         if include_parameters {
             if let Some(ref label_params) = self.label_parameters {
@@ -174,11 +180,6 @@ impl Step {
         }
         return full_label;
     }
-
-    ///Convienience method that automatically includes the data in the label, if this is set in the settings
-    pub fn get_full_label(&self) -> String {
-        self.get_full_label_with_params(get_settings().analysis.use_transition_data)
-    }
 }
 
 impl Eq for Step {}
@@ -223,6 +224,18 @@ impl Label {
             channel,
         }
     }
+
+    ///Return the name of the label, prefixed with a ? for stimuli and a ! for responses
+    pub fn get_full_label(&self) -> String {
+        let mut full_label = String::new();
+        if &self.direction == "in" || &self.direction == "stimulus" {
+            full_label.push('?')
+        } else if self.direction == "out" || self.direction == "response" {
+            full_label.push('!')
+        }
+        full_label.push_str(self.name.as_ref());
+        full_label
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -238,6 +251,10 @@ impl ExpectedLabel {
 //            deadline,
         }
     }
+
+    pub fn label(&self) -> &Label {
+        &self.label
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -270,7 +287,7 @@ pub mod tests {
             Some(Map::new()),
         );
         //When
-        let simple_step_full_label = simple_step.get_full_label();
+        let simple_step_full_label = simple_step.get_full_label_with_params(false);
         //Then
         assert_eq!(simple_step_full_label, "!C251_SIGNED_ON");
 
@@ -312,10 +329,10 @@ pub mod tests {
         let testcase: TestCase = from_reader(testcase_file).unwrap();
 
         let original_steps = testcase.steps;
-        let transitions = original_steps.iter().map(|step| step.to_transition(&model)).collect::<Vec<_>>();
-        let new_steps = transitions.iter().filter_map(|transition| transition.as_ref().unwrap().to_step(&original_steps)).collect::<Vec<_>>();
+        let transitions = original_steps.iter().map(|step| step.to_transition(&model, false)).collect::<Vec<_>>();
+        let new_steps = transitions.iter().filter_map(|transition| transition.as_ref().unwrap().to_step(&original_steps, false)).collect::<Vec<_>>();
         assert_eq!(0, transitions.iter().filter(|it| it.is_none()).count());
-        assert_eq!(original_steps.iter().map(|it| it.get_full_label()).collect::<Vec<_>>(), new_steps.iter().map(|it| it.get_full_label()).collect::<Vec<_>>());
+        assert_eq!(original_steps.iter().map(|it| it.get_full_label_with_params(false)).collect::<Vec<_>>(), new_steps.iter().map(|it| it.get_full_label_with_params(false)).collect::<Vec<_>>());
     }
 
     #[test]
@@ -329,12 +346,12 @@ pub mod tests {
 //        let step_d_out = Step::new(Label::new(String::from("d"), String::from("out"), String::from("default")), DateTime::from(SystemTime::now()), None, 3, None, None, None, None);
 
         let testcase = TestCase::new(Some(1), Some(TestResult::passed), None, vec![step_init.clone(), step_a_in.clone(), step_a_out.clone(), step_c_out.clone()], 6, None, None);
-        assert_eq!(vec![vec![step_init.get_full_label()], vec![step_a_in.get_full_label()], vec![step_a_out.get_full_label()], vec![step_c_out.get_full_label()]], testcase.get_step_combinations(1).iter().map(|it| it.iter().map(|t| t.get_full_label()).collect::<Vec<String>>()).collect::<Vec<Vec<String>>>());
-        assert_eq!(vec![vec![step_init.get_full_label(), step_a_in.get_full_label()], vec![step_a_in.get_full_label(), step_a_out.get_full_label()], vec![step_a_out.get_full_label(), step_c_out.get_full_label()]], testcase.get_step_combinations(2).iter().map(|it| it.iter().map(|t| t.get_full_label()).collect::<Vec<String>>()).collect::<Vec<Vec<String>>>());
-        assert_eq!(vec![vec![step_init.get_full_label(), step_a_in.get_full_label(), step_a_out.get_full_label()], vec![step_a_in.get_full_label(), step_a_out.get_full_label(), step_c_out.get_full_label()]], testcase.get_step_combinations(3).iter().map(|it| it.iter().map(|t| t.get_full_label()).collect::<Vec<String>>()).collect::<Vec<Vec<String>>>());
-        assert_eq!(vec![vec![step_init.get_full_label(), step_a_in.get_full_label(), step_a_out.get_full_label(), step_c_out.get_full_label()]], testcase.get_step_combinations(4).iter().map(|it| it.iter().map(|t| t.get_full_label()).collect::<Vec<String>>()).collect::<Vec<Vec<String>>>());
-        assert_eq!(vec![Vec::<String>::new()], testcase.get_step_combinations(5).iter().map(|it| it.iter().map(|t| t.get_full_label()).collect::<Vec<String>>()).collect::<Vec<Vec<String>>>());
+        assert_eq!(vec![vec![step_init.get_full_label_with_params(false)], vec![step_a_in.get_full_label_with_params(false)], vec![step_a_out.get_full_label_with_params(false)], vec![step_c_out.get_full_label_with_params(false)]], testcase.get_step_combinations(1).iter().map(|it| it.iter().map(|t| t.get_full_label_with_params(false)).collect::<Vec<String>>()).collect::<Vec<Vec<String>>>());
+        assert_eq!(vec![vec![step_init.get_full_label_with_params(false), step_a_in.get_full_label_with_params(false)], vec![step_a_in.get_full_label_with_params(false), step_a_out.get_full_label_with_params(false)], vec![step_a_out.get_full_label_with_params(false), step_c_out.get_full_label_with_params(false)]], testcase.get_step_combinations(2).iter().map(|it| it.iter().map(|t| t.get_full_label_with_params(false)).collect::<Vec<String>>()).collect::<Vec<Vec<String>>>());
+        assert_eq!(vec![vec![step_init.get_full_label_with_params(false), step_a_in.get_full_label_with_params(false), step_a_out.get_full_label_with_params(false)], vec![step_a_in.get_full_label_with_params(false), step_a_out.get_full_label_with_params(false), step_c_out.get_full_label_with_params(false)]], testcase.get_step_combinations(3).iter().map(|it| it.iter().map(|t| t.get_full_label_with_params(false)).collect::<Vec<String>>()).collect::<Vec<Vec<String>>>());
+        assert_eq!(vec![vec![step_init.get_full_label_with_params(false), step_a_in.get_full_label_with_params(false), step_a_out.get_full_label_with_params(false), step_c_out.get_full_label_with_params(false)]], testcase.get_step_combinations(4).iter().map(|it| it.iter().map(|t| t.get_full_label_with_params(false)).collect::<Vec<String>>()).collect::<Vec<Vec<String>>>());
+        assert_eq!(vec![Vec::<String>::new()], testcase.get_step_combinations(5).iter().map(|it| it.iter().map(|t| t.get_full_label_with_params(false)).collect::<Vec<String>>()).collect::<Vec<Vec<String>>>());
 
-        assert_eq!(vec![(step_init.get_full_label(), step_a_in.get_full_label()), (step_a_in.get_full_label(), step_a_out.get_full_label()), (step_a_out.get_full_label(), step_c_out.get_full_label())], testcase.get_step_tuples().iter().map(|it| (it.0.get_full_label(), it.1.get_full_label())).collect::<Vec<(String, String)>>());
+        assert_eq!(vec![(step_init.get_full_label_with_params(false), step_a_in.get_full_label_with_params(false)), (step_a_in.get_full_label_with_params(false), step_a_out.get_full_label_with_params(false)), (step_a_out.get_full_label_with_params(false), step_c_out.get_full_label_with_params(false))], testcase.get_step_tuples().iter().map(|it| (it.0.get_full_label_with_params(false), it.1.get_full_label_with_params(false))).collect::<Vec<(String, String)>>());
     }
 }
\ No newline at end of file
@@ -0,0 +1,140 @@
+use models::coverage_matrix::CoverageMatrix;
+use models::similarity::ComponentCounts;
+use models::similarity::SimilarityCoefficient;
+
+///The ways the similarity threshold can be calibrated at the end of the training
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum CalibrationMethod {
+    ///Keep the configured threshold
+    none,
+    ///Put the threshold in the middle of the largest gap between the coefficients of the components covered by failing tests
+    gap,
+    ///Put the threshold so that only the given fraction of the components covered by failing tests is above it
+    top_k,
+    ///Choose the threshold for which the most failing tests, analysed with a matrix without that test, point to one or two components
+    leave_one_out,
+}
+
+impl CalibrationMethod {
+    pub fn from_name(name: &str) -> Option<CalibrationMethod> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "none" => Some(CalibrationMethod::none),
+            "gap" => Some(CalibrationMethod::gap),
+            "top_k" => Some(CalibrationMethod::top_k),
+            "leave_one_out" => Some(CalibrationMethod::leave_one_out),
+            _ => None
+        }
+    }
+}
+
+///The outcome of a calibration of the similarity threshold
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ThresholdCalibration {
+    pub method: CalibrationMethod,
+    pub similarity_threshold: f32,
+    pub previous_similarity_threshold: f32,
+    pub number_of_coefficients: usize,
+    pub justification: String,
+}
+
+impl ThresholdCalibration {
+    ///Calibrate the similarity threshold on the distribution of coefficients of the components that are covered by the failing tests in the coverage matrix.
+    /// If there are no failing tests, or the method is `none`, no threshold is calibrated.
+    pub fn calibrate(coverage_matrix: &CoverageMatrix, coefficient: &SimilarityCoefficient, method: &CalibrationMethod, fraction: f32, previous_similarity_threshold: f32) -> Option<ThresholdCalibration> {
+        let failing_rows = coverage_matrix.error_vector.iter().enumerate().filter(|&(_, e)| *e).map(|(i, _)| i).collect::<Vec<usize>>();
+        if failing_rows.is_empty() {
+            return None;
+        }
+        let mut coefficients = coverage_matrix.data.iter()
+            .filter(|&(_, col)| failing_rows.iter().any(|row| col[*row]))
+            .filter_map(|(component, _)| ComponentCounts::from_matrix(component, coverage_matrix))
+            .map(|counts| counts.similarity(coefficient))
+            .collect::<Vec<f32>>();
+        coefficients.sort_by(|a, b| b.partial_cmp(a).unwrap());
+        let number_of_coefficients = coefficients.len();
+
+        let (similarity_threshold, justification) = match method {
+            CalibrationMethod::none => return None,
+            CalibrationMethod::gap => {
+                let mut largest_gap: Option<(f32, f32)> = None;
+                for pair in coefficients.windows(2) {
+                    if pair[0] - pair[1] > largest_gap.map(|(high, low)| high - low).unwrap_or(0.0) {
+                        largest_gap = Some((pair[0], pair[1]));
+                    }
+                }
+                match largest_gap {
+                    Some((high, low)) => ((high + low) / 2.0, format!("The largest gap between the {} coefficients of components covered by failing tests is between {} and {}", number_of_coefficients, low, high)),
+                    None => return None
+                }
+            },
+            CalibrationMethod::top_k => {
+                let k = ((number_of_coefficients as f32 * fraction).ceil() as usize).max(1).min(number_of_coefficients);
+                let high = coefficients[k - 1];
+                let low = coefficients.get(k).cloned().unwrap_or(0.0);
+                ((high + low) / 2.0, format!("{} of the {} coefficients of components covered by failing tests are above the threshold (fraction {})", k, number_of_coefficients, fraction))
+            },
+            CalibrationMethod::leave_one_out => {
+                let mut candidates = coefficients.windows(2).map(|pair| (pair[0] + pair[1]) / 2.0).collect::<Vec<f32>>();
+                candidates.push(0.0);
+                let left_out_coefficients = failing_rows.iter().map(|row| left_out_coefficients(coverage_matrix, *row, coefficient)).collect::<Vec<Vec<f32>>>();
+                let mut best: Option<(f32, usize)> = None;
+                for candidate in candidates {
+                    let located = left_out_coefficients.iter().filter(|it| {
+                        let above = it.iter().filter(|c| **c > candidate).count();
+                        above >= 1 && above <= 2
+                    }).count();
+                    if best.map(|(_, best_located)| located > best_located).unwrap_or(true) {
+                        best = Some((candidate, located));
+                    }
+                }
+                let (threshold, located) = best.unwrap();
+                (threshold, format!("Leaving out each of the {} failing tests, {} of them point to one or two components at this threshold", failing_rows.len(), located))
+            },
+        };
+        Some(ThresholdCalibration { method: *method, similarity_threshold, previous_similarity_threshold, number_of_coefficients, justification })
+    }
+}
+
+///The coefficients of the components covered by a test, as if that test was not part of the coverage matrix
+fn left_out_coefficients(coverage_matrix: &CoverageMatrix, row: usize, coefficient: &SimilarityCoefficient) -> Vec<f32> {
+    let errored = coverage_matrix.error_vector[row];
+    coverage_matrix.data.iter().filter(|&(_, col)| col[row]).filter_map(|(component, _)| {
+        ComponentCounts::from_matrix(component, coverage_matrix).map(|mut counts| {
+            let weight = coverage_matrix.row_weight(row);
+            if errored { counts.a_11 -= weight } else { counts.a_10 -= weight }
+            counts.similarity(coefficient)
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn check_calibration() {
+        //Given
+        let mut data = HashMap::new();
+        data.insert(String::from("faulty"), vec![true, true, true, false]);
+        data.insert(String::from("shared"), vec![true, true, true, true]);
+        data.insert(String::from("passing"), vec![false, false, true, true]);
+        let matrix = CoverageMatrix::new(vec![true, true, false, false], vec![1, 2, 3, 4], data);
+
+        //When
+        let none = ThresholdCalibration::calibrate(&matrix, &SimilarityCoefficient::ochiai, &CalibrationMethod::none, 0.1, 0.75);
+        let gap = ThresholdCalibration::calibrate(&matrix, &SimilarityCoefficient::ochiai, &CalibrationMethod::gap, 0.1, 0.75).unwrap();
+        let top_k = ThresholdCalibration::calibrate(&matrix, &SimilarityCoefficient::ochiai, &CalibrationMethod::top_k, 0.5, 0.75).unwrap();
+        let leave_one_out = ThresholdCalibration::calibrate(&matrix, &SimilarityCoefficient::ochiai, &CalibrationMethod::leave_one_out, 0.1, 0.75).unwrap();
+
+        //Then
+        assert_eq!(None, none);
+        //faulty is 0.82 and shared is 0.71
+        assert_eq!(2, gap.number_of_coefficients);
+        assert!(gap.similarity_threshold > 0.71 && gap.similarity_threshold < 0.82);
+        assert!(top_k.similarity_threshold > 0.71 && top_k.similarity_threshold < 0.82);
+        assert!(leave_one_out.similarity_threshold < 0.82);
+        assert_eq!(0.75, leave_one_out.previous_similarity_threshold);
+    }
+}
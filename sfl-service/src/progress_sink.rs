@@ -0,0 +1,49 @@
+use reqwest::header::ContentType;
+use reqwest::{Client, Url};
+
+///Where an instance sends the progress of the training to
+pub trait ProgressSink {
+    fn send_progress(&self, endpoint: &str, progress: f32);
+}
+
+///Post the progress to the configured progress endpoint, so Atana can show how far the training is
+pub struct HttpProgressSink {}
+
+impl ProgressSink for HttpProgressSink {
+    fn send_progress(&self, endpoint: &str, progress: f32) {
+        print!("Send progress of {} to Atana. ", progress);
+        let client = Client::new();
+        let res = client.post(Url::parse(endpoint).unwrap())
+            .body(progress.to_string())
+            .header(ContentType::json())
+            .send();
+        match res {
+            Ok(response) => println!("Success: {:?}", response),
+            Err(error) => println!("An error occurred: {:?}", error)
+        };
+    }
+}
+
+///Ignore the progress, e.g. in tests or for a training that nobody follows
+pub struct NoProgressSink {}
+
+impl ProgressSink for NoProgressSink {
+    fn send_progress(&self, _endpoint: &str, _progress: f32) {}
+}
+
+const HTTP_PROGRESS_SINK: HttpProgressSink = HttpProgressSink {};
+const NO_PROGRESS_SINK: NoProgressSink = NoProgressSink {};
+
+///Return the sink that ignores the progress
+pub fn no_progress_sink() -> &'static (ProgressSink + Sync) {
+    &NO_PROGRESS_SINK
+}
+
+///Return the default sink, which posts the progress to Atana. Tests do not post anything.
+pub fn default_progress_sink() -> &'static (ProgressSink + Sync) {
+    if cfg!(test) {
+        &NO_PROGRESS_SINK
+    } else {
+        &HTTP_PROGRESS_SINK
+    }
+}
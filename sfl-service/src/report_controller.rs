@@ -0,0 +1,88 @@
+use rocket::http::ContentType;
+use rocket::response::content::Content;
+use rocket::Rocket;
+use app_context::AppContext;
+use app_context::Context;
+use visualisation::model_to_dot;
+use visualisation::Heatmap;
+use report_service::render_report;
+use report_service::ReportData;
+use rocket::response::content::Html;
+use rocket::response::status::BadRequest;
+use rocket_contrib::Json;
+use coverage_summary_service::coverage_summary;
+use coverage_summary_service::CoverageSummary;
+use uuid::Uuid;
+
+pub fn mount(rocket_instance: Rocket) -> Rocket {
+    rocket_instance.mount("/data", routes![
+        //This is a list of all controller functions that are mounted
+        model_dot,
+        test_model_dot,
+        report,
+        coverage_summary_all,
+        coverage_summary_of_run,
+        ])
+}
+
+///A static HTML report of the diagnosis of all stored failing tests, which can be saved and shared as a single file
+#[get("/report")]
+fn report(context: Context) -> Html<String> {
+    Html(render_report(&ReportData::collect(&context)))
+}
+
+#[derive(FromForm)]
+struct CoverageSummaryQuery {
+    test_run_id: String,
+}
+
+///The coverage of the model by all stored coverage information
+#[get("/coverage/summary", rank = 2)]
+fn coverage_summary_all(context: Context) -> Option<Json<CoverageSummary>> {
+    coverage_summary(&context.storage(), None).map(Json)
+}
+
+///The coverage of the model by the stored coverage information of a single test run
+#[get("/coverage/summary?<query>")]
+fn coverage_summary_of_run(context: Context, query: CoverageSummaryQuery) -> Result<Option<Json<CoverageSummary>>, BadRequest<String>> {
+    let test_run_id = Uuid::parse_str(&query.test_run_id).map_err(|e| BadRequest(Some(format!("Invalid test_run_id: {}", e))))?;
+    let summary = coverage_summary(&context.storage(), Some(test_run_id));
+    Ok(summary.map(Json))
+}
+
+#[derive(FromForm)]
+struct DotQuery {
+    test_id: i64,
+}
+
+///Render the model as a Graphviz DOT graph, coloured by the coefficients of the trained coverage matrix
+#[get("/model.dot", rank = 2)]
+fn model_dot(context: Context) -> Option<Content<String>> {
+    render_dot(&context, None)
+}
+
+///Render the model as a Graphviz DOT graph for a single stored failing test, with only the transitions it covered coloured and its root cause highlighted
+#[get("/model.dot?<query>")]
+fn test_model_dot(context: Context, query: DotQuery) -> Option<Content<String>> {
+    render_dot(&context, Some(query.test_id))
+}
+
+fn render_dot(context: &AppContext, test_id: Option<i64>) -> Option<Content<String>> {
+    let settings = context.settings().clone();
+    let service = context.analysis_service();
+    let storage_service = context.storage();
+    let test_case = test_id.and_then(|test_id| storage_service.failing.iter().find(|it| it.id == Some(test_id)).cloned());
+    let coverage_model = test_case.as_ref().and_then(|it| it.to_coverage_model(&storage_service.coverage_information));
+    let model = match storage_service.model {
+        Some(ref model) => model,
+        None => return None
+    };
+    let analysis_result = match (test_case, storage_service.coverage_matrix.is_some()) {
+        (Some(test_case), true) => service.analyse_with(context, test_case, &storage_service.model, &storage_service.coverage_matrix, coverage_model.clone(), &storage_service.threshold_calibration),
+        _ => None
+    };
+    let mut heatmap = Heatmap::from_settings(storage_service.coverage_matrix.as_ref(), &settings);
+    heatmap.coverage_model = coverage_model.as_ref();
+    heatmap.analysis_result = analysis_result.as_ref();
+    Some(Content(ContentType::new("text", "vnd.graphviz"), model_to_dot(model, &heatmap)))
+}
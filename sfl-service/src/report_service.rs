@@ -0,0 +1,236 @@
+use analysis_service::configured_similarity_coefficient;
+use app_context::AppContext;
+use batch_analysis_service;
+use batch_analysis_service::BatchAnalysisResult;
+use analysis_service::effective_similarity_threshold;
+use models::coverage_matrix::CoverageMatrix;
+use models::model::TestModel;
+use models::similarity::ComponentCounts;
+use models::similarity::SimilarityCoefficient;
+use models::tag_filter::TagFilter;
+use models::test_case::TestCase;
+use serde_json;
+use std::fs::File;
+use std::io::Write;
+use visualisation::escape_xml;
+use visualisation::heat_rgb;
+use visualisation::model_to_svg;
+use visualisation::read_file;
+use visualisation::Heatmap;
+
+///Everything that is shown in the report, collected from the storage
+pub struct ReportData {
+    pub number_of_passing_tests: usize,
+    pub number_of_failing_tests: usize,
+    pub number_of_flaky_traces: usize,
+    pub similarity_coefficient: SimilarityCoefficient,
+    pub similarity_threshold: f32,
+    ///All components of the coverage matrix with their coefficient and counts, from most to least suspicious
+    pub ranking: Vec<(String, f32, ComponentCounts)>,
+    pub batch_result: BatchAnalysisResult,
+    pub model_svg: Option<String>,
+    ///Whether the labels of the root cause steps include their data
+    pub use_transition_data: bool,
+}
+
+impl ReportData {
+    ///Collect the report data from the storage and analyse all stored failing tests
+    pub fn collect(context: &AppContext) -> ReportData {
+        let settings = context.settings().clone();
+        let similarity_coefficient = configured_similarity_coefficient(&settings);
+        let (similarity_threshold, failing) = {
+            let storage_service = context.storage();
+            (effective_similarity_threshold(&settings, &storage_service.threshold_calibration), storage_service.failing.clone())
+        };
+        let batch_result = batch_analysis_service::analyse_batch(context, failing, &TagFilter::default(), 1);
+        let storage_service = context.storage();
+        let model_svg = storage_service.model.as_ref().map(|model| model_to_svg(model, &Heatmap::from_settings(storage_service.coverage_matrix.as_ref(), &settings)));
+        ReportData {
+            number_of_passing_tests: storage_service.passing.len(),
+            number_of_failing_tests: storage_service.failing.len(),
+            number_of_flaky_traces: storage_service.flaky_traces.len(),
+            similarity_coefficient,
+            similarity_threshold,
+            ranking: storage_service.coverage_matrix.as_ref().map(|it| rank_components(it, &similarity_coefficient)).unwrap_or(vec![]),
+            batch_result,
+            model_svg,
+            use_transition_data: settings.analysis.use_transition_data,
+        }
+    }
+}
+
+///Rank all components of the coverage matrix from most to least suspicious
+fn rank_components(coverage_matrix: &CoverageMatrix, coefficient: &SimilarityCoefficient) -> Vec<(String, f32, ComponentCounts)> {
+    let mut ranking = coverage_matrix.components().into_iter()
+        .filter_map(|component| ComponentCounts::from_matrix(&component, coverage_matrix).map(|counts| (component, counts.similarity(coefficient), counts)))
+        .collect::<Vec<(String, f32, ComponentCounts)>>();
+    ranking.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+    ranking
+}
+
+///Create a single HTML page with the summary of the run, the ranking of the components, the analysis result of every failing test, the failure groups and the model with its heatmap.
+/// The page has no external dependencies, so it can be sent around as a file.
+pub fn render_report(data: &ReportData) -> String {
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Diagnosis report</title>\n<style>\n");
+    html.push_str("body { font-family: sans-serif; margin: 2em; color: #222; }\n");
+    html.push_str("table { border-collapse: collapse; margin-bottom: 2em; }\n");
+    html.push_str("th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }\n");
+    html.push_str("th.sortable { cursor: pointer; background: #f0f0f0; }\n");
+    html.push_str(".swatch { display: inline-block; width: 1em; height: 1em; vertical-align: middle; margin-right: 4px; }\n");
+    html.push_str("</style>\n</head>\n<body>\n<h1>Diagnosis report</h1>\n");
+
+    html.push_str("<h2>Summary</h2>\n<table>\n");
+    for &(name, ref value) in [
+        ("Passing tests", data.number_of_passing_tests.to_string()),
+        ("Failing tests", data.number_of_failing_tests.to_string()),
+        ("Flaky traces", data.number_of_flaky_traces.to_string()),
+        ("Components", data.ranking.len().to_string()),
+        ("Failure groups", data.batch_result.groups.len().to_string()),
+        ("Similarity coefficient", format!("{:?}", data.similarity_coefficient)),
+        ("Similarity threshold", format!("{:.2}", data.similarity_threshold)),
+    ].iter() {
+        html.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", name, escape_xml(value)));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Component ranking</h2>\n<p>Click a column header to sort the table.</p>\n<table id=\"ranking\">\n<tr>");
+    for (index, header) in ["Component", "Coefficient", "Failing, covered", "Passing, covered", "Failing, not covered", "Passing, not covered"].iter().enumerate() {
+        html.push_str(&format!("<th class=\"sortable\" onclick=\"sortTable({})\">{}</th>", index, header));
+    }
+    html.push_str("</tr>\n");
+    for &(ref component, coefficient, ref counts) in data.ranking.iter() {
+        html.push_str(&format!("<tr><td>{}</td><td><span class=\"swatch\" style=\"background: {}\"></span>{:.3}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                               escape_xml(component), heat_rgb(coefficient), coefficient, counts.a_11, counts.a_10, counts.a_01, counts.a_00));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Failing tests</h2>\n");
+    for test_result in data.batch_result.results.iter() {
+        let test_id = test_result.test_id.map(|it| it.to_string()).unwrap_or(String::from("unknown"));
+        html.push_str(&format!("<h3>Test {}</h3>\n", escape_xml(&test_id)));
+        let result = match test_result.result {
+            Some(ref result) => result,
+            None => {
+                html.push_str("<p>Could not be analysed.</p>\n");
+                continue;
+            }
+        };
+        html.push_str(&format!("<p>Group: <b>{}</b></p>\n", escape_xml(result.group_name())));
+        if let Some(ref deviation) = result.deviation {
+            html.push_str(&format!("<p>Deviation: {} (coefficient {:.3})</p>\n", escape_xml(&deviation.component()), deviation.coefficient));
+        }
+        if !result.root_cause_steps.is_empty() {
+            html.push_str(&format!("<p>Root cause steps: {}</p>\n", escape_xml(&result.root_cause_steps.iter().map(|it| it.get_full_label_with_params(data.use_transition_data)).collect::<Vec<String>>().join(", "))));
+        }
+        if let Some(ref explanation) = result.explanation {
+            html.push_str("<table>\n<tr><th>Component</th><th>Coefficient</th><th>Crossed the threshold</th></tr>\n");
+            for component in explanation.components.iter() {
+                html.push_str(&format!("<tr><td>{}</td><td>{:.3}</td><td>{}</td></tr>\n", escape_xml(&component.component), component.coefficient, if component.crossed_threshold { "yes" } else { "no" }));
+            }
+            html.push_str("</table>\n");
+            if let Some(ref state_extraction) = explanation.state_extraction {
+                html.push_str(&format!("<p>{}</p>\n", escape_xml(state_extraction)));
+            }
+        }
+    }
+
+    html.push_str("<h2>Failure groups</h2>\n<table>\n<tr><th>Group</th><th>Tests</th><th>Root cause</th></tr>\n");
+    for group in data.batch_result.groups.iter() {
+        let root_cause = group.root_cause_state.as_ref().map(|it| format!("State {}", it.id))
+            .or(group.root_cause_transition.as_ref().map(|it| format!("Transition {}", it.attributes.label)))
+            .unwrap_or(String::from("-"));
+        let test_ids = group.test_ids.iter().map(|it| it.map(|id| id.to_string()).unwrap_or(String::from("unknown"))).collect::<Vec<String>>().join(", ");
+        html.push_str(&format!("<tr><td>{}</td><td>{} ({})</td><td>{}</td></tr>\n", escape_xml(&group.group_name), group.count, escape_xml(&test_ids), escape_xml(&root_cause)));
+    }
+    html.push_str("</table>\n");
+
+    if let Some(ref model_svg) = data.model_svg {
+        html.push_str("<h2>Model</h2>\n<p>Transitions and states are coloured from green to red by their coefficient. Hover over them for their labels.</p>\n");
+        html.push_str(model_svg);
+    }
+
+    //Sort the ranking table on a column, numerically if the column contains numbers
+    html.push_str("<script>\nfunction sortTable(column) {\n");
+    html.push_str("    var table = document.getElementById('ranking');\n");
+    html.push_str("    var rows = Array.prototype.slice.call(table.rows, 1);\n");
+    html.push_str("    var ascending = table.getAttribute('data-sorted') !== column + '-asc';\n");
+    html.push_str("    rows.sort(function(a, b) {\n");
+    html.push_str("        var x = a.cells[column].textContent, y = b.cells[column].textContent;\n");
+    html.push_str("        var order = isNaN(parseFloat(x)) ? x.localeCompare(y) : parseFloat(x) - parseFloat(y);\n");
+    html.push_str("        return ascending ? order : -order;\n");
+    html.push_str("    });\n");
+    html.push_str("    rows.forEach(function(row) { table.appendChild(row); });\n");
+    html.push_str("    table.setAttribute('data-sorted', column + (ascending ? '-asc' : '-desc'));\n");
+    html.push_str("}\n</script>\n</body>\n</html>\n");
+    html
+}
+
+///Create the report from files, as used on the command line: `[--model <model.json>] --passing <tests.json> --failing <tests.json> [--coverages <coverage.json>] [--output <report.html>]`.
+/// Without a model, e.g. for imported tests, the steps are used for the analysis and the report has no model.
+/// The files are stored as if they were submitted, the coverage matrix is trained and the report is written to the output file or otherwise printed.
+pub fn report_from_arguments(context: &AppContext, arguments: &[String]) -> Result<(), String> {
+    let mut output = None;
+    let mut options = arguments.iter();
+    while let Some(option) = options.next() {
+        let path = options.next().ok_or(format!("Missing the file after {}", option))?;
+        let contents = if option == "--output" { String::new() } else { read_file(path)? };
+        let mut storage_service = context.storage();
+        match option.as_str() {
+            "--model" => storage_service.set_model(Some(serde_json::from_str::<TestModel>(&contents).map_err(|e| format!("Could not parse the model {}: {}", path, e))?)),
+            "--passing" => storage_service.set_passing_tests(serde_json::from_str::<Vec<TestCase>>(&contents).map_err(|e| format!("Could not parse the passing tests {}: {}", path, e))?),
+            "--failing" => storage_service.set_failing_tests(serde_json::from_str::<Vec<TestCase>>(&contents).map_err(|e| format!("Could not parse the failing tests {}: {}", path, e))?),
+            "--coverages" => storage_service.set_coverage_informations(serde_json::from_str::<Vec<TestModel>>(&contents).map_err(|e| format!("Could not parse the coverage information {}: {}", path, e))?),
+            "--output" => output = Some(path.clone()),
+            _ => return Err(format!("Unknown option {}", option))
+        }
+    }
+    if context.storage().failing.is_empty() {
+        return Err(String::from("Usage: --report [--model <model.json>] --passing <tests.json> --failing <tests.json> [--coverages <coverage.json>] [--output <report.html>]"));
+    }
+    if context.storage().model.is_none() {
+        context.settings().analysis.use_steps_instead_of_transitions_for_analysis = true;
+    }
+    context.analysis_service().train(context);
+    let report = render_report(&ReportData::collect(context));
+    match output {
+        Some(path) => File::create(&path).and_then(|mut file| file.write_all(report.as_bytes())).map_err(|e| format!("Could not write the report to {}: {}", path, e)),
+        None => {
+            print!("{}", report);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use batch_analysis_service::TestAnalysisResult;
+    use models::analysis_result::AnalysisResult;
+
+    #[test]
+    fn check_render_report() {
+        //Given
+        let data = ReportData {
+            number_of_passing_tests: 3,
+            number_of_failing_tests: 1,
+            number_of_flaky_traces: 0,
+            similarity_coefficient: SimilarityCoefficient::ochiai,
+            similarity_threshold: 0.75,
+            ranking: vec![(String::from("s0--?a-->s1"), 0.82, ComponentCounts { a_11: 1.0, a_10: 0.5, a_01: 0.0, a_00: 2.0, a_s1: 0.0, a_s0: 0.0 })],
+            batch_result: BatchAnalysisResult::new(vec![TestAnalysisResult { test_id: Some(4), result: Some(AnalysisResult::new(String::from("Transition <?a>"), None, None, None)) }]),
+            model_svg: None,
+            use_transition_data: false,
+        };
+
+        //When
+        let html = render_report(&data);
+
+        //Then
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<tr><th>Passing tests</th><td>3</td></tr>"));
+        assert!(html.contains("<td>s0--?a--&gt;s1</td>"));
+        assert!(html.contains("<h3>Test 4</h3>\n<p>Group: <b>Transition &lt;?a&gt;</b></p>"));
+        assert!(html.contains("<tr><td>Transition &lt;?a&gt;</td><td>1 (4)</td><td>-</td></tr>"));
+        assert!(!html.contains("<h2>Model</h2>"));
+    }
+}
@@ -0,0 +1,252 @@
+use analysis_service::configured_similarity_coefficient;
+use app_context::AppContext;
+use batch_analysis_service::BatchAnalysisResult;
+use batch_analysis_service::GroupSummary;
+use batch_analysis_service::TestAnalysisResult;
+use models::coverage_matrix::CoverageMatrix;
+use models::model::TestModel;
+use models::similarity::ComponentCounts;
+use models::similarity::SimilarityCoefficient;
+use models::test_case::TestCase;
+use models::test_case::TestResult;
+use models::threshold_calibration::ThresholdCalibration;
+use std::collections::HashSet;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use uuid::Uuid;
+
+///Compare a candidate test run with a baseline test run, e.g. the runs before and after a release of the SUT
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RunComparisonRequest {
+    pub baseline: Uuid,
+    pub candidate: Uuid,
+    ///How much the coefficient of a component has to increase to call it newly suspicious
+    #[serde(default = "default_min_increase")]
+    pub min_increase: f32,
+}
+
+fn default_min_increase() -> f32 {
+    0.1
+}
+
+///A component that is more suspicious in the candidate run than in the baseline run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ComponentChange {
+    pub component: String,
+    ///The coefficient in the baseline run, or None if the component was not covered in the baseline run
+    pub baseline_coefficient: Option<f32>,
+    pub candidate_coefficient: f32,
+    pub increase: f32,
+}
+
+///What got worse in the candidate run compared to the baseline run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RunComparison {
+    pub baseline: Uuid,
+    pub candidate: Uuid,
+    pub number_of_baseline_tests: usize,
+    pub number_of_candidate_tests: usize,
+    ///The components whose coefficient increased the most, from the largest to the smallest increase
+    pub newly_suspicious: Vec<ComponentChange>,
+    ///The failure groups of the candidate run that were not in the baseline run
+    pub appeared_groups: Vec<GroupSummary>,
+    ///The failure groups of the baseline run that are not in the candidate run
+    pub disappeared_groups: Vec<GroupSummary>,
+    ///The transitions (or steps) that were covered in the baseline run, but not in the candidate run
+    pub lost_coverage: Vec<String>,
+}
+
+///A test run as it is stored: its tests, the coverage information of these tests and the coverage matrix that is trained on only these tests
+struct StoredRun {
+    test_cases: Vec<TestCase>,
+    coverage_information: Vec<TestModel>,
+    coverage_matrix: CoverageMatrix,
+    threshold_calibration: Option<ThresholdCalibration>,
+}
+
+impl StoredRun {
+    ///Load the tests of the run and train on them in the scratch context, so the run is not mixed up with other runs that use the same test ids
+    fn load(context: &AppContext, test_run_id: &Uuid) -> Result<StoredRun, String> {
+        let (test_cases, coverage_information) = {
+            let storage_service = context.storage();
+            let coverage_information = storage_service.coverage_information.iter().filter(|it| &it.test_run_id == test_run_id).cloned().collect::<Vec<TestModel>>();
+            (storage_service.test_cases_of_run(test_run_id), coverage_information)
+        };
+        if test_cases.is_empty() {
+            return Err(format!("No tests found for test run {}", test_run_id));
+        }
+
+        let scratch = context.scratch();
+        let scratch_context = scratch.as_ref().unwrap();
+        {
+            let mut storage_service = scratch_context.storage();
+            storage_service.set_passing_tests(test_cases.iter().filter(|it| it.verdict == Some(TestResult::passed)).cloned().collect());
+            storage_service.set_failing_tests(test_cases.iter().filter(|it| it.verdict != Some(TestResult::passed)).cloned().collect());
+            storage_service.set_coverage_informations(coverage_information.clone());
+        }
+        if panic::catch_unwind(AssertUnwindSafe(|| scratch_context.analysis_service().train(scratch_context))).is_err() {
+            return Err(format!("The training on test run {} failed", test_run_id));
+        }
+        let storage_service = scratch_context.storage();
+        match storage_service.coverage_matrix {
+            Some(ref coverage_matrix) => Ok(StoredRun {
+                test_cases,
+                coverage_information,
+                coverage_matrix: coverage_matrix.clone(),
+                threshold_calibration: storage_service.threshold_calibration.clone(),
+            }),
+            None => Err(format!("The training on test run {} did not result in a coverage matrix", test_run_id))
+        }
+    }
+
+    ///Analyse the failing tests of the run with only the spectra of the run itself
+    fn analyse(&self, context: &AppContext) -> BatchAnalysisResult {
+        let service = context.analysis_service();
+        let model = context.storage().model.clone();
+        let coverage_matrix = Some(self.coverage_matrix.clone());
+        BatchAnalysisResult::new(self.test_cases.iter().filter(|it| it.verdict != Some(TestResult::passed)).map(|test_case| {
+            let coverage_model = self.coverage_information.iter().find(|it| it.testcase_id == test_case.id).cloned();
+            TestAnalysisResult { test_id: test_case.id, result: service.analyse_with(context, test_case.clone(), &model, &coverage_matrix, coverage_model, &self.threshold_calibration) }
+        }).collect())
+    }
+
+    ///The covered transitions, or the covered steps if there is no coverage information for the run
+    fn covered_components(&self, use_transition_data: bool) -> HashSet<String> {
+        if self.coverage_information.is_empty() {
+            self.test_cases.iter().map(|it| it.get_steps(use_transition_data)).flatten().collect()
+        } else {
+            self.coverage_information.iter().map(|it| it.all_transitions()).flatten().filter(|it| it.attributes.covered.unwrap_or(false)).map(|it| it.to_string_with_data(use_transition_data)).collect()
+        }
+    }
+}
+
+///Compare the candidate run with the baseline run. Both runs are analysed with a coverage matrix that is trained on only their own tests.
+pub fn compare(context: &AppContext, request: &RunComparisonRequest) -> Result<RunComparison, String> {
+    let baseline = StoredRun::load(context, &request.baseline)?;
+    let candidate = StoredRun::load(context, &request.candidate)?;
+    let (coefficient, use_transition_data) = {
+        let settings = context.settings();
+        (configured_similarity_coefficient(&settings), settings.analysis.use_transition_data)
+    };
+
+    let (appeared_groups, disappeared_groups) = group_changes(&baseline.analyse(context), &candidate.analyse(context));
+    let candidate_coverage = candidate.covered_components(use_transition_data);
+    let mut lost_coverage = baseline.covered_components(use_transition_data).into_iter().filter(|it| !candidate_coverage.contains(it)).collect::<Vec<String>>();
+    lost_coverage.sort();
+
+    Ok(RunComparison {
+        baseline: request.baseline,
+        candidate: request.candidate,
+        number_of_baseline_tests: baseline.test_cases.len(),
+        number_of_candidate_tests: candidate.test_cases.len(),
+        newly_suspicious: newly_suspicious(&baseline.coverage_matrix, &candidate.coverage_matrix, &coefficient, request.min_increase),
+        appeared_groups,
+        disappeared_groups,
+        lost_coverage,
+    })
+}
+
+///Find the components whose coefficient increased at least the minimal increase. A component that was not covered in the baseline counts as 0 in the baseline.
+fn newly_suspicious(baseline: &CoverageMatrix, candidate: &CoverageMatrix, coefficient: &SimilarityCoefficient, min_increase: f32) -> Vec<ComponentChange> {
+    let mut changes = candidate.components().into_iter().filter_map(|component| {
+        let candidate_coefficient = ComponentCounts::from_matrix(&component, candidate).map(|it| it.similarity(coefficient)).unwrap_or(0.0);
+        let baseline_counts = ComponentCounts::from_matrix(&component, baseline).filter(|it| it.a_11 + it.a_10 > 0.0);
+        let baseline_coefficient = baseline_counts.map(|it| it.similarity(coefficient));
+        let increase = candidate_coefficient - baseline_coefficient.unwrap_or(0.0);
+        if increase >= min_increase {
+            Some(ComponentChange { component, baseline_coefficient, candidate_coefficient, increase })
+        } else {
+            None
+        }
+    }).collect::<Vec<ComponentChange>>();
+    changes.sort_by(|a, b| b.increase.partial_cmp(&a.increase).unwrap().then(a.component.cmp(&b.component)));
+    changes
+}
+
+///The failure groups that appeared in the candidate and the groups that disappeared from the baseline
+fn group_changes(baseline: &BatchAnalysisResult, candidate: &BatchAnalysisResult) -> (Vec<GroupSummary>, Vec<GroupSummary>) {
+    let appeared = candidate.groups.iter().filter(|group| !baseline.groups.iter().any(|it| it.group_name == group.group_name)).cloned().collect();
+    let disappeared = baseline.groups.iter().filter(|group| !candidate.groups.iter().any(|it| it.group_name == group.group_name)).cloned().collect();
+    (appeared, disappeared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use adapters::TestRecord;
+    use models::analysis_result::AnalysisResult;
+    use std::collections::HashMap;
+
+    #[test]
+    fn check_newly_suspicious() {
+        //Given
+        let mut baseline_data = HashMap::new();
+        baseline_data.insert(String::from("a"), vec![true, true]);
+        baseline_data.insert(String::from("b"), vec![true, false]);
+        let baseline = CoverageMatrix::new(vec![true, false], vec![1, 2], baseline_data);
+        let mut candidate_data = HashMap::new();
+        candidate_data.insert(String::from("a"), vec![true, false]);
+        candidate_data.insert(String::from("b"), vec![true, false]);
+        candidate_data.insert(String::from("c"), vec![true, false]);
+        let candidate = CoverageMatrix::new(vec![true, false], vec![3, 4], candidate_data);
+
+        //When
+        let changes = newly_suspicious(&baseline, &candidate, &SimilarityCoefficient::ochiai, 0.1);
+
+        //Then
+        assert_eq!(vec![String::from("c"), String::from("a")], changes.iter().map(|it| it.component.clone()).collect::<Vec<String>>());
+        assert_eq!(None, changes[0].baseline_coefficient);
+        assert_eq!(1.0, changes[0].increase);
+        assert_eq!(1.0, changes[1].candidate_coefficient);
+    }
+
+    #[test]
+    fn check_group_changes() {
+        //Given
+        let result = |test_id: i64, group_name: &str| TestAnalysisResult { test_id: Some(test_id), result: Some(AnalysisResult::new(group_name.to_string(), None, None, None)) };
+        let baseline = BatchAnalysisResult::new(vec![result(1, "State s1"), result(2, "Transition ?a")]);
+        let candidate = BatchAnalysisResult::new(vec![result(3, "State s1"), result(4, "State s2")]);
+
+        //When
+        let (appeared, disappeared) = group_changes(&baseline, &candidate);
+
+        //Then
+        assert_eq!(vec![String::from("State s2")], appeared.iter().map(|it| it.group_name.clone()).collect::<Vec<String>>());
+        assert_eq!(vec![String::from("Transition ?a")], disappeared.iter().map(|it| it.group_name.clone()).collect::<Vec<String>>());
+    }
+
+    #[test]
+    fn check_runs_with_the_same_test_ids() {
+        //Given
+        let context = AppContext::from_settings_file();
+        {
+            let mut settings = context.settings();
+            settings.analysis.use_steps_instead_of_transitions_for_analysis = true;
+            settings.analysis.number_of_pairs_to_include_for_order = 0;
+            settings.analysis.export_path = String::new();
+        }
+        let (baseline, candidate) = (Uuid::new_v4(), Uuid::new_v4());
+        {
+            let mut storage_service = context.storage();
+            storage_service.add_test_run(baseline, vec![
+                TestRecord::new(1, TestResult::passed, vec![String::from("a"), String::from("b")], None).to_test_case(),
+                TestRecord::new(2, TestResult::failed, vec![String::from("a"), String::from("c")], None).to_test_case(),
+            ]);
+            storage_service.add_test_run(candidate, vec![
+                TestRecord::new(1, TestResult::passed, vec![String::from("a"), String::from("b")], None).to_test_case(),
+                TestRecord::new(2, TestResult::failed, vec![String::from("a"), String::from("d")], None).to_test_case(),
+            ]);
+        }
+
+        //When
+        let comparison = compare(&context, &RunComparisonRequest { baseline, candidate, min_increase: 0.1 }).unwrap();
+
+        //Then
+        assert_eq!(2, comparison.number_of_baseline_tests);
+        assert_eq!(2, comparison.number_of_candidate_tests);
+        assert_eq!(vec![String::from("c")], comparison.lost_coverage);
+        assert_eq!(vec![String::from("d")], comparison.newly_suspicious.iter().map(|it| it.component.clone()).collect::<Vec<String>>());
+        let storage_service = context.storage();
+        assert_eq!(4, storage_service.passing.len() + storage_service.failing.len());
+    }
+}
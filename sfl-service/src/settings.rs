@@ -5,13 +5,13 @@ extern crate serde_json;
 
 use std::env;
 use config::{ConfigError, Config, File, Environment};
-use get_settings;
 use serde_json::Map;
 use serde_json::Value;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Analysis {
     pub similarity_threshold: f32,
+    pub similarity_coefficient: String,
     pub progress_endpoint: String,
     pub use_thread_for_progress: bool,
     pub use_thread_for_training: bool,
@@ -19,28 +19,56 @@ pub struct Analysis {
     pub use_steps_instead_of_transitions_for_analysis: bool,
     pub use_transition_data: bool,
     pub return_highest_similarity_if_nothing_found: bool,
+    pub incremental_training: bool,
+    pub export_path: String,
+    pub export_format: String,
+    pub threshold_calibration: String,
+    pub threshold_calibration_fraction: f32,
+    pub include_explanation: bool,
+    pub include_reproduction_path: bool,
+    pub number_of_threads_for_batch_analysis: i32,
+    pub failure_proximity_weight: f32,
+    pub analyse_per_error_signature: bool,
+    pub training_include_tags: String,
+    pub training_exclude_tags: String,
+    pub flaky_test_policy: String,
+    pub flaky_test_weight: f32,
+    pub flaky_trace_similarity: f32,
+    pub deduplicate_test_rows: bool,
+    pub count_duplicate_test_rows: bool,
+    pub model_diff_similarity: f32,
 }
 
 impl Analysis {
     ///Parse the map of keys and values and store it in the settings
-    pub fn parse_from_map(settings_map: Map<String, Value>){
+    pub fn parse_from_map(&mut self, settings_map: Map<String, Value>){
         println!("Parse map!");
         if settings_map.contains_key("similarity_threshold") {
             let similarity_threshold = Analysis::parse_f32(&settings_map["similarity_threshold"]);
             match similarity_threshold {
                 Ok(similarity_threshold) => {
                     println!("Set similarity to {}", similarity_threshold);
-                    get_settings().analysis.similarity_threshold = similarity_threshold;
+                    self.similarity_threshold = similarity_threshold;
                 },
                 Err(er) => println!("{}", er)
             }
         }
+        if settings_map.contains_key("similarity_coefficient") {
+            let similarity_coefficient = &settings_map["similarity_coefficient"];
+            match similarity_coefficient {
+                &Value::String(ref similarity_coefficient) => {
+                    println!("Set similarity_coefficient to {}", similarity_coefficient);
+                    self.similarity_coefficient = similarity_coefficient.to_string();
+                },
+                _ => println!("similarity_coefficient is not a string: {}", similarity_coefficient)
+            }
+        }
         if settings_map.contains_key("progress_endpoint") {
             let progress_endpoint = &settings_map["progress_endpoint"];
             match progress_endpoint {
                 &Value::String(ref progress_endpoint) => {
                     println!("Set progress_endpoint to {}", progress_endpoint);
-                    get_settings().analysis.progress_endpoint=progress_endpoint.to_string();
+                    self.progress_endpoint=progress_endpoint.to_string();
                 },
                 _ => println!("progress_endpoint is not a string: {}", progress_endpoint)
             }
@@ -50,7 +78,7 @@ impl Analysis {
             match use_thread_for_training {
                 Ok(ref use_thread_for_training) => {
                     println!("Set use_thread_for_training to {}", use_thread_for_training);
-                    get_settings().analysis.use_thread_for_training = *use_thread_for_training;
+                    self.use_thread_for_training = *use_thread_for_training;
                 },
                 Err(e) => println!("use_thread_for_training is not a boolean: {}", e)
             }
@@ -60,7 +88,7 @@ impl Analysis {
             match use_steps_instead_of_transitions_for_analysis {
                 Ok(ref use_steps_instead_of_transitions_for_analysis) => {
                     println!("Set use_steps_instead_of_transitions_for_analysis to {}", use_steps_instead_of_transitions_for_analysis);
-                    get_settings().analysis.use_steps_instead_of_transitions_for_analysis = *use_steps_instead_of_transitions_for_analysis;
+                    self.use_steps_instead_of_transitions_for_analysis = *use_steps_instead_of_transitions_for_analysis;
                 },
                 Err(e) => println!("use_steps_instead_of_transitions_for_analysis is not a boolean: {}", e)
             }
@@ -70,7 +98,7 @@ impl Analysis {
             match use_transition_data {
                 Ok(ref use_transition_data) => {
                     println!("Set use_transition_data to {}", use_transition_data);
-                    get_settings().analysis.use_transition_data = *use_transition_data;
+                    self.use_transition_data = *use_transition_data;
                 },
                 Err(e) => println!("use_transition_data is not a boolean: {}", e)
             }
@@ -80,7 +108,7 @@ impl Analysis {
             match number_of_pairs_to_include_for_order {
                 Ok(ref number_of_pairs_to_include_for_order) => {
                     println!("Set number_of_pairs_to_include_for_order to {}", number_of_pairs_to_include_for_order);
-                    get_settings().analysis.number_of_pairs_to_include_for_order = *number_of_pairs_to_include_for_order;
+                    self.number_of_pairs_to_include_for_order = *number_of_pairs_to_include_for_order;
                 },
                 Err(e) => println!("number_of_pairs_to_include_for_order is not an integer: {}", e)
             }
@@ -90,11 +118,191 @@ impl Analysis {
             match return_highest_similarity_if_nothing_found {
                 Ok(ref return_highest_similarity_if_nothing_found) => {
                     println!("Set return_highest_similarity_if_nothing_found to {}", return_highest_similarity_if_nothing_found);
-                    get_settings().analysis.return_highest_similarity_if_nothing_found = *return_highest_similarity_if_nothing_found;
+                    self.return_highest_similarity_if_nothing_found = *return_highest_similarity_if_nothing_found;
                 },
                 Err(e) => println!("return_highest_similarity_if_nothing_found is not a boolean: {}", e)
             }
         }
+        if settings_map.contains_key("export_path") {
+            let export_path = &settings_map["export_path"];
+            match export_path {
+                &Value::String(ref export_path) => {
+                    println!("Set export_path to {}", export_path);
+                    self.export_path = export_path.to_string();
+                },
+                _ => println!("export_path is not a string: {}", export_path)
+            }
+        }
+        if settings_map.contains_key("export_format") {
+            let export_format = &settings_map["export_format"];
+            match export_format {
+                &Value::String(ref export_format) => {
+                    println!("Set export_format to {}", export_format);
+                    self.export_format = export_format.to_string();
+                },
+                _ => println!("export_format is not a string: {}", export_format)
+            }
+        }
+        if settings_map.contains_key("incremental_training") {
+            let incremental_training = Analysis::parse_bool(&settings_map["incremental_training"]);
+            match incremental_training {
+                Ok(ref incremental_training) => {
+                    println!("Set incremental_training to {}", incremental_training);
+                    self.incremental_training = *incremental_training;
+                },
+                Err(e) => println!("incremental_training is not a boolean: {}", e)
+            }
+        }
+        if settings_map.contains_key("threshold_calibration") {
+            let threshold_calibration = &settings_map["threshold_calibration"];
+            match threshold_calibration {
+                &Value::String(ref threshold_calibration) => {
+                    println!("Set threshold_calibration to {}", threshold_calibration);
+                    self.threshold_calibration = threshold_calibration.to_string();
+                },
+                _ => println!("threshold_calibration is not a string: {}", threshold_calibration)
+            }
+        }
+        if settings_map.contains_key("threshold_calibration_fraction") {
+            let threshold_calibration_fraction = Analysis::parse_f32(&settings_map["threshold_calibration_fraction"]);
+            match threshold_calibration_fraction {
+                Ok(threshold_calibration_fraction) => {
+                    println!("Set threshold_calibration_fraction to {}", threshold_calibration_fraction);
+                    self.threshold_calibration_fraction = threshold_calibration_fraction;
+                },
+                Err(er) => println!("{}", er)
+            }
+        }
+        if settings_map.contains_key("include_explanation") {
+            let include_explanation = Analysis::parse_bool(&settings_map["include_explanation"]);
+            match include_explanation {
+                Ok(ref include_explanation) => {
+                    println!("Set include_explanation to {}", include_explanation);
+                    self.include_explanation = *include_explanation;
+                },
+                Err(e) => println!("include_explanation is not a boolean: {}", e)
+            }
+        }
+        if settings_map.contains_key("include_reproduction_path") {
+            let include_reproduction_path = Analysis::parse_bool(&settings_map["include_reproduction_path"]);
+            match include_reproduction_path {
+                Ok(ref include_reproduction_path) => {
+                    println!("Set include_reproduction_path to {}", include_reproduction_path);
+                    self.include_reproduction_path = *include_reproduction_path;
+                },
+                Err(e) => println!("include_reproduction_path is not a boolean: {}", e)
+            }
+        }
+        if settings_map.contains_key("number_of_threads_for_batch_analysis") {
+            let number_of_threads_for_batch_analysis = Analysis::parse_i32(&settings_map["number_of_threads_for_batch_analysis"]);
+            match number_of_threads_for_batch_analysis {
+                Ok(ref number_of_threads_for_batch_analysis) => {
+                    println!("Set number_of_threads_for_batch_analysis to {}", number_of_threads_for_batch_analysis);
+                    self.number_of_threads_for_batch_analysis = *number_of_threads_for_batch_analysis;
+                },
+                Err(e) => println!("number_of_threads_for_batch_analysis is not an integer: {}", e)
+            }
+        }
+        if settings_map.contains_key("failure_proximity_weight") {
+            let failure_proximity_weight = Analysis::parse_f32(&settings_map["failure_proximity_weight"]);
+            match failure_proximity_weight {
+                Ok(failure_proximity_weight) => {
+                    println!("Set failure_proximity_weight to {}", failure_proximity_weight);
+                    self.failure_proximity_weight = failure_proximity_weight;
+                },
+                Err(er) => println!("{}", er)
+            }
+        }
+        if settings_map.contains_key("analyse_per_error_signature") {
+            let analyse_per_error_signature = Analysis::parse_bool(&settings_map["analyse_per_error_signature"]);
+            match analyse_per_error_signature {
+                Ok(ref analyse_per_error_signature) => {
+                    println!("Set analyse_per_error_signature to {}", analyse_per_error_signature);
+                    self.analyse_per_error_signature = *analyse_per_error_signature;
+                },
+                Err(e) => println!("analyse_per_error_signature is not a boolean: {}", e)
+            }
+        }
+        if settings_map.contains_key("training_include_tags") {
+            let training_include_tags = &settings_map["training_include_tags"];
+            match training_include_tags {
+                &Value::String(ref training_include_tags) => {
+                    println!("Set training_include_tags to {}", training_include_tags);
+                    self.training_include_tags = training_include_tags.to_string();
+                },
+                _ => println!("training_include_tags is not a string: {}", training_include_tags)
+            }
+        }
+        if settings_map.contains_key("training_exclude_tags") {
+            let training_exclude_tags = &settings_map["training_exclude_tags"];
+            match training_exclude_tags {
+                &Value::String(ref training_exclude_tags) => {
+                    println!("Set training_exclude_tags to {}", training_exclude_tags);
+                    self.training_exclude_tags = training_exclude_tags.to_string();
+                },
+                _ => println!("training_exclude_tags is not a string: {}", training_exclude_tags)
+            }
+        }
+        if settings_map.contains_key("flaky_test_policy") {
+            let flaky_test_policy = &settings_map["flaky_test_policy"];
+            match flaky_test_policy {
+                &Value::String(ref flaky_test_policy) => {
+                    println!("Set flaky_test_policy to {}", flaky_test_policy);
+                    self.flaky_test_policy = flaky_test_policy.to_string();
+                },
+                _ => println!("flaky_test_policy is not a string: {}", flaky_test_policy)
+            }
+        }
+        if settings_map.contains_key("flaky_test_weight") {
+            let flaky_test_weight = Analysis::parse_f32(&settings_map["flaky_test_weight"]);
+            match flaky_test_weight {
+                Ok(flaky_test_weight) => {
+                    println!("Set flaky_test_weight to {}", flaky_test_weight);
+                    self.flaky_test_weight = flaky_test_weight;
+                },
+                Err(er) => println!("{}", er)
+            }
+        }
+        if settings_map.contains_key("flaky_trace_similarity") {
+            let flaky_trace_similarity = Analysis::parse_f32(&settings_map["flaky_trace_similarity"]);
+            match flaky_trace_similarity {
+                Ok(flaky_trace_similarity) => {
+                    println!("Set flaky_trace_similarity to {}", flaky_trace_similarity);
+                    self.flaky_trace_similarity = flaky_trace_similarity;
+                },
+                Err(er) => println!("{}", er)
+            }
+        }
+        if settings_map.contains_key("model_diff_similarity") {
+            let model_diff_similarity = Analysis::parse_f32(&settings_map["model_diff_similarity"]);
+            match model_diff_similarity {
+                Ok(model_diff_similarity) => {
+                    println!("Set model_diff_similarity to {}", model_diff_similarity);
+                    self.model_diff_similarity = model_diff_similarity;
+                },
+                Err(er) => println!("{}", er)
+            }
+        }
+        if settings_map.contains_key("deduplicate_test_rows") {
+            let deduplicate_test_rows = Analysis::parse_bool(&settings_map["deduplicate_test_rows"]);
+            match deduplicate_test_rows {
+                Ok(ref deduplicate_test_rows) => {
+                    println!("Set deduplicate_test_rows to {}", deduplicate_test_rows);
+                    self.deduplicate_test_rows = *deduplicate_test_rows;
+                },
+                Err(e) => println!("deduplicate_test_rows is not a boolean: {}", e)
+            }
+        }
+        if settings_map.contains_key("count_duplicate_test_rows") {
+            let count_duplicate_test_rows = Analysis::parse_bool(&settings_map["count_duplicate_test_rows"]);
+            match count_duplicate_test_rows {
+                Ok(ref count_duplicate_test_rows) => {
+                    println!("Set count_duplicate_test_rows to {}", count_duplicate_test_rows);
+                    self.count_duplicate_test_rows = *count_duplicate_test_rows;
+                },
+                Err(e) => println!("count_duplicate_test_rows is not a boolean: {}", e)
+            }
+        }
     }
 
     ///Parse a float or give an error message
@@ -132,7 +340,7 @@ impl Analysis {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Settings {
     pub debug: bool,
     pub analysis: Analysis,
@@ -172,6 +380,7 @@ impl Settings {
                     debug: false,
                     analysis: Analysis {
                         similarity_threshold: 0.75,
+                        similarity_coefficient: String::from("ochiai"),
                         progress_endpoint: String::from("http://localhost/analyse/train/progress"),
                         use_thread_for_progress: false,
                         use_thread_for_training: false,
@@ -179,6 +388,24 @@ impl Settings {
                         use_steps_instead_of_transitions_for_analysis: false,
                         use_transition_data: false,
                         return_highest_similarity_if_nothing_found: false,
+                        incremental_training: false,
+                        export_path: String::from("output.csv"),
+                        export_format: String::from("csv"),
+                        threshold_calibration: String::from("none"),
+                        threshold_calibration_fraction: 0.1,
+                        include_explanation: false,
+                        include_reproduction_path: false,
+                        number_of_threads_for_batch_analysis: 1,
+                        failure_proximity_weight: 0.0,
+                        analyse_per_error_signature: false,
+                        training_include_tags: String::new(),
+                        training_exclude_tags: String::new(),
+                        flaky_test_policy: String::from("report"),
+                        flaky_test_weight: 0.5,
+                        flaky_trace_similarity: 1.0,
+                        deduplicate_test_rows: false,
+                        count_duplicate_test_rows: true,
+                        model_diff_similarity: 0.5,
                     },
                 }
             }
@@ -2,20 +2,16 @@ extern crate rulinalg;
 
 use models::model::TestModel;
 use models::test_case::TestCase;
-use std::sync::Mutex;
-use std::sync::MutexGuard;
+use models::test_case::TestResult;
 use models::coverage_matrix::CoverageMatrix;
-
-lazy_static! {
-    static ref STORAGE_SERVICE: Mutex<StorageService> = Mutex::new(StorageService::new());
-}
-
-pub fn get_storage_service<'a>() -> MutexGuard<'a, StorageService> {
-    return match STORAGE_SERVICE.lock() {
-        Ok(service) => service,
-        Err(poisoned) => poisoned.into_inner()
-    };
-}
+use std::collections::HashMap;
+use std::collections::HashSet;
+use models::threshold_calibration::ThresholdCalibration;
+use models::tag_filter::TagFilter;
+use models::flakiness::FlakyTrace;
+use uuid::Uuid;
+use models::model_diff::ModelDiff;
+use models::model_diff::ModelRemapResult;
 
 pub struct StorageService {
     pub model: Option<TestModel>,
@@ -23,6 +19,17 @@ pub struct StorageService {
     pub passing: Vec<TestCase>,
     pub coverage_information: Vec<TestModel>,
     pub coverage_matrix: Option<CoverageMatrix>,
+    pub threshold_calibration: Option<ThresholdCalibration>,
+    ///The coverage matrices of tagged subsets of the tests, by the key of their tag filter. They are cleared when the coverage matrix changes.
+    pub tagged_coverage_matrices: HashMap<String, CoverageMatrix>,
+    ///The traces that both passed and failed during the last training
+    pub flaky_traces: Vec<FlakyTrace>,
+    ///The user-supplied weights of the tests, by test id, which are applied during training
+    pub test_weights: HashMap<i64, f32>,
+    ///The tests that were submitted as part of a test run, by the id of the test run. They are kept per run, because the runs can use the same test ids.
+    pub test_runs: HashMap<Uuid, Vec<TestCase>>,
+    ///The coverage information that could not be translated to the current model, which is no longer used for training
+    pub stale_coverage_information: Vec<TestModel>,
 }
 
 impl StorageService {
@@ -33,6 +40,25 @@ impl StorageService {
             passing: vec![],
             coverage_information: vec![],
             coverage_matrix: None,
+            threshold_calibration: None,
+            tagged_coverage_matrices: HashMap::new(),
+            flaky_traces: vec![],
+            test_weights: HashMap::new(),
+            test_runs: HashMap::new(),
+            stale_coverage_information: vec![],
+        }
+    }
+
+    ///A copy of the data that the training is based on: the model, the tests, their coverage information, the test weights and the test runs
+    pub fn training_data(&self) -> StorageService {
+        StorageService {
+            model: self.model.clone(),
+            failing: self.failing.clone(),
+            passing: self.passing.clone(),
+            coverage_information: self.coverage_information.clone(),
+            test_weights: self.test_weights.clone(),
+            test_runs: self.test_runs.clone(),
+            ..StorageService::new()
         }
     }
 
@@ -54,6 +80,89 @@ impl StorageService {
 
     pub fn set_coverage_matrix(&mut self, coverage_matrix: CoverageMatrix){
         self.coverage_matrix = Some(coverage_matrix);
+        self.tagged_coverage_matrices.clear();
+    }
+
+    ///The coverage matrix with only the stored tests that match the tag filter. The matrix is cached, so the subsets of e.g. smoke and regression tests can be used side by side.
+    pub fn tagged_coverage_matrix(&mut self, tag_filter: &TagFilter) -> Option<CoverageMatrix> {
+        if tag_filter.is_empty() {
+            return self.coverage_matrix.clone();
+        }
+        let key = tag_filter.key();
+        if !self.tagged_coverage_matrices.contains_key(&key) {
+            let test_ids = self.passing.iter().chain(self.failing.iter()).filter(|it| tag_filter.matches(it)).filter_map(|it| it.id).collect::<HashSet<i64>>();
+            let tagged_coverage_matrix = match self.coverage_matrix {
+                Some(ref coverage_matrix) => coverage_matrix.for_test_ids(&test_ids),
+                None => return None
+            };
+            self.tagged_coverage_matrices.insert(key.clone(), tagged_coverage_matrix);
+        }
+        self.tagged_coverage_matrices.get(&key).cloned()
+    }
+
+    ///Add a single test to the coverage matrix. If there is no coverage matrix yet, an empty one is created first.
+    pub fn add_test_to_coverage_matrix(&mut self, test_id: i64, errored: bool, covered_components: &HashSet<String>) {
+        if self.coverage_matrix.is_none() {
+            self.coverage_matrix = Some(CoverageMatrix::new(vec![], vec![], HashMap::new()));
+        }
+        if let Some(ref mut coverage_matrix) = self.coverage_matrix {
+            coverage_matrix.add_test(test_id, errored, covered_components);
+        }
+        self.tagged_coverage_matrices.clear();
+    }
+
+    ///Replace the model by a new version and translate the stored coverage information to the new model.
+    /// Coverage information that refers to states or transitions without a counterpart in the new model is moved to the stale coverage information.
+    /// The coverage matrix was trained on the old model, so it is removed and has to be trained again.
+    pub fn replace_model(&mut self, model: TestModel, min_similarity: f32) -> ModelRemapResult {
+        let diff = match self.model {
+            Some(ref old_model) => ModelDiff::new(old_model, &model, min_similarity),
+            None => ModelDiff::default()
+        };
+        let mut result = ModelRemapResult { diff, remapped: 0, stale_test_ids: vec![] };
+        if self.model.is_some() {
+            let coverage_information = ::std::mem::replace(&mut self.coverage_information, vec![]);
+            for coverage in coverage_information {
+                match result.diff.remap(&coverage, &model) {
+                    Ok(remapped) => {
+                        self.coverage_information.push(remapped);
+                        result.remapped += 1;
+                    },
+                    Err(_) => {
+                        result.stale_test_ids.push(coverage.testcase_id);
+                        self.stale_coverage_information.push(coverage);
+                    }
+                }
+            }
+            self.coverage_matrix = None;
+            self.threshold_calibration = None;
+            self.tagged_coverage_matrices.clear();
+        }
+        self.set_model(Some(model));
+        result
+    }
+
+    ///Store the tests of a test run. The passing tests are added to the passing tests and all other tests to the failing tests, and they are kept with the run as well.
+    pub fn add_test_run(&mut self, test_run_id: Uuid, test_cases: Vec<TestCase>) {
+        for test_case in test_cases.iter() {
+            if test_case.verdict == Some(TestResult::passed) {
+                self.passing.push(test_case.clone());
+            } else {
+                self.failing.push(test_case.clone());
+            }
+        }
+        self.test_runs.entry(test_run_id).or_insert_with(Vec::new).extend(test_cases);
+    }
+
+    ///The tests of a test run. A run that was only submitted as coverage information has no tests of its own, so its tests are found by the test case ids of the coverage information.
+    pub fn test_cases_of_run(&self, test_run_id: &Uuid) -> Vec<TestCase> {
+        match self.test_runs.get(test_run_id) {
+            Some(test_cases) => test_cases.clone(),
+            None => {
+                let test_ids = self.coverage_information.iter().filter(|it| &it.test_run_id == test_run_id).filter_map(|it| it.testcase_id).collect::<HashSet<i64>>();
+                self.passing.iter().chain(self.failing.iter()).filter(|it| it.id.map(|id| test_ids.contains(&id)).unwrap_or(false)).cloned().collect()
+            }
+        }
     }
 
     ///Clear the model and make the passing and failing tests an empty vector. Also remove the coverage information
@@ -63,5 +172,52 @@ impl StorageService {
         self.set_failing_tests(vec![]);
         self.coverage_matrix = None;
         self.coverage_information = vec![];
+        self.threshold_calibration = None;
+        self.tagged_coverage_matrices.clear();
+        self.flaky_traces = vec![];
+        self.test_weights.clear();
+        self.test_runs.clear();
+        self.stale_coverage_information = vec![];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tagged_test(id: i64, verdict: TestResult, tags: &[&str]) -> TestCase {
+        TestCase::new(Some(id), Some(verdict), None, vec![], 0, None, Some(tags.iter().map(|it| it.to_string()).collect()))
+    }
+
+    #[test]
+    fn test_tagged_coverage_matrix() {
+        //Given
+        let mut storage_service = StorageService::new();
+        storage_service.set_passing_tests(vec![tagged_test(1, TestResult::passed, &["smoke"]), tagged_test(2, TestResult::passed, &["regression"])]);
+        let mut data = HashMap::new();
+        data.insert(String::from("a"), vec![true, false]);
+        storage_service.set_coverage_matrix(CoverageMatrix::new(vec![false, false], vec![1, 2], data));
+        let smoke = TagFilter::new("smoke", "");
+
+        //When
+        let tagged = storage_service.tagged_coverage_matrix(&smoke).unwrap();
+
+        //Then
+        assert_eq!(vec![1], *tagged.test_ids());
+        assert_eq!(1, storage_service.tagged_coverage_matrices.len());
+        assert_eq!(vec![1, 2], *storage_service.tagged_coverage_matrix(&TagFilter::default()).unwrap().test_ids());
+
+        //A new test invalidates the cached matrices
+        storage_service.failing.push(tagged_test(3, TestResult::failed, &["smoke"]));
+        storage_service.add_test_to_coverage_matrix(3, true, &[String::from("a")].iter().cloned().collect());
+        assert!(storage_service.tagged_coverage_matrices.is_empty());
+        assert_eq!(vec![1, 3], *storage_service.tagged_coverage_matrix(&smoke).unwrap().test_ids());
+
+        //So does a new coverage matrix
+        let mut data = HashMap::new();
+        data.insert(String::from("a"), vec![true, true]);
+        storage_service.set_coverage_matrix(CoverageMatrix::new(vec![false, true], vec![2, 3], data));
+        assert!(storage_service.tagged_coverage_matrices.is_empty());
+        assert_eq!(vec![3], *storage_service.tagged_coverage_matrix(&smoke).unwrap().test_ids());
     }
 }
\ No newline at end of file
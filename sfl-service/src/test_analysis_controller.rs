@@ -6,16 +6,89 @@ pub mod analysis_controller {
     use rocket_contrib::Json;
     use rocket::Rocket;
     use models::analysis_result::AnalysisResult;
-    use analysis_service::get_analysis_service;
+    use app_context::Context;
+    use evaluation_service;
+    use evaluation_service::EvaluationRequest;
+    use evaluation_service::EvaluationResult;
+    use batch_analysis_service;
+    use batch_analysis_service::BatchAnalysisResult;
+    use clustering_service;
+    use clustering_service::ClusteringRequest;
+    use clustering_service::ClusteringResult;
+    use std::cmp::max;
+    use models::tag_filter::TagFilter;
+    use run_comparison_service;
+    use run_comparison_service::RunComparisonRequest;
+    use run_comparison_service::RunComparison;
+    use rocket::response::status::BadRequest;
+    use minimisation_service;
+    use minimisation_service::MinimisationRequest;
+    use minimisation_service::MinimisationResult;
 
     pub fn mount(rocket_instance: Rocket) -> Rocket {
-        rocket_instance.mount("/data", routes![testcase])
+        rocket_instance.mount("/data", routes![testcase, tagged_testcase, batch, tagged_batch, stored, cluster, evaluate, compare, minimise])
     }
 
-    #[post("/analyse", format = "application/json", data = "<testcase>")]
-    pub fn testcase(testcase: Json<TestCase>) -> Json<AnalysisResult> {
-        let service = get_analysis_service().unwrap();
-        return Json(service.analyse(testcase.into_inner()).unwrap());
+    #[post("/analyse", format = "application/json", data = "<testcase>", rank = 2)]
+    pub fn testcase(context: Context, testcase: Json<TestCase>) -> Json<AnalysisResult> {
+        let service = context.analysis_service();
+        return Json(service.analyse(&context, testcase.into_inner()).unwrap());
+    }
+
+    ///Analyse a test case against the spectra of only the tests with the given tags, e.g. `/analyse?include=smoke&exclude=flaky`
+    #[post("/analyse?<tags>", format = "application/json", data = "<testcase>")]
+    pub fn tagged_testcase(context: Context, testcase: Json<TestCase>, tags: TagFilter) -> Json<AnalysisResult> {
+        let service = context.analysis_service();
+        let testcase = testcase.into_inner();
+        let mut storage_service = context.storage();
+        let coverage_model = testcase.to_coverage_model(&storage_service.coverage_information);
+        let coverage_matrix = storage_service.tagged_coverage_matrix(&tags);
+        return Json(service.analyse_with(&context, testcase, &storage_service.model, &coverage_matrix, coverage_model, &storage_service.threshold_calibration).unwrap());
+    }
+
+    ///Analyse a list of test cases in one pass and summarise the groups they end up in
+    #[post("/analyse/batch", format = "application/json", data = "<testcases>", rank = 2)]
+    pub fn batch(context: Context, testcases: Json<Vec<TestCase>>) -> Json<BatchAnalysisResult> {
+        tagged_batch(context, testcases, TagFilter::default())
+    }
+
+    ///Analyse a list of test cases in one pass against the spectra of only the tests with the given tags
+    #[post("/analyse/batch?<tags>", format = "application/json", data = "<testcases>")]
+    pub fn tagged_batch(context: Context, testcases: Json<Vec<TestCase>>, tags: TagFilter) -> Json<BatchAnalysisResult> {
+        let number_of_threads = context.settings().analysis.number_of_threads_for_batch_analysis;
+        return Json(batch_analysis_service::analyse_batch(&context, testcases.into_inner(), &tags, max(number_of_threads, 1) as usize));
+    }
+
+    ///Analyse all stored failing test cases in one pass and summarise the groups they end up in
+    #[post("/analyse/stored")]
+    pub fn stored(context: Context) -> Json<BatchAnalysisResult> {
+        let number_of_threads = context.settings().analysis.number_of_threads_for_batch_analysis;
+        let failing = context.storage().failing.clone();
+        return Json(batch_analysis_service::analyse_batch(&context, failing, &TagFilter::default(), max(number_of_threads, 1) as usize));
+    }
+
+    ///Cluster failing tests in stable buckets, based on their suspiciousness vectors, error messages and last steps
+    #[post("/cluster", format = "application/json", data = "<request>")]
+    pub fn cluster(context: Context, request: Json<ClusteringRequest>) -> Json<ClusteringResult> {
+        return Json(clustering_service::cluster(&context, &request.into_inner()));
+    }
+
+    ///Evaluate the quality of the diagnosis for test cases that are annotated with their actual fault, for every requested combination of settings
+    #[post("/evaluate", format = "application/json", data = "<request>")]
+    pub fn evaluate(context: Context, request: Json<EvaluationRequest>) -> Json<Vec<EvaluationResult>> {
+        return Json(evaluation_service::evaluate(&context, &request.into_inner()));
+    }
+
+    ///Compare a candidate test run with a baseline test run: which components became more suspicious, which failure groups appeared or disappeared and which transitions lost coverage
+    #[post("/compare", format = "application/json", data = "<request>")]
+    pub fn compare(context: Context, request: Json<RunComparisonRequest>) -> Result<Json<RunComparison>, BadRequest<String>> {
+        return run_comparison_service::compare(&context, &request.into_inner()).map(Json).map_err(|e| BadRequest(Some(e)));
+    }
+
+    ///Minimise a failing test case to a shorter test case that still ends with the failing step and covers its most suspicious components, which can be replayed
+    #[post("/minimise", format = "application/json", data = "<request>")]
+    pub fn minimise(context: Context, request: Json<MinimisationRequest>) -> Result<Json<MinimisationResult>, BadRequest<String>> {
+        return minimisation_service::minimise(&context, &request.into_inner()).map(Json).map_err(|e| BadRequest(Some(e)));
     }
 }
 
@@ -36,11 +109,11 @@ mod tests {
     use models::model::TransitionAttribute;
     use models::analysis_result::AnalysisResult;
     use rocket_contrib::json::Json;
-    use analysis_service::set_analysis_service;
     use analysis_service::tests::MockAnalysisService;
     use models::test_case::ExpectedLabel;
-
-    pub static mut SERVICE_MOCK: MockAnalysisService = MockAnalysisService { next_result: None };
+    use app_context::AppContext;
+    use app_context::Context;
+    use std::sync::Arc;
 
     #[test]
     fn check_testcase() {
@@ -81,11 +154,10 @@ mod tests {
             )
         };
 
-        unsafe { //Unsafe because of static mutable variable SERVICE_MOCK. Because this is mutable, one thread could be updating SERVICE_MOCK while another is reading it, causing memory unsafety.
-            set_analysis_service(&SERVICE_MOCK);
-            SERVICE_MOCK.next_result = Some(expected_result.clone());
-        }
+        //Every context has its own analysis service, so the mock is not shared with other tests
+        let context = AppContext::from_settings_file();
+        context.set_analysis_service(Arc::new(MockAnalysisService { next_result: Some(expected_result.clone()) }));
 
-        assert_eq!(testcase(Json(test_testcase)).into_inner(), expected_result);
+        assert_eq!(testcase(Context::new(&context), Json(test_testcase)).into_inner(), expected_result);
     }
 }
\ No newline at end of file
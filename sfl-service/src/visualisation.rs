@@ -0,0 +1,292 @@
+use analysis_service::configured_similarity_coefficient;
+use models::analysis_result::AnalysisResult;
+use models::coverage_matrix::CoverageMatrix;
+use models::model::Sts;
+use models::model::TestModel;
+use models::model::Transition;
+use models::similarity::ComponentCounts;
+use models::similarity::SimilarityCoefficient;
+use serde_json;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::Read;
+use settings::Settings;
+
+///The suspiciousness information that is drawn on top of the model
+pub struct Heatmap<'a> {
+    pub coverage_matrix: Option<&'a CoverageMatrix>,
+    pub coefficient: SimilarityCoefficient,
+    ///The coverage information of a single test. If it is given, only the transitions that were covered by this test are coloured.
+    pub coverage_model: Option<&'a TestModel>,
+    ///The analysis result of a single test, of which the root cause is highlighted
+    pub analysis_result: Option<&'a AnalysisResult>,
+    ///Whether the components of the coverage matrix are steps instead of transitions
+    pub use_steps: bool,
+    ///Whether the labels of the components include the data of the transitions
+    pub use_transition_data: bool,
+}
+
+impl<'a> Heatmap<'a> {
+    pub fn new(coverage_matrix: Option<&'a CoverageMatrix>, coefficient: SimilarityCoefficient) -> Heatmap<'a> {
+        Heatmap { coverage_matrix, coefficient, coverage_model: None, analysis_result: None, use_steps: false, use_transition_data: false }
+    }
+
+    ///A heatmap with the similarity coefficient and the components of the coverage matrix as configured in the settings
+    pub fn from_settings(coverage_matrix: Option<&'a CoverageMatrix>, settings: &Settings) -> Heatmap<'a> {
+        let mut heatmap = Heatmap::new(coverage_matrix, configured_similarity_coefficient(settings));
+        heatmap.use_steps = settings.analysis.use_steps_instead_of_transitions_for_analysis;
+        heatmap.use_transition_data = settings.analysis.use_transition_data;
+        heatmap
+    }
+
+    ///Whether the transition was covered, according to the coverage information of the test or otherwise the model itself
+    pub fn covered(&self, transition: &Transition) -> Option<bool> {
+        match self.coverage_model {
+            Some(coverage_model) => Some(coverage_model.all_transitions().iter().any(|it| it == transition && it.attributes.covered.unwrap_or(false))),
+            None => transition.attributes.covered
+        }
+    }
+
+    ///The coefficient of the component of the transition in the coverage matrix, if the transition should be coloured
+    pub fn coefficient(&self, transition: &Transition) -> Option<f32> {
+        if self.coverage_model.is_some() && self.covered(transition) != Some(true) {
+            return None;
+        }
+        let component = if self.use_steps { transition.attributes.label.clone() } else { transition.to_string_with_data(self.use_transition_data) };
+        self.coverage_matrix.and_then(|coverage_matrix| ComponentCounts::from_matrix(&component, coverage_matrix)).map(|it| it.similarity(&self.coefficient))
+    }
+}
+
+///Render the model as a Graphviz DOT graph, with a subgraph for every STS (the main behaviour and every child behaviour).
+/// Transitions are coloured from green to red by their coefficient and states by the highest coefficient of their transitions.
+/// Uncovered transitions are dashed and grey, and the root cause of the analysis result is drawn with a thick red line.
+pub fn model_to_dot(model: &TestModel, heatmap: &Heatmap) -> String {
+    let (transition_coefficients, state_coefficients) = coefficients(model, heatmap);
+    let root_cause_state = heatmap.analysis_result.and_then(|it| it.root_cause_state()).map(|it| it.id.clone());
+    let root_cause_transition = heatmap.analysis_result.and_then(|it| it.root_cause_transition());
+
+    let mut dot = String::from("digraph model {\n    rankdir=LR;\n    node [shape=circle, style=filled, fillcolor=white];\n");
+    for (index, sts) in model.stss.iter().enumerate() {
+        dot.push_str(&format!("    subgraph cluster_{} {{\n        label=\"{}\";\n", index, escape(&sts_label(sts, model))));
+        for state in sts.states.iter() {
+            let mut attributes = vec![format!("label=\"{}\"", escape(state.label()))];
+            if let Some(coefficient) = state_coefficients.get(&state.id) {
+                attributes.push(format!("fillcolor=\"{}\"", heat_colour(*coefficient)));
+                attributes.push(format!("tooltip=\"{:.2}\"", coefficient));
+            }
+            if state.covered() == Some(false) {
+                attributes.push(String::from("style=\"filled,dashed\""));
+            }
+            if root_cause_state.as_ref() == Some(&state.id) {
+                attributes.push(String::from("color=red, penwidth=3"));
+            }
+            dot.push_str(&format!("        \"{}\" [{}];\n", escape(&state.id), attributes.join(", ")));
+        }
+        dot.push_str("    }\n");
+    }
+    for &(ref transition, coefficient) in transition_coefficients.iter() {
+        let mut attributes = vec![];
+        match coefficient {
+            Some(coefficient) => {
+                attributes.push(format!("label=\"{}\\n{:.2}\"", escape(&transition.attributes.label), coefficient));
+                attributes.push(format!("color=\"{}\"", heat_colour(coefficient)));
+            },
+            None => attributes.push(format!("label=\"{}\"", escape(&transition.attributes.label)))
+        }
+        if heatmap.covered(transition) == Some(false) {
+            attributes.push(String::from("style=dashed, color=gray60, fontcolor=gray60"));
+        }
+        if root_cause_transition == Some(transition) {
+            attributes.push(String::from("penwidth=4"));
+        }
+        dot.push_str(&format!("    \"{}\" -> \"{}\" [{}];\n", escape(&transition.source), escape(&transition.target), attributes.join(", ")));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+///Render the model as an SVG image, for places where Graphviz is not available. The states of every STS are drawn on a circle and the STSs are placed in a grid.
+/// The colours and styles are the same as in the DOT graph, and the labels and coefficients are shown as tooltips.
+pub fn model_to_svg(model: &TestModel, heatmap: &Heatmap) -> String {
+    let (transition_coefficients, state_coefficients) = coefficients(model, heatmap);
+    let root_cause_state = heatmap.analysis_result.and_then(|it| it.root_cause_state()).map(|it| it.id.clone());
+    let root_cause_transition = heatmap.analysis_result.and_then(|it| it.root_cause_transition());
+
+    //Every STS gets a square cell that fits the circle of the largest STS
+    let largest = model.stss.iter().map(|it| it.states.len()).max().unwrap_or(0) as f32;
+    let radius = (largest * 24.0 / (2.0 * PI)).max(40.0);
+    let cell_size = 2.0 * radius + 80.0;
+    let columns = (model.stss.len() as f32).sqrt().ceil().max(1.0) as usize;
+    let rows = (model.stss.len() + columns - 1) / columns;
+    let mut positions: HashMap<&str, (f32, f32)> = HashMap::new();
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" font-family=\"sans-serif\" font-size=\"12\">\n", columns as f32 * cell_size, rows as f32 * cell_size);
+    svg.push_str("<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto\"><path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"#666\"/></marker></defs>\n");
+    for (index, sts) in model.stss.iter().enumerate() {
+        let center = ((index % columns) as f32 * cell_size + cell_size / 2.0, (index / columns) as f32 * cell_size + cell_size / 2.0);
+        svg.push_str(&format!("<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"none\" stroke=\"#ddd\"/>\n", center.0, center.1, radius + 20.0));
+        svg.push_str(&format!("<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>\n", center.0, center.1 - radius - 26.0, escape_xml(&sts_label(sts, model))));
+        for (state_index, state) in sts.states.iter().enumerate() {
+            let angle = 2.0 * PI * state_index as f32 / sts.states.len() as f32;
+            positions.insert(state.id.as_str(), (center.0 + radius * angle.cos(), center.1 + radius * angle.sin()));
+        }
+    }
+    for &(ref transition, coefficient) in transition_coefficients.iter() {
+        let (source, target) = match (positions.get(transition.source.as_str()), positions.get(transition.target.as_str())) {
+            (Some(source), Some(target)) => (*source, *target),
+            _ => continue
+        };
+        let colour = if heatmap.covered(transition) == Some(false) { String::from("#999") } else { coefficient.map(|it| heat_rgb(it)).unwrap_or(String::from("#666")) };
+        let dashes = if heatmap.covered(transition) == Some(false) { " stroke-dasharray=\"4 3\"" } else { "" };
+        let width = if root_cause_transition == Some(transition) { 4 } else { 1 };
+        let title = match coefficient {
+            Some(coefficient) => format!("{} ({:.2})", transition.attributes.label, coefficient),
+            None => transition.attributes.label.clone()
+        };
+        if transition.source == transition.target {
+            svg.push_str(&format!("<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"8\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"{}><title>{}</title></circle>\n", source.0, source.1 - 12.0, colour, width, dashes, escape_xml(&title)));
+            continue;
+        }
+        //Stop the line at the border of the target state, so the arrow is visible
+        let length = ((target.0 - source.0).powi(2) + (target.1 - source.1).powi(2)).sqrt().max(1.0);
+        let end = (target.0 - (target.0 - source.0) * 8.0 / length, target.1 - (target.1 - source.1) * 8.0 / length);
+        svg.push_str(&format!("<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-width=\"{}\"{} marker-end=\"url(#arrow)\"><title>{}</title></line>\n", source.0, source.1, end.0, end.1, colour, width, dashes, escape_xml(&title)));
+    }
+    for sts in model.stss.iter() {
+        for state in sts.states.iter() {
+            let position = positions[state.id.as_str()];
+            let fill = state_coefficients.get(&state.id).map(|it| heat_rgb(*it)).unwrap_or(String::from("#fff"));
+            let stroke = if root_cause_state.as_ref() == Some(&state.id) { "stroke=\"red\" stroke-width=\"3\"" } else { "stroke=\"#333\"" };
+            let dashes = if state.covered() == Some(false) { " stroke-dasharray=\"2 2\"" } else { "" };
+            let title = match state_coefficients.get(&state.id) {
+                Some(coefficient) => format!("{} ({:.2})", state.label(), coefficient),
+                None => state.label().clone()
+            };
+            svg.push_str(&format!("<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"8\" fill=\"{}\" {}{}><title>{}</title></circle>\n", position.0, position.1, fill, stroke, dashes, escape_xml(&title)));
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+///The coefficient of every transition that should be coloured, and for every state the highest coefficient of its transitions
+fn coefficients(model: &TestModel, heatmap: &Heatmap) -> (Vec<(Transition, Option<f32>)>, HashMap<String, f32>) {
+    let transition_coefficients = model.all_transitions().into_iter().map(|it| {
+        let coefficient = heatmap.coefficient(&it);
+        (it, coefficient)
+    }).collect::<Vec<(Transition, Option<f32>)>>();
+    let mut state_coefficients: HashMap<String, f32> = HashMap::new();
+    for &(ref transition, coefficient) in transition_coefficients.iter() {
+        if let Some(coefficient) = coefficient {
+            for state in vec![&transition.source, &transition.target] {
+                let state_coefficient = state_coefficients.entry(state.clone()).or_insert(coefficient);
+                *state_coefficient = state_coefficient.max(coefficient);
+            }
+        }
+    }
+    (transition_coefficients, state_coefficients)
+}
+
+///The name of the STS, together with the id of the child behaviour that it is an instance of
+fn sts_label(sts: &Sts, model: &TestModel) -> String {
+    match model.child_of(sts) {
+        Some(child) => format!("{} ({})", sts.name(), child.id()),
+        None => sts.name().clone()
+    }
+}
+
+///A colour from green (0) to red (1) as a Graphviz HSV colour
+fn heat_colour(coefficient: f32) -> String {
+    let coefficient = coefficient.max(0.0).min(1.0);
+    format!("{:.3} 0.800 0.900", (1.0 - coefficient) / 3.0)
+}
+
+///A colour from green (0) to red (1) as a CSS colour
+pub fn heat_rgb(coefficient: f32) -> String {
+    let coefficient = coefficient.max(0.0).min(1.0);
+    format!("hsl({:.0}, 75%, 50%)", (1.0 - coefficient) * 120.0)
+}
+
+///Escape text for use in HTML and SVG
+pub fn escape_xml(text: &str) -> String {
+    text.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;").replace("\"", "&quot;")
+}
+
+fn escape(text: &str) -> String {
+    text.replace("\\", "\\\\").replace("\"", "\\\"")
+}
+
+///Render a model from files, as used on the command line: `<model.json> [--matrix <matrix.arff>] [--coverage <coverage.json>]`.
+/// The coverage matrix gives the colours and the coverage information of a single test limits them to that test.
+pub fn dot_from_arguments(settings: &Settings, arguments: &[String]) -> Result<String, String> {
+    let model_path = arguments.first().ok_or(String::from("Usage: --model-dot <model.json> [--matrix <matrix.arff>] [--coverage <coverage.json>]"))?;
+    let model: TestModel = serde_json::from_str(&read_file(model_path)?).map_err(|e| format!("Could not parse the model {}: {}", model_path, e))?;
+    let mut coverage_matrix = None;
+    let mut coverage_model = None;
+    let mut options = arguments[1..].iter();
+    while let Some(option) = options.next() {
+        let path = options.next().ok_or(format!("Missing the file after {}", option))?;
+        match option.as_str() {
+            "--matrix" => coverage_matrix = Some(CoverageMatrix::from_arff(&read_file(path)?)?),
+            "--coverage" => coverage_model = Some(serde_json::from_str::<TestModel>(&read_file(path)?).map_err(|e| format!("Could not parse the coverage information {}: {}", path, e))?),
+            _ => return Err(format!("Unknown option {}", option))
+        }
+    }
+    let mut heatmap = Heatmap::from_settings(coverage_matrix.as_ref(), settings);
+    heatmap.coverage_model = coverage_model.as_ref();
+    Ok(model_to_dot(&model, &heatmap))
+}
+
+pub fn read_file(path: &str) -> Result<String, String> {
+    let mut contents = String::new();
+    File::open(path).and_then(|mut file| file.read_to_string(&mut contents)).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::model::State;
+    use models::model::StateAttribute;
+    use models::model::TransitionAttribute;
+    use uuid::Uuid;
+
+    #[test]
+    fn check_model_to_dot() {
+        //Given
+        let state = |id: &str| State::new(id.to_string(), StateAttribute::new(id.to_string(), String::from("state"), Some(true)));
+        let faulty = Transition::new(String::from("s0"), String::from("s1"), TransitionAttribute::new(String::from("?a"), None, Some(true)));
+        let uncovered = Transition::new(String::from("s1"), String::from("s0"), TransitionAttribute::new(String::from("!b"), None, Some(false)));
+        let model = TestModel::new(Uuid::nil(), vec![Sts::new(String::from("main"), vec![state("s0"), state("s1")], vec![], vec![], vec![faulty.clone(), uncovered], vec![], None, None, None)], None);
+        let mut data = HashMap::new();
+        data.insert(faulty.to_string_with_data(false), vec![true, false]);
+        let matrix = CoverageMatrix::new(vec![true, false], vec![1, 2], data);
+
+        //When
+        let dot = model_to_dot(&model, &Heatmap::new(Some(&matrix), SimilarityCoefficient::ochiai));
+
+        //Then
+        assert!(dot.starts_with("digraph model {"));
+        assert!(dot.contains("subgraph cluster_0 {\n        label=\"main\";"));
+        assert!(dot.contains("\"s0\" -> \"s1\" [label=\"?a\\n1.00\", color=\"0.000 0.800 0.900\"];"));
+        assert!(dot.contains("\"s1\" -> \"s0\" [label=\"!b\", style=dashed, color=gray60, fontcolor=gray60];"));
+        assert!(dot.contains("\"s0\" [label=\"s0\", fillcolor=\"0.000 0.800 0.900\", tooltip=\"1.00\"];"));
+    }
+
+    #[test]
+    fn check_model_to_svg() {
+        //Given
+        let state = |id: &str| State::new(id.to_string(), StateAttribute::new(id.to_string(), String::from("state"), Some(true)));
+        let transition = Transition::new(String::from("s0"), String::from("s1"), TransitionAttribute::new(String::from("?a<b>"), None, Some(false)));
+        let model = TestModel::new(Uuid::nil(), vec![Sts::new(String::from("main"), vec![state("s0"), state("s1")], vec![], vec![], vec![transition], vec![], None, None, None)], None);
+
+        //When
+        let svg = model_to_svg(&model, &Heatmap::new(None, SimilarityCoefficient::ochiai));
+
+        //Then
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(2, svg.matches("r=\"8\" fill=\"#fff\"").count());
+        assert!(svg.contains("stroke=\"#999\" stroke-width=\"1\" stroke-dasharray=\"4 3\" marker-end=\"url(#arrow)\"><title>?a&lt;b&gt;</title>"));
+    }
+}